@@ -59,6 +59,11 @@ data class InferenceResult(
     }
 }
 
+/** Receives the result JSON of every completed inference, on the thread that ran it */
+fun interface InferenceResultListener {
+    fun onInference(json: String)
+}
+
 class OnnxInference private constructor() {
     
     companion object {
@@ -208,11 +213,14 @@ class OnnxInference private constructor() {
 
         return try {
             // Simple JSON parsing for the prediction format
-            // Format: [{"class_id":123,"class_name":"dog","confidence":0.95}, ...]
+            // Format: {"schema_version":N,"predictions":[{"class_id":123,"class_name":"dog","confidence":0.95}, ...]}
             val predictions = mutableListOf<ClassificationResult>()
 
-            // Remove brackets and split by objects
-            val cleanJson = json.trim().removePrefix("[").removeSuffix("]")
+            // Keep only the predictions array, then remove brackets and split by objects
+            val start = json.indexOf('[')
+            val end = json.lastIndexOf(']')
+            if (start < 0 || end < start) return emptyList()
+            val cleanJson = json.substring(start + 1, end).trim()
             if (cleanJson.isEmpty()) return emptyList()
 
             val objects = cleanJson.split("},{").map {
@@ -254,22 +262,164 @@ class OnnxInference private constructor() {
     private external fun runInferenceNative(
         imageBytes: ByteArray,
     ): FloatArray?
+    private external fun runInferenceOnRoiNative(
+        imageBytes: ByteArray,
+        x: Int,
+        y: Int,
+        width: Int,
+        height: Int,
+    ): FloatArray?
+    private external fun runInferenceIntoNative(
+        imageBytes: ByteArray,
+        outBuffer: FloatArray,
+    ): Int
+    private external fun runInferenceTensorNative(
+        tensor: FloatArray,
+        shape: IntArray,
+    ): FloatArray?
+    private external fun runInferenceWithDeadlineNative(
+        imageBytes: ByteArray,
+        deadlineMs: Long,
+    ): FloatArray?
+    private external fun runInferencePureNative(imageBytes: ByteArray): String?
+    private external fun classifyNative(imageBytes: ByteArray): String?
+    private external fun runInferenceTtaNative(
+        imageBytes: ByteArray,
+        mode: Int,
+    ): FloatArray?
+    private external fun runInferenceByIndexNative(
+        modelIndex: Int,
+        imageBytes: ByteArray,
+    ): FloatArray?
+    private external fun runEnsembleNative(
+        modelIndices: IntArray,
+        imageBytes: ByteArray,
+    ): FloatArray?
+    private external fun setEnsembleWeightsNative(weights: FloatArray): Int
+    private external fun runInferenceOnPixelsNative(
+        pixels: ByteArray,
+        width: Int,
+        height: Int,
+        pixelFormat: Int,
+    ): FloatArray?
     private external fun isClassificationNative(): Boolean
+    private external fun validateInputNative(imageBytes: ByteArray): String
+
+    // Polling API: start a background job, poll its JSON status, release it when done
+    private external fun startInferenceNative(imageBytes: ByteArray): Int
+    private external fun startInferenceWithRequestIdNative(imageBytes: ByteArray, requestId: Long): Int
+    private external fun pollInferenceNative(jobId: Int): String
+    private external fun releaseJobNative(jobId: Int): Int
+    private external fun registerResultListenerNative(listener: InferenceResultListener): Int
+    private external fun unregisterResultListenerNative(): Int
+
+    private external fun modelLooksLikeClassifierNative(): Boolean
+    private external fun getNumClassesNative(): Int
     private external fun getOutputShapeNative(): IntArray?
+    private external fun getOutputShapesJsonNative(): String?
     private external fun getTopPredictionsJsonNative(): String?
+    private external fun getTopPredictionsFormattedNative(): Array<String>?
+    private external fun getTopGroupsNative(k: Int): String?
+    private external fun getTopPredictionsPerHeadJsonNative(): String?
+    private external fun getDetectionsJsonNative(): String?
+    private external fun getTopIndicesNative(k: Int): IntArray?
+    private external fun getTopScoresNative(k: Int): FloatArray?
+    private external fun getLogitsNative(): FloatArray?
+    private external fun getRawOutputBase64Native(): String?
+    private external fun getProbabilitiesNative(): FloatArray?
+    private external fun exportDistributionCsvNative(outPath: String): String
+    // Requires the native library to be built with the `flatbuffers` feature
+    private external fun getLastResultFlatbufferNative(): ByteArray?
     private external fun getLastError(): String
+    private external fun getLastErrorJsonNative(): String?
     private external fun loadImageNetLabelsNative(labelsPath: String): String
+    private external fun loadLabelsFromBytesNative(content: ByteArray): String
+    private external fun replaceLabelsAtomicNative(content: ByteArray): Int
+    private external fun getLabelWarningNative(): String
+    private external fun getLastWarningsNative(): String
+    private external fun getClassIndexByNameNative(name: String): Int
+    private external fun getPostprocessModeNative(): String?
+    private external fun setRegionPredictionsNative(on: Boolean)
+    private external fun getRegionPredictionsJsonNative(): String?
+    private external fun getLabelResolutionStatusNative(): String
+    private external fun setStrictLabelsNative(strict: Boolean)
+    private external fun setExpectedClassCountNative(count: Int): Int
     
     // New session management methods
     private external fun loadModelNative(modelPath: String): String
+    private external fun loadModelWithProvidersNative(modelPath: String, providerIds: IntArray): String?
+    private external fun preloadModelsNative(manifestPath: String): String?
+    private external fun loadModelFromFdNative(fd: Int, offset: Long, length: Long): String
     private external fun isModelLoadedNative(): Boolean  
     private external fun getLoadedModelPathNative(): String
+    private external fun getLoadedModelsJsonNative(): String
+    private external fun clearCacheNative()
+    private external fun setKeepWarmNative(on: Boolean, intervalMs: Int): Int
+    private external fun getActiveProviderNative(): String
+    private external fun getModelInfoJsonNative(): String?
+    private external fun probeNnapiNative(): String
     
     // Timing methods
     private external fun getInferenceTimeNative(): Float
+    private external fun getTimingBreakdownJsonNative(): String?
     private external fun getPreprocessingTimeNative(): Float
     private external fun getPostprocessingTimeNative(): Float
     private external fun getTotalTimeNative(): Float
+    private external fun getJniTimeNative(): Float
+    private external fun getPredictionEntropyNative(): Float
+    private external fun getTopMarginNative(): Float
+    private external fun benchmarkNative(imageBytes: ByteArray, iterations: Int): String?
+    private external fun benchmarkModelOnlyNative(iterations: Int): String?
+    private external fun evaluateNative(manifestPath: String): String?
+    private external fun startTraceNative(path: String): Int
+    private external fun stopTraceNative(): Int
+
+    // Preprocessing configuration
+    private external fun setResizeModeNative(mode: Int, size: Int): Int
+    private external fun setNormalizationPresetNative(preset: Int): Int
+    private external fun setNormalizationNative(mean: FloatArray, std: FloatArray, scale: Float): Int
+    private external fun setQuantParamsNative(scale: Float, zeroPoint: Int): Int
+    private external fun setMaxImageSizeNative(maxPixels: Int): Int
+    private external fun setMinImageQualityNative(minDim: Int): Int
+    private external fun setAlphaBackgroundNative(background: Int): Int
+    private external fun setDataOrderNative(order: Int): Int
+    private external fun setPerImageStandardizationNative(on: Boolean)
+    private external fun setPreprocessPipelineNative(json: String): Int
+    private external fun getLetterboxInfoNative(): FloatArray?
+    private external fun getOriginalImageSizeNative(): IntArray?
+    private external fun getLastInputStatsNative(): String?
+    private external fun setInputCaptureNative(on: Boolean)
+    private external fun saveDebugInputNative(outPath: String): String
+
+    // Postprocessing configuration
+    private external fun setClassAllowlistNative(classIds: IntArray): Int
+    private external fun setConfidenceFormatNative(format: Int): Int
+    private external fun setTimingPrecisionNative(decimals: Int): Int
+    private external fun setRequestedOutputsNative(names: Array<String>): Int
+    private external fun setMaxSoftmaxClassesNative(n: Int): Int
+    private external fun setMaxReturnedElementsNative(n: Int, subset: Int): Int
+    private external fun setCalibrationNative(temperature: Float, classBias: FloatArray): Int
+    private external fun setDetectionThresholdsNative(confThreshold: Float, iouThreshold: Float): Int
+    private external fun setDetectionClassFilterNative(classes: IntArray): Int
+    private external fun setClassGroupsNative(classToGroup: IntArray, groupNames: Array<String>): Int
+    private external fun setClassificationHeadsNative(headOffsets: IntArray, headSizes: IntArray, headNames: Array<String>): Int
+
+    // Runtime logging
+    private external fun initRuntimeNative(): String
+    private external fun setLogLevelNative(level: Int): Int
+    private external fun getRuntimeLogNative(): String
+
+    // Session configuration (applied on the next loadModel)
+    private external fun setDeterministicNative(on: Boolean)
+    private external fun setResultCacheNative(on: Boolean)
+    private external fun setInterOpThreadsNative(threads: Int): Int
+    private external fun setInputNameNative(inputName: String)
+    private external fun setExecutionProviderNative(provider: Int): Int
+    private external fun setArenaConfigNative(strategy: Int, initBytes: Long): Int
+    private external fun resetConfigNative()
+    private external fun getSupportedImageFormatsNative(): String
+    private external fun exportConfigJsonNative(): String
+    private external fun importConfigJsonNative(json: String): Int
 
     /**
      * Get the last error message from Rust
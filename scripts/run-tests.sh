@@ -9,6 +9,14 @@ log_info "Running tests"
 log_step "Running Rust tests"
 cargo test
 
+# Session-backed tests are ignored by default; they need an ONNX Runtime shared library on the host
+if [ -n "$ORT_DYLIB_PATH" ]; then
+    log_step "Running ONNX Runtime session tests"
+    cargo test -- --ignored
+else
+    log_warning "Skipping ONNX Runtime session tests - ORT_DYLIB_PATH not set"
+fi
+
 log_step "Running Rust tests with GPU features"
 cargo test --features gpu
 
@@ -0,0 +1,69 @@
+/// Shared CPU memory arena registered on the ONNX Runtime environment
+use crate::config::ArenaConfig;
+use crate::errors::{InferenceError, InferenceResult};
+use ort::AsPointer;
+use ort::memory::{AllocationDevice, AllocatorType, MemoryInfo, MemoryType};
+use std::ffi::c_char;
+use std::ptr;
+use std::sync::Mutex;
+
+/// Arena settings currently registered on the environment (`None` = nothing registered yet)
+static REGISTERED_ARENA: Mutex<Option<ArenaConfig>> = Mutex::new(None);
+
+/// Environment-level CPU arena shared by sessions built with env allocators
+pub struct CpuArena;
+
+impl CpuArena {
+    /// Register the environment's CPU arena with `config`, replacing a previously registered one.
+    /// Sessions already loaded keep the arena they were created with.
+    pub fn register(config: ArenaConfig) -> InferenceResult<()> {
+        let mut registered = REGISTERED_ARENA
+            .lock()
+            .map_err(|_| InferenceError::memory_error("Failed to acquire arena mutex"))?;
+        if *registered == Some(config) {
+            return Ok(());
+        }
+
+        let env = ort::environment::get_environment()
+            .map_err(|e| InferenceError::session_failed(format!("Failed to get ONNX Runtime environment: {:?}", e)))?;
+        let memory_info = MemoryInfo::new(AllocationDevice::CPU, 0, AllocatorType::Arena, MemoryType::Default)
+            .map_err(|e| InferenceError::session_failed(format!("Failed to create CPU memory info: {:?}", e)))?;
+
+        let mut keys: Vec<*const c_char> = vec![c"arena_extend_strategy".as_ptr()];
+        let mut values: Vec<usize> = vec![config.extend_strategy.ort_value()];
+        if config.initial_chunk_bytes > 0 {
+            keys.push(c"initial_chunk_size_bytes".as_ptr());
+            values.push(config.initial_chunk_bytes);
+        }
+
+        let api = ort::api();
+        let env_ptr = env.ptr().cast_mut();
+        // SAFETY: `env` and `memory_info` are live ORT objects, `keys`/`values` have equal length and
+        // outlive the calls, and the arena config is released after registration copies it
+        unsafe {
+            if registered.is_some() {
+                Self::check((api.UnregisterAllocator)(env_ptr, memory_info.ptr()), "unregister the previous arena")?;
+                *registered = None;
+            }
+
+            let mut arena_cfg = ptr::null_mut();
+            Self::check(
+                (api.CreateArenaCfgV2)(keys.as_ptr(), values.as_ptr(), keys.len(), &mut arena_cfg),
+                "create the arena config",
+            )?;
+            let status = (api.CreateAndRegisterAllocator)(env_ptr, memory_info.ptr(), arena_cfg);
+            (api.ReleaseArenaCfg)(arena_cfg);
+            Self::check(status, "register the CPU arena")?;
+        }
+
+        *registered = Some(config);
+        Ok(())
+    }
+
+    /// Convert an ORT status into an error naming the failed step.
+    /// `status` must be null or a status returned by an ORT API call.
+    unsafe fn check(status: ort::sys::OrtStatusPtr, action: &str) -> InferenceResult<()> {
+        unsafe { ort::error::status_to_result(status) }
+            .map_err(|e| InferenceError::session_failed(format!("Failed to {}: {}", action, e)))
+    }
+}
@@ -0,0 +1,64 @@
+//! Standard (RFC 4648) Base64 with `=` padding, for shipping raw output bytes as text
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as padded Base64
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (u32::from(chunk[0]) << 16)
+            | (u32::from(chunk.get(1).copied().unwrap_or(0)) << 8)
+            | u32::from(chunk.get(2).copied().unwrap_or(0));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Little-endian bytes of `values`, the layout `getRawOutputBase64Native` encodes
+pub fn f32_le_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|value| value.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference decoder for the round-trip test
+    fn decode(encoded: &str) -> Vec<u8> {
+        let sextets: Vec<u32> = encoded
+            .bytes()
+            .filter(|&byte| byte != b'=')
+            .map(|byte| ALPHABET.iter().position(|&c| c == byte).unwrap() as u32)
+            .collect();
+        let mut bytes = Vec::new();
+        for chunk in sextets.chunks(4) {
+            let group = chunk.iter().enumerate().fold(0, |group, (i, &sextet)| group | sextet << (18 - 6 * i));
+            bytes.extend(group.to_be_bytes()[1..chunk.len()].iter());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_rfc4648_vectors() {
+        for (plain, encoded) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")] {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn test_float_round_trip() {
+        let values = [0.0f32, -1.5, 3.25e-7, f32::MAX, 42.0];
+        let decoded = decode(&encode(&f32_le_bytes(&values)));
+        let floats: Vec<f32> = decoded
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect();
+        assert_eq!(floats, values);
+    }
+}
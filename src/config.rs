@@ -0,0 +1,794 @@
+/// Runtime configuration for preprocessing and session creation
+use crate::constants::{CLIP_MEAN, CLIP_STD, DEFAULT_MAX_IMAGE_PIXELS, IMAGENET_MEAN, IMAGENET_STD, INCEPTION_MEAN, INCEPTION_STD};
+use crate::pipeline::Pipeline;
+use std::sync::Mutex;
+
+/// Strategy used to resize the decoded image before normalization
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResizeMode {
+    /// Stretch to the fixed model input size, ignoring aspect ratio
+    Stretch,
+    /// Resize so the shorter side equals the given length, preserving aspect ratio (no crop).
+    /// Produces a non-square tensor, so the model must accept dynamic spatial dims.
+    ResizeShortestSide(u32),
+    /// Scale to fit inside the model input size preserving aspect ratio, padding the rest
+    Letterbox,
+    /// Size each spatial axis from the loaded model's declared input: fixed axes use the
+    /// declared size, dynamic axes (`None`) follow the source aspect ratio snapped to a
+    /// multiple of `stride`. Dims are filled in from the active model before preprocessing.
+    ModelInput {
+        height: Option<u32>,
+        width: Option<u32>,
+        stride: u32,
+    },
+    /// Pad the shorter side symmetrically with an RGB fill color to make the image square, then
+    /// stretch to the model input size. Keeps the aspect ratio without recording a box transform.
+    PadToSquare([u8; 3]),
+}
+
+impl ResizeMode {
+    /// Map a JNI mode code (and its size parameter) to a resize mode
+    pub fn from_code(mode: i32, size: i32) -> Option<Self> {
+        match mode {
+            0 => Some(ResizeMode::Stretch),
+            1 if size > 0 => Some(ResizeMode::ResizeShortestSide(size as u32)),
+            2 => Some(ResizeMode::Letterbox),
+            3 if size > 0 => Some(ResizeMode::ModelInput {
+                height: None,
+                width: None,
+                stride: size as u32,
+            }),
+            // `size` carries the fill color as 0xRRGGBB
+            4 if (0..=0xFF_FFFF).contains(&size) => {
+                let [_, r, g, b] = size.to_be_bytes();
+                Some(ResizeMode::PadToSquare([r, g, b]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Fill `ModelInput` height/width from a declared `[N, C, H, W]` shape (negative dims stay dynamic).
+    /// Other modes are returned unchanged.
+    pub fn with_model_dims(self, declared_shape: &[i64]) -> Self {
+        match (self, declared_shape) {
+            (ResizeMode::ModelInput { stride, .. }, [_, _, height, width]) => {
+                let fixed = |dim: i64| (dim > 0).then_some(dim as u32);
+                ResizeMode::ModelInput {
+                    height: fixed(*height),
+                    width: fixed(*width),
+                    stride,
+                }
+            }
+            (mode, _) => mode,
+        }
+    }
+}
+
+/// Per-channel pixel normalization: `(pixel * scale - mean[c]) / std[c]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    pub mean: [f32; 3],
+    pub std: [f32; 3],
+    /// Applied to raw 0..=255 pixel values before the mean is subtracted
+    pub scale: f32,
+}
+
+impl Normalization {
+    /// Build a custom normalization; every std value must be non-zero and finite
+    pub fn new(mean: [f32; 3], std: [f32; 3], scale: f32) -> Option<Self> {
+        let valid = std.iter().all(|&s| s != 0.0 && s.is_finite())
+            && mean.iter().all(|m| m.is_finite())
+            && scale.is_finite();
+        valid.then_some(Self { mean, std, scale })
+    }
+}
+
+/// Named normalization recipes for common training pipelines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalizationPreset {
+    /// scale 1/255, mean [0.485, 0.456, 0.406], std [0.229, 0.224, 0.225]
+    ImageNet,
+    /// scale 1/255, mean 0.5, std 0.5 per channel, giving values in [-1, 1]
+    Inception,
+    /// scale 1/255, mean [0.48145466, 0.4578275, 0.40821073], std [0.26862954, 0.26130258, 0.27577711]
+    Clip,
+    /// scale 1/255, mean 0, std 1: plain [0, 1] pixels
+    None,
+}
+
+impl NormalizationPreset {
+    /// Map a JNI preset code (0 = ImageNet, 1 = Inception, 2 = CLIP, 3 = None)
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(NormalizationPreset::ImageNet),
+            1 => Some(NormalizationPreset::Inception),
+            2 => Some(NormalizationPreset::Clip),
+            3 => Some(NormalizationPreset::None),
+            _ => None,
+        }
+    }
+
+    /// Get the exact constants for this preset
+    pub const fn normalization(self) -> Normalization {
+        let (mean, std) = match self {
+            NormalizationPreset::ImageNet => (IMAGENET_MEAN, IMAGENET_STD),
+            NormalizationPreset::Inception => (INCEPTION_MEAN, INCEPTION_STD),
+            NormalizationPreset::Clip => (CLIP_MEAN, CLIP_STD),
+            NormalizationPreset::None => ([0.0; 3], [1.0; 3]),
+        };
+        Normalization {
+            mean,
+            std,
+            scale: 1.0 / 255.0,
+        }
+    }
+}
+
+/// Input quantization of an int8/uint8 model: `q = round(value / scale) + zero_point`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuantParams {
+    pub scale: f32,
+    pub zero_point: i32,
+}
+
+impl QuantParams {
+    /// Build quantization parameters; scale must be positive and the zero point fit in 8 bits
+    pub fn new(scale: f32, zero_point: i32) -> Option<Self> {
+        let valid = scale.is_finite() && scale > 0.0 && (-128..=255).contains(&zero_point);
+        valid.then_some(Self { scale, zero_point })
+    }
+}
+
+/// Order of the float values in a color input tensor's flat buffer
+///
+/// The declared shape stays `[1, 3, H, W]` either way. Interleaved is only for models whose
+/// exporter mislabeled the input: the graph reads HWC data (e.g. a TF/TFLite conversion that
+/// reshapes or transposes internally) while declaring NCHW, so planar input gives confident but
+/// wrong predictions. Single-channel inputs are the same in both orders.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataOrder {
+    /// One plane per channel: `RRR..GGG..BBB..` (NCHW, the default)
+    Planar,
+    /// Channels interleaved per pixel: `RGBRGB..` (NHWC values behind an NCHW shape)
+    Interleaved,
+}
+
+impl DataOrder {
+    /// Map a JNI order code to a data order
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(DataOrder::Planar),
+            1 => Some(DataOrder::Interleaved),
+            _ => None,
+        }
+    }
+
+    /// Flat index of `channel` of the `pixel`-th pixel in a 3-channel tensor with `plane` pixels
+    pub fn index(self, pixel: usize, channel: usize, plane: usize) -> usize {
+        match self {
+            DataOrder::Planar => channel * plane + pixel,
+            DataOrder::Interleaved => pixel * 3 + channel,
+        }
+    }
+}
+
+/// Image preprocessing configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreprocessConfig {
+    pub resize_mode: ResizeMode,
+    pub normalization: Normalization,
+    /// When set and the model takes a uint8/int8 image, pixels are normalized and quantized in one pass
+    pub quantization: Option<QuantParams>,
+    /// Largest width * height accepted for decoding; bigger images fail before allocation
+    pub max_image_pixels: u64,
+    /// Shorter image side below which a low-resolution warning is added to the result's `warnings`;
+    /// inference still runs (`None` = no check)
+    pub min_image_dimension: Option<u32>,
+    /// Produce a single luma channel (`[1, 1, H, W]`) normalized with channel 0's mean/std.
+    /// Turned on per run when the active model's image input declares one channel (float inputs only).
+    pub grayscale: bool,
+    /// RGB color images with an alpha channel are composited onto (`None` = alpha is dropped)
+    pub alpha_background: Option<[u8; 3]>,
+    /// Layout of color values in the tensor's flat buffer (quantized inputs included)
+    pub data_order: DataOrder,
+    /// Normalize by the resized image's own mean and std instead of `normalization`
+    /// (`tf.image.per_image_standardization`), as some medical-imaging models expect
+    pub per_image_standardization: bool,
+    /// Ordered resize/crop/flip/scale/normalize steps that replace `resize_mode` and `normalization`
+    /// (`None` = use those). Data order, quantization and per-image standardization still apply.
+    pub pipeline: Option<Pipeline>,
+}
+
+impl PreprocessConfig {
+    /// Default preprocessing: stretch to the standard ImageNet input size, ImageNet statistics
+    pub const fn new() -> Self {
+        Self {
+            resize_mode: ResizeMode::Stretch,
+            normalization: NormalizationPreset::ImageNet.normalization(),
+            quantization: None,
+            max_image_pixels: DEFAULT_MAX_IMAGE_PIXELS,
+            min_image_dimension: None,
+            grayscale: false,
+            alpha_background: None,
+            data_order: DataOrder::Planar,
+            per_image_standardization: false,
+            pipeline: None,
+        }
+    }
+}
+
+impl Default for PreprocessConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How classification confidences are written in JSON outputs. Detection `score`s are not
+/// converted: they are whatever the detector emits, compared as-is against the detection threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfidenceFormat {
+    /// Probability in `[0, 1]`
+    Fraction,
+    /// Probability scaled to `[0, 100]`
+    Percent,
+}
+
+impl ConfidenceFormat {
+    /// Map a JNI format code to a confidence format
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(ConfidenceFormat::Fraction),
+            1 => Some(ConfidenceFormat::Percent),
+            _ => None,
+        }
+    }
+
+    /// Express a `[0, 1]` confidence in this format
+    pub fn apply(self, confidence: f32) -> f32 {
+        match self {
+            ConfidenceFormat::Fraction => confidence,
+            ConfidenceFormat::Percent => confidence * 100.0,
+        }
+    }
+}
+
+/// Which values of a capped output are returned to Java
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReturnedSubset {
+    /// The leading values, in output order
+    First,
+    /// The largest values, highest first (ties keep the lower index first). Their class ids are
+    /// not returned with them; `getTopIndicesNative(n)` lists them in the same order.
+    Largest,
+}
+
+impl ReturnedSubset {
+    /// Map a JNI subset code to a returned subset
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(ReturnedSubset::First),
+            1 => Some(ReturnedSubset::Largest),
+            _ => None,
+        }
+    }
+}
+
+/// Limit on how many output values `runInferenceNative` copies back to Java
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputCap {
+    pub max_elements: usize,
+    pub subset: ReturnedSubset,
+}
+
+/// Mapping of classes to named super-categories for grouped confidences
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassGroups {
+    /// Group index per class id (`None` = the class belongs to no group)
+    pub class_to_group: Vec<Option<usize>>,
+    pub names: Vec<String>,
+}
+
+impl ClassGroups {
+    /// Build a mapping from JNI values, where -1 leaves a class ungrouped; every other
+    /// value must index `names`
+    pub fn new(class_to_group: &[i32], names: Vec<String>) -> Option<Self> {
+        let class_to_group = class_to_group
+            .iter()
+            .map(|&group| match usize::try_from(group) {
+                Ok(group) if group < names.len() => Some(Some(group)),
+                _ if group == -1 => Some(None),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { class_to_group, names })
+    }
+}
+
+/// Learned confidence calibration applied to classifier logits before softmax
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    /// Logits are divided by this before the bias is added
+    pub temperature: f32,
+    /// Per-class logit offsets (empty = temperature scaling only)
+    pub class_bias: Vec<f32>,
+}
+
+impl Calibration {
+    /// Build a calibration, rejecting a non-positive temperature or non-finite values
+    pub fn new(temperature: f32, class_bias: Vec<f32>) -> Option<Self> {
+        let valid = temperature.is_finite() && temperature > 0.0 && class_bias.iter().all(|bias| bias.is_finite());
+        valid.then_some(Self { temperature, class_bias })
+    }
+}
+
+/// One head of a multi-head classifier: a named slice of the output vector
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassificationHead {
+    pub name: String,
+    /// Index of the head's first logit in the output
+    pub offset: usize,
+    /// Number of classes in the head
+    pub size: usize,
+}
+
+impl ClassificationHead {
+    /// Build a head from JNI values, rejecting a negative offset or an empty head
+    pub fn new(offset: i32, size: i32, name: String) -> Option<Self> {
+        match (usize::try_from(offset), usize::try_from(size)) {
+            (Ok(offset), Ok(size)) if size > 0 => Some(Self { name, offset, size }),
+            _ => None,
+        }
+    }
+}
+
+/// Filtering applied to decoded detections: score threshold and class filter, then per-class NMS
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectionFilter {
+    /// Boxes scoring below this are dropped
+    pub conf_threshold: f32,
+    /// A box overlapping a higher-scoring box of its class by more than this IoU is suppressed
+    pub iou_threshold: f32,
+    /// Class ids to keep (`None` = every class)
+    pub classes: Option<Vec<usize>>,
+}
+
+impl DetectionFilter {
+    /// Keep every box: no threshold, no class filter, and an IoU limit no overlap exceeds
+    pub const fn new() -> Self {
+        Self {
+            conf_threshold: f32::NEG_INFINITY,
+            iou_threshold: 1.0,
+            classes: None,
+        }
+    }
+}
+
+impl Default for DetectionFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Output postprocessing configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostprocessConfig {
+    /// Class indices top predictions are restricted to (`None` = all classes)
+    pub class_allowlist: Option<Vec<usize>>,
+    /// Confidence scale used in JSON outputs
+    pub confidence_format: ConfidenceFormat,
+    /// Decimal places reported timings are rounded to (`None` = full precision). Results keep
+    /// full precision; only the timing getters and JSON round.
+    pub timing_decimals: Option<u32>,
+    /// Model outputs to compute and extract, primary first (`None` = only the first output is extracted)
+    pub requested_outputs: Option<Vec<String>>,
+    /// Super-categories for `InferenceEngine::top_groups`
+    pub class_groups: Option<ClassGroups>,
+    /// Heads for `InferenceEngine::top_predictions_per_head` (empty = single-head output)
+    pub classification_heads: Vec<ClassificationHead>,
+    /// Treat `[N, C]` outputs as `N` region proposals, each softmaxed and ranked on its own
+    pub region_predictions: bool,
+    /// Cap on the output values returned to Java (`None` = the whole output). Results keep every value.
+    pub output_cap: Option<OutputCap>,
+    /// Largest output softmax is computed over; bigger outputs only report the raw argmax (`None` = no limit)
+    pub max_softmax_classes: Option<usize>,
+    /// Calibration applied to classifier logits before softmax (`None` = uncalibrated)
+    pub calibration: Option<Calibration>,
+    /// Per-member weights for `InferenceEngine::run_ensemble`, in model index order (`None` = equal weights)
+    pub ensemble_weights: Option<Vec<f32>>,
+    /// Thresholds and class filter for decoded detections
+    pub detection_filter: DetectionFilter,
+}
+
+impl PostprocessConfig {
+    /// Default postprocessing: rank every class, fractional confidences
+    pub const fn new() -> Self {
+        Self {
+            class_allowlist: None,
+            confidence_format: ConfidenceFormat::Fraction,
+            timing_decimals: None,
+            requested_outputs: None,
+            class_groups: None,
+            classification_heads: Vec::new(),
+            region_predictions: false,
+            output_cap: None,
+            max_softmax_classes: None,
+            calibration: None,
+            ensemble_weights: None,
+            detection_filter: DetectionFilter::new(),
+        }
+    }
+}
+
+impl Default for PostprocessConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Execution provider requested for subsequently loaded models
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecutionProviderKind {
+    /// ORT's default CPU (MLAS) kernels
+    Cpu,
+    /// Android Neural Networks API; ORT falls back to CPU if it cannot be registered
+    Nnapi,
+    /// XNNPACK's optimized CPU kernels (needs an ORT build that includes it)
+    Xnnpack,
+}
+
+impl ExecutionProviderKind {
+    /// Map a JNI provider code to a provider
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(ExecutionProviderKind::Cpu),
+            1 => Some(ExecutionProviderKind::Nnapi),
+            2 => Some(ExecutionProviderKind::Xnnpack),
+            _ => None,
+        }
+    }
+}
+
+/// How the CPU memory arena grows when it runs out of space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArenaExtendStrategy {
+    /// Double the reserved size each time (ORT default): few extensions, so allocation stays off the
+    /// `inference_time_ms` hot path after warm-up, at the cost of reserving up to ~2x the peak need
+    NextPowerOfTwo,
+    /// Grow by exactly the failed request: the smallest footprint, but runs that need new memory
+    /// pay for an extra allocation and show higher, noisier `inference_time_ms`
+    SameAsRequested,
+}
+
+impl ArenaExtendStrategy {
+    /// Map a JNI strategy code to a strategy
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(ArenaExtendStrategy::NextPowerOfTwo),
+            1 => Some(ArenaExtendStrategy::SameAsRequested),
+            _ => None,
+        }
+    }
+
+    /// Value of ORT's `arena_extend_strategy` arena config key
+    pub fn ort_value(self) -> usize {
+        match self {
+            ArenaExtendStrategy::NextPowerOfTwo => 0,
+            ArenaExtendStrategy::SameAsRequested => 1,
+        }
+    }
+}
+
+/// Shared CPU arena settings for sessions that opt out of ORT's per-session default arena
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArenaConfig {
+    pub extend_strategy: ArenaExtendStrategy,
+    /// First chunk reserved by the arena (0 = ORT default)
+    pub initial_chunk_bytes: usize,
+}
+
+/// ONNX Runtime session options applied during `load_model`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionConfig {
+    /// Single-threaded execution with the CPU memory arena disabled.
+    /// Gives reproducible outputs and stable timings at the cost of throughput
+    /// (no intra-op parallelism) and more allocator calls per run.
+    pub deterministic: bool,
+    /// Threads used to run independent graph branches in parallel (0 = ORT default).
+    /// Ignored while `deterministic` is on, which forces a single sequential thread.
+    pub inter_op_threads: usize,
+    /// Name of the input fed with the image; `None` picks the first rank-4 input
+    pub input_name: Option<String>,
+    /// Providers to try in order before CPU; the first that registers is used (empty = CPU only).
+    /// Ignored while `deterministic` is on, which pins the CPU provider.
+    pub execution_providers: Vec<ExecutionProviderKind>,
+    /// Shared CPU arena to allocate from (`None` = ORT's default per-session arena).
+    /// Ignored while `deterministic` is on, which disables the arena.
+    pub arena: Option<ArenaConfig>,
+}
+
+impl SessionConfig {
+    /// Default session options: ORT-managed threading and arena
+    pub const fn new() -> Self {
+        Self {
+            deterministic: false,
+            inter_op_threads: 0,
+            input_name: None,
+            execution_providers: Vec::new(),
+            arena: None,
+        }
+    }
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Static storage for the active preprocessing configuration
+static PREPROCESS_CONFIG: Mutex<PreprocessConfig> = Mutex::new(PreprocessConfig::new());
+
+/// Static storage for the active postprocessing configuration
+static POSTPROCESS_CONFIG: Mutex<PostprocessConfig> = Mutex::new(PostprocessConfig::new());
+
+/// Static storage for the session options used by the next model load
+static SESSION_CONFIG: Mutex<SessionConfig> = Mutex::new(SessionConfig::new());
+
+/// Global configuration access
+pub struct ConfigManager;
+
+impl ConfigManager {
+    /// Restore preprocessing, postprocessing and session options to their defaults
+    pub fn reset() {
+        Self::replace(PreprocessConfig::default(), PostprocessConfig::default(), SessionConfig::default());
+    }
+
+    /// Replace preprocessing, postprocessing and session options together (e.g. from an imported config).
+    /// All three locks are taken before anything is written, so the sections change as one.
+    pub fn replace(preprocess: PreprocessConfig, postprocess: PostprocessConfig, session: SessionConfig) {
+        if let (Ok(mut preprocess_config), Ok(mut postprocess_config), Ok(mut session_config)) =
+            (PREPROCESS_CONFIG.lock(), POSTPROCESS_CONFIG.lock(), SESSION_CONFIG.lock())
+        {
+            *preprocess_config = preprocess;
+            *postprocess_config = postprocess;
+            *session_config = session;
+        }
+    }
+
+    /// Get a snapshot of the current preprocessing configuration
+    pub fn preprocess_config() -> PreprocessConfig {
+        match PREPROCESS_CONFIG.lock() {
+            Ok(config) => config.clone(),
+            Err(_) => PreprocessConfig::default(),
+        }
+    }
+
+    /// Set the resize strategy used by preprocessing
+    pub fn set_resize_mode(mode: ResizeMode) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.resize_mode = mode;
+        }
+    }
+
+    /// Set the pixel normalization used by preprocessing
+    pub fn set_normalization(normalization: Normalization) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.normalization = normalization;
+        }
+    }
+
+    /// Set (or clear) the input quantization used for int8/uint8 models
+    pub fn set_quant_params(params: Option<QuantParams>) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.quantization = params;
+        }
+    }
+
+    /// Set the largest image (in pixels) that will be decoded
+    pub fn set_max_image_pixels(max_pixels: u64) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.max_image_pixels = max_pixels;
+        }
+    }
+
+    /// Warn about images whose shorter side is below `min_dimension` (`None` = don't check)
+    pub fn set_min_image_dimension(min_dimension: Option<u32>) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.min_image_dimension = min_dimension;
+        }
+    }
+
+    /// Composite images with transparency onto an RGB background (`None` drops alpha)
+    pub fn set_alpha_background(background: Option<[u8; 3]>) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.alpha_background = background;
+        }
+    }
+
+    /// Emit color tensors planar (NCHW) or interleaved (HWC values behind the NCHW shape)
+    pub fn set_data_order(order: DataOrder) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.data_order = order;
+        }
+    }
+
+    /// Normalize each image by its own statistics (on) or by the configured normalization (off)
+    pub fn set_per_image_standardization(on: bool) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.per_image_standardization = on;
+        }
+    }
+
+    /// Preprocess through `pipeline` instead of the resize mode and normalization (`None` = stop)
+    pub fn set_preprocess_pipeline(pipeline: Option<Pipeline>) {
+        if let Ok(mut config) = PREPROCESS_CONFIG.lock() {
+            config.pipeline = pipeline;
+        }
+    }
+
+    /// Get a snapshot of the current postprocessing configuration
+    pub fn postprocess_config() -> PostprocessConfig {
+        match POSTPROCESS_CONFIG.lock() {
+            Ok(config) => config.clone(),
+            Err(_) => PostprocessConfig::default(),
+        }
+    }
+
+    /// Restrict top predictions to the given class indices (empty restores all classes)
+    pub fn set_class_allowlist(class_ids: Vec<usize>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.class_allowlist = (!class_ids.is_empty()).then_some(class_ids);
+        }
+    }
+
+    /// Set how confidences are written in JSON outputs
+    pub fn set_confidence_format(format: ConfidenceFormat) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.confidence_format = format;
+        }
+    }
+
+    /// Round reported timings to `decimals` places (`None` = full precision)
+    pub fn set_timing_decimals(decimals: Option<u32>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.timing_decimals = decimals;
+        }
+    }
+
+    /// Limit runs to the named outputs; ORT prunes the rest of the graph. An empty list restores the default
+    pub fn set_requested_outputs(names: Vec<String>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.requested_outputs = (!names.is_empty()).then_some(names);
+        }
+    }
+
+    /// Set (or clear with `None`) the class-to-group mapping used for grouped confidences
+    pub fn set_class_groups(groups: Option<ClassGroups>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.class_groups = groups;
+        }
+    }
+
+    /// Set the heads of a multi-head classifier output (an empty list clears them)
+    pub fn set_classification_heads(heads: Vec<ClassificationHead>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.classification_heads = heads;
+        }
+    }
+
+    /// Turn per-region top-1 predictions for `[N, C]` proposal outputs on or off
+    pub fn set_region_predictions(on: bool) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.region_predictions = on;
+        }
+    }
+
+    /// Cap (or with `None` stop capping) the output values returned to Java
+    pub fn set_output_cap(cap: Option<OutputCap>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.output_cap = cap;
+        }
+    }
+
+    /// Skip softmax for outputs with more than `max_classes` values (`None` = always apply it)
+    pub fn set_max_softmax_classes(max_classes: Option<usize>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.max_softmax_classes = max_classes;
+        }
+    }
+
+    /// Set (or clear with `None`) the calibration applied to classifier logits
+    pub fn set_calibration(calibration: Option<Calibration>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.calibration = calibration;
+        }
+    }
+
+    /// Weight ensemble members' probabilities (or with `None` weight them equally)
+    pub fn set_ensemble_weights(weights: Option<Vec<f32>>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.ensemble_weights = weights;
+        }
+    }
+
+    /// Set the detection score and NMS IoU thresholds
+    pub fn set_detection_thresholds(conf_threshold: f32, iou_threshold: f32) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.detection_filter.conf_threshold = conf_threshold;
+            config.detection_filter.iou_threshold = iou_threshold;
+        }
+    }
+
+    /// Keep only detections of the given classes (empty keeps every class)
+    pub fn set_detection_class_filter(class_ids: Vec<usize>) {
+        if let Ok(mut config) = POSTPROCESS_CONFIG.lock() {
+            config.detection_filter.classes = (!class_ids.is_empty()).then_some(class_ids);
+        }
+    }
+
+    /// Get a snapshot of the current session configuration
+    pub fn session_config() -> SessionConfig {
+        match SESSION_CONFIG.lock() {
+            Ok(config) => config.clone(),
+            Err(_) => SessionConfig::default(),
+        }
+    }
+
+    /// Enable or disable deterministic (single-threaded, arena-free) sessions
+    pub fn set_deterministic(on: bool) {
+        if let Ok(mut config) = SESSION_CONFIG.lock() {
+            config.deterministic = on;
+        }
+    }
+
+    /// Set the inter-op thread count for subsequently loaded models (0 = ORT default)
+    pub fn set_inter_op_threads(threads: usize) {
+        if let Ok(mut config) = SESSION_CONFIG.lock() {
+            config.inter_op_threads = threads;
+        }
+    }
+
+    /// Set the image input name for subsequently loaded models (`None` = auto-detect)
+    pub fn set_input_name(name: Option<String>) {
+        if let Ok(mut config) = SESSION_CONFIG.lock() {
+            config.input_name = name;
+        }
+    }
+
+    /// Set (or clear) the shared CPU arena used by subsequently loaded models
+    pub fn set_arena_config(arena: Option<ArenaConfig>) {
+        if let Ok(mut config) = SESSION_CONFIG.lock() {
+            config.arena = arena;
+        }
+    }
+
+    /// Set the execution provider requested for subsequently loaded models
+    pub fn set_execution_provider(provider: ExecutionProviderKind) {
+        if let Ok(mut config) = SESSION_CONFIG.lock() {
+            config.execution_providers = vec![provider];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::lock_global_state;
+
+    #[test]
+    fn test_reset_restores_defaults() {
+        let _guard = lock_global_state();
+        ConfigManager::set_resize_mode(ResizeMode::Letterbox);
+        ConfigManager::set_normalization(NormalizationPreset::Clip.normalization());
+        ConfigManager::set_quant_params(QuantParams::new(0.02, 128));
+        ConfigManager::set_class_allowlist(vec![1, 2, 3]);
+        ConfigManager::set_deterministic(true);
+        ConfigManager::set_input_name(Some("pixel_values".to_string()));
+
+        ConfigManager::reset();
+
+        assert_eq!(ConfigManager::preprocess_config(), PreprocessConfig::default());
+        assert_eq!(ConfigManager::postprocess_config(), PostprocessConfig::default());
+        assert_eq!(ConfigManager::session_config(), SessionConfig::default());
+    }
+}
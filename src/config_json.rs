@@ -0,0 +1,533 @@
+//! Export and import of the whole engine configuration as one JSON document, so a device's settings
+//! can be attached to a bug report or shipped per model from a server
+use crate::config::{
+    ArenaConfig, ArenaExtendStrategy, Calibration, ClassGroups, ClassificationHead, ConfidenceFormat, ConfigManager,
+    DataOrder, DetectionFilter, ExecutionProviderKind, Normalization, OutputCap, PostprocessConfig, PreprocessConfig, QuantParams,
+    ResizeMode, ReturnedSubset, SessionConfig,
+};
+use crate::json::{self, Json};
+use crate::pipeline::Pipeline;
+
+/// Version written to and required by the config document
+const CONFIG_SCHEMA_VERSION: u32 = 1;
+
+/// Config import/export
+pub struct ConfigJson;
+
+impl ConfigJson {
+    /// Serialize the current preprocessing, postprocessing and session configuration:
+    /// `{"schema_version":1,"preprocess":{..},"postprocess":{..},"session":{..}}`
+    pub fn export() -> String {
+        format!(
+            "{{\"schema_version\":{},\"preprocess\":{},\"postprocess\":{},\"session\":{}}}",
+            CONFIG_SCHEMA_VERSION,
+            Self::preprocess_json(&ConfigManager::preprocess_config()),
+            Self::postprocess_json(&ConfigManager::postprocess_config()),
+            Self::session_json(&ConfigManager::session_config())
+        )
+    }
+
+    /// Replace the configuration with one from `export`. Keys left out take their default values;
+    /// nothing is applied unless the whole document is valid.
+    pub fn import(text: &str) -> Result<(), String> {
+        let document = json::parse(text)?;
+        match document.get("schema_version").and_then(Json::as_usize) {
+            Some(version) if version == CONFIG_SCHEMA_VERSION as usize => {}
+            _ => return Err(format!("Config schema_version must be {}", CONFIG_SCHEMA_VERSION)),
+        }
+
+        let section = |name: &str| document.get(name).unwrap_or(&Json::Null);
+        let preprocess = Self::parse_preprocess(section("preprocess"))?;
+        let postprocess = Self::parse_postprocess(section("postprocess"))?;
+        let session = Self::parse_session(section("session"))?;
+        ConfigManager::replace(preprocess, postprocess, session);
+        Ok(())
+    }
+
+    fn preprocess_json(config: &PreprocessConfig) -> String {
+        let Normalization { mean, std, scale } = config.normalization;
+        format!(
+            "{{\"resize_mode\":{},\"normalization\":{{\"mean\":{},\"std\":{},\"scale\":{}}},\"quantization\":{},\
+             \"max_image_pixels\":{},\"min_image_dimension\":{},\"alpha_background\":{},\"data_order\":\"{}\",\"per_image_standardization\":{},\"pipeline\":{}}}",
+            Self::resize_mode_json(config.resize_mode),
+            list(&mean),
+            list(&std),
+            scale,
+            nullable(config.quantization, |quant| format!(
+                "{{\"scale\":{},\"zero_point\":{}}}",
+                quant.scale, quant.zero_point
+            )),
+            config.max_image_pixels,
+            nullable(config.min_image_dimension, |min| min.to_string()),
+            nullable(config.alpha_background, |color| list(&color)),
+            match config.data_order {
+                DataOrder::Planar => "planar",
+                DataOrder::Interleaved => "interleaved",
+            },
+            config.per_image_standardization,
+            nullable(config.pipeline.as_ref(), Pipeline::to_json)
+        )
+    }
+
+    fn resize_mode_json(mode: ResizeMode) -> String {
+        match mode {
+            ResizeMode::Stretch => "{\"mode\":\"stretch\"}".to_string(),
+            ResizeMode::ResizeShortestSide(size) => format!("{{\"mode\":\"shortest_side\",\"size\":{}}}", size),
+            ResizeMode::Letterbox => "{\"mode\":\"letterbox\"}".to_string(),
+            ResizeMode::ModelInput { height, width, stride } => format!(
+                "{{\"mode\":\"model_input\",\"height\":{},\"width\":{},\"stride\":{}}}",
+                nullable(height, |h| h.to_string()),
+                nullable(width, |w| w.to_string()),
+                stride
+            ),
+            ResizeMode::PadToSquare(fill) => format!("{{\"mode\":\"pad_to_square\",\"fill\":{}}}", list(&fill)),
+        }
+    }
+
+    fn postprocess_json(config: &PostprocessConfig) -> String {
+        let groups = config.class_groups.as_ref().map(|groups| {
+            let mapping: Vec<i64> = groups
+                .class_to_group
+                .iter()
+                .map(|group| group.map_or(-1, |group| group as i64))
+                .collect();
+            format!("{{\"class_to_group\":{},\"names\":{}}}", list(&mapping), strings(&groups.names))
+        });
+        let heads: Vec<String> = config
+            .classification_heads
+            .iter()
+            .map(|head| format!("{{\"name\":{},\"offset\":{},\"size\":{}}}", quote(&head.name), head.offset, head.size))
+            .collect();
+        let filter = &config.detection_filter;
+
+        format!(
+            "{{\"class_allowlist\":{},\"confidence_format\":\"{}\",\"timing_decimals\":{},\"requested_outputs\":{},\"class_groups\":{},\
+             \"classification_heads\":[{}],\"region_predictions\":{},\"output_cap\":{},\"max_softmax_classes\":{},\"calibration\":{},\
+             \"ensemble_weights\":{},\"detection_filter\":{{\"conf_threshold\":{},\"iou_threshold\":{},\"classes\":{}}}}}",
+            nullable(config.class_allowlist.as_deref(), list),
+            match config.confidence_format {
+                ConfidenceFormat::Fraction => "fraction",
+                ConfidenceFormat::Percent => "percent",
+            },
+            nullable(config.timing_decimals, |decimals| decimals.to_string()),
+            nullable(config.requested_outputs.as_deref(), strings),
+            groups.unwrap_or_else(|| "null".to_string()),
+            heads.join(","),
+            config.region_predictions,
+            nullable(config.output_cap, |cap| format!(
+                "{{\"max_elements\":{},\"subset\":\"{}\"}}",
+                cap.max_elements,
+                match cap.subset {
+                    ReturnedSubset::First => "first",
+                    ReturnedSubset::Largest => "largest",
+                }
+            )),
+            nullable(config.max_softmax_classes, |max| max.to_string()),
+            nullable(config.calibration.as_ref(), |calibration| format!(
+                "{{\"temperature\":{},\"class_bias\":{}}}",
+                calibration.temperature,
+                list(&calibration.class_bias)
+            )),
+            nullable(config.ensemble_weights.as_deref(), list),
+            // JSON has no infinity, so "no threshold" is written as null
+            nullable(Some(filter.conf_threshold).filter(|t| t.is_finite()), |t| t.to_string()),
+            filter.iou_threshold,
+            nullable(filter.classes.as_deref(), list)
+        )
+    }
+
+    fn session_json(config: &SessionConfig) -> String {
+        let providers: Vec<String> = config
+            .execution_providers
+            .iter()
+            .map(|&provider| quote(provider_name(provider)))
+            .collect();
+        format!(
+            "{{\"deterministic\":{},\"inter_op_threads\":{},\"input_name\":{},\"execution_providers\":[{}],\"arena\":{}}}",
+            config.deterministic,
+            config.inter_op_threads,
+            nullable(config.input_name.as_deref(), quote),
+            providers.join(","),
+            nullable(config.arena, |arena| format!(
+                "{{\"extend_strategy\":\"{}\",\"initial_chunk_bytes\":{}}}",
+                match arena.extend_strategy {
+                    ArenaExtendStrategy::NextPowerOfTwo => "next_power_of_two",
+                    ArenaExtendStrategy::SameAsRequested => "same_as_requested",
+                },
+                arena.initial_chunk_bytes
+            ))
+        )
+    }
+
+    fn parse_preprocess(section: &Json) -> Result<PreprocessConfig, String> {
+        let mut config = PreprocessConfig::default();
+        if let Some(mode) = read(section, "resize_mode", Self::parse_resize_mode)? {
+            config.resize_mode = mode;
+        }
+        if let Some(normalization) = read(section, "normalization", |value| {
+            let mean = f32_triple(value.get("mean")?)?;
+            let std = f32_triple(value.get("std")?)?;
+            Normalization::new(mean, std, value.get("scale")?.as_f64()? as f32)
+        })? {
+            config.normalization = normalization;
+        }
+        if let Some(quantization) = read(section, "quantization", |value| {
+            or_null(value, |quant| {
+                let zero_point = i32::try_from(quant.get("zero_point")?.as_i64()?).ok()?;
+                QuantParams::new(quant.get("scale")?.as_f64()? as f32, zero_point)
+            })
+        })? {
+            config.quantization = quantization;
+        }
+        if let Some(max_pixels) = read(section, "max_image_pixels", |value| value.as_usize().filter(|&max| max > 0))? {
+            config.max_image_pixels = max_pixels as u64;
+        }
+        if let Some(min_dimension) = read(section, "min_image_dimension", |value| {
+            or_null(value, |min| u32::try_from(min.as_usize()?).ok())
+        })? {
+            config.min_image_dimension = min_dimension;
+        }
+        if let Some(background) = read(section, "alpha_background", |value| or_null(value, u8_triple))? {
+            config.alpha_background = background;
+        }
+        if let Some(order) = read(section, "data_order", |value| match value.as_str()? {
+            "planar" => Some(DataOrder::Planar),
+            "interleaved" => Some(DataOrder::Interleaved),
+            _ => None,
+        })? {
+            config.data_order = order;
+        }
+        if let Some(on) = read(section, "per_image_standardization", Json::as_bool)? {
+            config.per_image_standardization = on;
+        }
+        // Parsed here rather than through `read` so the error names the step that failed
+        match section.get("pipeline") {
+            None | Some(Json::Null) => {}
+            Some(steps) => {
+                let pipeline = Pipeline::from_json(steps).map_err(|e| format!("Invalid config value for \"pipeline\": {}", e))?;
+                config.pipeline = Some(pipeline);
+            }
+        }
+        Ok(config)
+    }
+
+    fn parse_resize_mode(value: &Json) -> Option<ResizeMode> {
+        let size = |key: &str| u32::try_from(value.get(key)?.as_usize()?).ok().filter(|&size| size > 0);
+        let dim = |key: &str| match value.get(key) {
+            None | Some(Json::Null) => Some(None),
+            Some(_) => size(key).map(Some),
+        };
+        match value.get("mode")?.as_str()? {
+            "stretch" => Some(ResizeMode::Stretch),
+            "shortest_side" => Some(ResizeMode::ResizeShortestSide(size("size")?)),
+            "letterbox" => Some(ResizeMode::Letterbox),
+            "model_input" => Some(ResizeMode::ModelInput {
+                height: dim("height")?,
+                width: dim("width")?,
+                stride: size("stride")?,
+            }),
+            "pad_to_square" => Some(ResizeMode::PadToSquare(u8_triple(value.get("fill")?)?)),
+            _ => None,
+        }
+    }
+
+    fn parse_postprocess(section: &Json) -> Result<PostprocessConfig, String> {
+        let mut config = PostprocessConfig::default();
+        if let Some(allowlist) = read(section, "class_allowlist", |value| or_null(value, usizes))? {
+            config.class_allowlist = allowlist;
+        }
+        if let Some(format) = read(section, "confidence_format", |value| match value.as_str()? {
+            "fraction" => Some(ConfidenceFormat::Fraction),
+            "percent" => Some(ConfidenceFormat::Percent),
+            _ => None,
+        })? {
+            config.confidence_format = format;
+        }
+        if let Some(decimals) = read(section, "timing_decimals", |value| {
+            or_null(value, |decimals| u32::try_from(decimals.as_usize()?).ok())
+        })? {
+            config.timing_decimals = decimals;
+        }
+        if let Some(outputs) = read(section, "requested_outputs", |value| {
+            or_null(value, |names| owned_strings(names).filter(|names| !names.is_empty()))
+        })? {
+            config.requested_outputs = outputs;
+        }
+        if let Some(groups) = read(section, "class_groups", |value| {
+            or_null(value, |groups| {
+                let mapping = groups
+                    .get("class_to_group")?
+                    .as_array()?
+                    .iter()
+                    .map(|group| i32::try_from(group.as_i64()?).ok())
+                    .collect::<Option<Vec<_>>>()?;
+                ClassGroups::new(&mapping, owned_strings(groups.get("names")?)?)
+            })
+        })? {
+            config.class_groups = groups;
+        }
+        if let Some(heads) = read(section, "classification_heads", |value| {
+            value
+                .as_array()?
+                .iter()
+                .map(|head| {
+                    let offset = i32::try_from(head.get("offset")?.as_usize()?).ok()?;
+                    let size = i32::try_from(head.get("size")?.as_usize()?).ok()?;
+                    ClassificationHead::new(offset, size, head.get("name")?.as_str()?.to_string())
+                })
+                .collect::<Option<Vec<_>>>()
+        })? {
+            config.classification_heads = heads;
+        }
+        if let Some(on) = read(section, "region_predictions", Json::as_bool)? {
+            config.region_predictions = on;
+        }
+        if let Some(cap) = read(section, "output_cap", |value| {
+            or_null(value, |cap| {
+                let subset = match cap.get("subset")?.as_str()? {
+                    "first" => ReturnedSubset::First,
+                    "largest" => ReturnedSubset::Largest,
+                    _ => return None,
+                };
+                Some(OutputCap { max_elements: cap.get("max_elements")?.as_usize()?, subset })
+            })
+        })? {
+            config.output_cap = cap;
+        }
+        if let Some(max_classes) = read(section, "max_softmax_classes", |value| or_null(value, Json::as_usize))? {
+            config.max_softmax_classes = max_classes;
+        }
+        if let Some(calibration) = read(section, "calibration", |value| {
+            or_null(value, |calibration| {
+                let class_bias = calibration
+                    .get("class_bias")?
+                    .as_array()?
+                    .iter()
+                    .map(|bias| bias.as_f64().map(|bias| bias as f32))
+                    .collect::<Option<Vec<_>>>()?;
+                Calibration::new(calibration.get("temperature")?.as_f64()? as f32, class_bias)
+            })
+        })? {
+            config.calibration = calibration;
+        }
+        if let Some(weights) = read(section, "ensemble_weights", |value| {
+            or_null(value, |weights| {
+                weights
+                    .as_array()?
+                    .iter()
+                    .map(|weight| weight.as_f64().map(|weight| weight as f32))
+                    .collect::<Option<Vec<_>>>()
+            })
+        })? {
+            config.ensemble_weights = weights;
+        }
+        if let Some(filter) = read(section, "detection_filter", |value| {
+            let defaults = DetectionFilter::default();
+            let conf_threshold = match value.get("conf_threshold") {
+                None | Some(Json::Null) => defaults.conf_threshold,
+                Some(threshold) => threshold.as_f64()? as f32,
+            };
+            let iou_threshold = match value.get("iou_threshold") {
+                None => defaults.iou_threshold,
+                Some(threshold) => threshold.as_f64().filter(|t| (0.0..=1.0).contains(t))? as f32,
+            };
+            let classes = or_null(value.get("classes").unwrap_or(&Json::Null), usizes)?;
+            Some(DetectionFilter { conf_threshold, iou_threshold, classes })
+        })? {
+            config.detection_filter = filter;
+        }
+        Ok(config)
+    }
+
+    fn parse_session(section: &Json) -> Result<SessionConfig, String> {
+        let mut config = SessionConfig::default();
+        if let Some(deterministic) = read(section, "deterministic", Json::as_bool)? {
+            config.deterministic = deterministic;
+        }
+        if let Some(threads) = read(section, "inter_op_threads", Json::as_usize)? {
+            config.inter_op_threads = threads;
+        }
+        if let Some(name) = read(section, "input_name", |value| or_null(value, |name| Some(name.as_str()?.to_string())))? {
+            config.input_name = name;
+        }
+        if let Some(providers) = read(section, "execution_providers", |value| {
+            value
+                .as_array()?
+                .iter()
+                .map(|provider| {
+                    [ExecutionProviderKind::Cpu, ExecutionProviderKind::Nnapi, ExecutionProviderKind::Xnnpack]
+                        .into_iter()
+                        .find(|&kind| Some(provider_name(kind)) == provider.as_str())
+                })
+                .collect::<Option<Vec<_>>>()
+        })? {
+            config.execution_providers = providers;
+        }
+        if let Some(arena) = read(section, "arena", |value| {
+            or_null(value, |arena| {
+                let extend_strategy = match arena.get("extend_strategy")?.as_str()? {
+                    "next_power_of_two" => ArenaExtendStrategy::NextPowerOfTwo,
+                    "same_as_requested" => ArenaExtendStrategy::SameAsRequested,
+                    _ => return None,
+                };
+                let initial_chunk_bytes = arena.get("initial_chunk_bytes")?.as_usize()?;
+                Some(ArenaConfig { extend_strategy, initial_chunk_bytes })
+            })
+        })? {
+            config.arena = arena;
+        }
+        Ok(config)
+    }
+}
+
+/// Read `key` from a config section with `parse`: `Ok(None)` if the key is absent,
+/// an error naming the key if `parse` rejects its value
+fn read<T>(section: &Json, key: &str, parse: impl FnOnce(&Json) -> Option<T>) -> Result<Option<T>, String> {
+    match section.get(key) {
+        None => Ok(None),
+        Some(value) => parse(value).map(Some).ok_or_else(|| format!("Invalid config value for \"{}\"", key)),
+    }
+}
+
+/// `Some(None)` for JSON null, otherwise `parse` wrapped in `Some` (`None` if it fails)
+fn or_null<T>(value: &Json, parse: impl FnOnce(&Json) -> Option<T>) -> Option<Option<T>> {
+    if value.is_null() { Some(None) } else { parse(value).map(Some) }
+}
+
+fn usizes(value: &Json) -> Option<Vec<usize>> {
+    value.as_array()?.iter().map(Json::as_usize).collect()
+}
+
+fn owned_strings(value: &Json) -> Option<Vec<String>> {
+    value.as_array()?.iter().map(|item| Some(item.as_str()?.to_string())).collect()
+}
+
+fn f32_triple(value: &Json) -> Option<[f32; 3]> {
+    match value.as_array()? {
+        [a, b, c] => Some([a.as_f64()? as f32, b.as_f64()? as f32, c.as_f64()? as f32]),
+        _ => None,
+    }
+}
+
+fn u8_triple(value: &Json) -> Option<[u8; 3]> {
+    let channel = |item: &Json| u8::try_from(item.as_usize()?).ok();
+    match value.as_array()? {
+        [r, g, b] => Some([channel(r)?, channel(g)?, channel(b)?]),
+        _ => None,
+    }
+}
+
+fn provider_name(provider: ExecutionProviderKind) -> &'static str {
+    match provider {
+        ExecutionProviderKind::Cpu => "cpu",
+        ExecutionProviderKind::Nnapi => "nnapi",
+        ExecutionProviderKind::Xnnpack => "xnnpack",
+    }
+}
+
+fn nullable<T>(value: Option<T>, f: impl FnOnce(T) -> String) -> String {
+    value.map(f).unwrap_or_else(|| "null".to_string())
+}
+
+fn list<T: std::fmt::Display>(values: &[T]) -> String {
+    let items: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", json::escape(s))
+}
+
+fn strings(values: &[String]) -> String {
+    let items: Vec<String> = values.iter().map(|value| quote(value)).collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NormalizationPreset;
+    use crate::test_utils::lock_global_state;
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let _guard = lock_global_state();
+        ConfigManager::set_resize_mode(ResizeMode::PadToSquare([1, 2, 3]));
+        ConfigManager::set_normalization(NormalizationPreset::Clip.normalization());
+        ConfigManager::set_quant_params(QuantParams::new(0.02, -3));
+        ConfigManager::set_alpha_background(Some([255, 255, 255]));
+        ConfigManager::set_data_order(DataOrder::Interleaved);
+        ConfigManager::set_per_image_standardization(true);
+        ConfigManager::set_min_image_dimension(Some(64));
+        ConfigManager::set_preprocess_pipeline(Some(Pipeline::parse(r#"[{"op":"flip","direction":"vertical"},{"op":"resize","width":64,"height":48},{"op":"scale","factor":0.5}]"#).unwrap()));
+        ConfigManager::set_class_allowlist(vec![4, 1]);
+        ConfigManager::set_confidence_format(ConfidenceFormat::Percent);
+        ConfigManager::set_timing_decimals(Some(2));
+        ConfigManager::set_requested_outputs(vec!["logits".to_string()]);
+        ConfigManager::set_class_groups(ClassGroups::new(&[0, -1, 1], vec!["cats".to_string(), "do\"gs".to_string()]));
+        ConfigManager::set_classification_heads(vec![ClassificationHead::new(0, 3, "color".to_string()).unwrap()]);
+        ConfigManager::set_region_predictions(true);
+        ConfigManager::set_output_cap(Some(OutputCap { max_elements: 5, subset: ReturnedSubset::Largest }));
+        ConfigManager::set_calibration(Calibration::new(1.5, vec![0.1, -0.2, 0.0]));
+        ConfigManager::set_ensemble_weights(Some(vec![0.75, 0.25]));
+        ConfigManager::set_detection_thresholds(0.25, 0.45);
+        ConfigManager::set_input_name(Some("pixel_values".to_string()));
+        ConfigManager::set_inter_op_threads(2);
+        ConfigManager::set_execution_provider(ExecutionProviderKind::Nnapi);
+        ConfigManager::set_arena_config(Some(ArenaConfig {
+            extend_strategy: ArenaExtendStrategy::SameAsRequested,
+            initial_chunk_bytes: 1 << 20,
+        }));
+        let expected = (
+            ConfigManager::preprocess_config(),
+            ConfigManager::postprocess_config(),
+            ConfigManager::session_config(),
+        );
+
+        let exported = ConfigJson::export();
+        ConfigManager::reset();
+        ConfigJson::import(&exported).unwrap();
+        let imported = (
+            ConfigManager::preprocess_config(),
+            ConfigManager::postprocess_config(),
+            ConfigManager::session_config(),
+        );
+        ConfigManager::reset();
+
+        assert_eq!(imported, expected);
+    }
+
+    #[test]
+    fn test_invalid_import_changes_nothing() {
+        let _guard = lock_global_state();
+        ConfigManager::set_inter_op_threads(3);
+
+        let err = ConfigJson::import(r#"{"schema_version":1,"session":{"inter_op_threads":1},"preprocess":{"resize_mode":{"mode":"zoom"}}}"#)
+            .unwrap_err();
+        assert_eq!(err, "Invalid config value for \"resize_mode\"");
+        assert_eq!(ConfigManager::session_config().inter_op_threads, 3);
+
+        ConfigJson::import(r#"{"schema_version":1}"#).unwrap();
+        assert_eq!(ConfigManager::session_config(), SessionConfig::default());
+        ConfigManager::reset();
+    }
+
+    #[test]
+    fn test_import_applies_setter_validation() {
+        let _guard = lock_global_state();
+        ConfigManager::reset();
+        let import = |section: &str, body: &str| ConfigJson::import(&format!(r#"{{"schema_version":1,"{}":{}}}"#, section, body));
+
+        assert!(import("preprocess", r#"{"max_image_pixels":0}"#).is_err());
+        assert!(import("postprocess", r#"{"detection_filter":{"iou_threshold":1.5}}"#).is_err());
+        assert!(import("postprocess", r#"{"detection_filter":{"iou_threshold":-0.1}}"#).is_err());
+        assert!(import("session", r#"{"inter_op_threads":-1}"#).is_err());
+        let err = import("preprocess", r#"{"pipeline":[{"op":"crop","width":8,"height":8},{"op":"blur"}]}"#).unwrap_err();
+        assert_eq!(err, "Invalid config value for \"pipeline\": Pipeline step 1: unknown op 'blur'");
+        assert_eq!(ConfigManager::preprocess_config(), PreprocessConfig::default());
+
+        import("postprocess", r#"{"detection_filter":{"iou_threshold":1}}"#).unwrap();
+        assert_eq!(ConfigManager::postprocess_config().detection_filter.iou_threshold, 1.0);
+        ConfigManager::reset();
+    }
+}
@@ -1,17 +1,65 @@
-/// Configuration constants for ONNX inference
+//! Configuration constants for ONNX inference
 
 /// Standard ImageNet input dimensions
 pub const IMAGE_WIDTH: u32 = 224;
 pub const IMAGE_HEIGHT: u32 = 224;
 
+/// Decode guards: images larger than this are rejected before their pixels are allocated
+pub const MAX_IMAGE_DIMENSION: u32 = 16384;
+pub const DEFAULT_MAX_IMAGE_PIXELS: u64 = 64_000_000;
+
+/// Gray fill value for letterbox padding
+pub const LETTERBOX_FILL: u8 = 114;
+
+/// BT.601 luma weights (0.299, 0.587, 0.114) in 16-bit fixed point; they sum to `LUMA_ONE`
+pub const LUMA_WEIGHTS: [u32; 3] = [19595, 38470, 7471];
+pub const LUMA_ONE: u32 = 1 << 16;
+
 /// ImageNet normalization constants (ImageNet dataset statistics)
 pub const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
 pub const IMAGENET_STD: [f32; 3] = [0.229, 0.224, 0.225];
 
+/// Inception-style normalization: maps [0, 1] pixels to [-1, 1]
+pub const INCEPTION_MEAN: [f32; 3] = [0.5, 0.5, 0.5];
+pub const INCEPTION_STD: [f32; 3] = [0.5, 0.5, 0.5];
+
+/// OpenAI CLIP normalization constants (as published, rounded to f32)
+#[allow(clippy::excessive_precision)]
+pub const CLIP_MEAN: [f32; 3] = [0.48145466, 0.4578275, 0.40821073];
+#[allow(clippy::excessive_precision)]
+pub const CLIP_STD: [f32; 3] = [0.26862954, 0.26130258, 0.27577711];
+
+/// Version of the serialized result format, reported as `schema_version` in every JSON output.
+/// Bump whenever a serialized field is added, removed or changes meaning. A field that is only
+/// emitted when the caller opted into it (e.g. `request_id`) can be added without a bump, since
+/// output that doesn't opt in is unchanged.
+/// 1: JSON outputs are objects `{"schema_version":1,...}` wrapping the former top-level arrays
+/// 2: `confidence` follows the configured `ConfidenceFormat`, so it can be a percentage in `[0, 100]`
+/// 3: results served from the result cache carry `"cached":true` and zero timings
+pub const RESULT_SCHEMA_VERSION: u32 = 3;
+
 /// Classification thresholds and limits
 pub const TOP_K_PREDICTIONS: usize = 5;
+/// Default expected class count (see `LabelsManager::set_expected_class_count`)
 pub const MIN_CLASSIFICATION_CLASSES: usize = 1000;
 
+/// Values copied per JNI array region call when returning outputs (4 MiB of f32), so very large
+/// outputs don't go through a single huge region copy
+pub const OUTPUT_MARSHAL_CHUNK: usize = 1 << 20;
+
+/// Maximum number of ONNX sessions kept loaded at once (oldest is evicted first)
+pub const MAX_CACHED_MODELS: usize = 4;
+
+/// Maximum number of ONNX Runtime log entries kept in memory
+pub const RUNTIME_LOG_CAPACITY: usize = 256;
+
+/// Leading bytes of a gzip stream
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Largest model a gzip-compressed model may inflate to (1 GiB), so a small crafted archive
+/// can't exhaust memory
+pub const MAX_INFLATED_MODEL_BYTES: u64 = 1 << 30;
+
 /// Fallback ImageNet class labels (first 15 classes)
 pub const FALLBACK_LABELS: &[&str] = &[
     "tench",
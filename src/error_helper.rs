@@ -7,15 +7,33 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLastError(
     use std::ptr;
     use crate::inference::InferenceEngine;
     
-    if let Some(error) = InferenceEngine::get_last_error() {
-        match env.new_string(&error) {
-            Ok(jstr) => return jstr.into_raw(),
-            Err(_) => {}
-        }
+    if let Some(error) = InferenceEngine::get_last_error()
+        && let Ok(jstr) = env.new_string(&error)
+    {
+        return jstr.into_raw();
     }
     
     match env.new_string("No error message available") {
         Ok(jstr) => jstr.into_raw(),
         Err(_) => ptr::null_mut(),
     }
-}
\ No newline at end of file
+}
+
+// Get last error as JSON: {"code":..,"variant":"ModelNotFound","message":..}; null if no error was stored.
+// Errors that aren't an InferenceError (e.g. invalid setter arguments) report code 0 and variant "Other".
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLastErrorJsonNative(
+    env: jni::JNIEnv,
+    _class: jni::objects::JClass,
+) -> jni::sys::jstring {
+    use std::ptr;
+    use crate::inference::InferenceEngine;
+
+    match InferenceEngine::last_error_json() {
+        Some(json) => match env.new_string(json) {
+            Ok(jstr) => jstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
@@ -1,4 +1,5 @@
 /// Error handling for ONNX inference operations
+use crate::json;
 use std::fmt;
 
 /// Custom error type for inference operations
@@ -20,6 +21,18 @@ pub enum InferenceError {
     LabelsLoadingFailed(String),
     /// Memory allocation failed
     MemoryError(String),
+    /// Input tensor shape does not match the model's expected input
+    ShapeMismatch(String),
+    /// No inference has produced the requested data yet
+    NoResult(String),
+    /// The caller's deadline passed before the model was run
+    Deadline(String),
+    /// The model produced an output that can't be used (e.g. a tensor with no elements)
+    InvalidOutput(String),
+    /// The configured options contradict each other or the call (e.g. one ensemble weight per model)
+    InvalidConfig(String),
+    /// A caller-supplied input can't be used as given (e.g. tensor dims whose product overflows)
+    InvalidInput(String),
 }
 
 impl fmt::Display for InferenceError {
@@ -33,6 +46,12 @@ impl fmt::Display for InferenceError {
             InferenceError::OutputProcessingFailed(msg) => write!(f, "Failed to process output: {}", msg),
             InferenceError::LabelsLoadingFailed(msg) => write!(f, "Failed to load labels: {}", msg),
             InferenceError::MemoryError(msg) => write!(f, "Memory allocation failed: {}", msg),
+            InferenceError::ShapeMismatch(msg) => write!(f, "Input shape mismatch: {}", msg),
+            InferenceError::NoResult(msg) => write!(f, "No inference result available: {}", msg),
+            InferenceError::Deadline(msg) => write!(f, "Deadline exceeded: {}", msg),
+            InferenceError::InvalidOutput(msg) => write!(f, "Invalid model output: {}", msg),
+            InferenceError::InvalidConfig(msg) => write!(f, "Invalid configuration: {}", msg),
+            InferenceError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
         }
     }
 }
@@ -83,6 +102,94 @@ impl InferenceError {
     pub fn memory_error<S: Into<String>>(msg: S) -> Self {
         InferenceError::MemoryError(msg.into())
     }
+
+    /// Create an input shape mismatch error
+    pub fn shape_mismatch<S: Into<String>>(msg: S) -> Self {
+        InferenceError::ShapeMismatch(msg.into())
+    }
+
+    /// Create a missing inference result error
+    pub fn no_result<S: Into<String>>(msg: S) -> Self {
+        InferenceError::NoResult(msg.into())
+    }
+
+    /// Create a deadline exceeded error
+    pub fn deadline<S: Into<String>>(msg: S) -> Self {
+        InferenceError::Deadline(msg.into())
+    }
+
+    /// Create an invalid output error
+    pub fn invalid_output<S: Into<String>>(msg: S) -> Self {
+        InferenceError::InvalidOutput(msg.into())
+    }
+
+    /// Create an invalid configuration error
+    pub fn invalid_config<S: Into<String>>(msg: S) -> Self {
+        InferenceError::InvalidConfig(msg.into())
+    }
+
+    /// Create an invalid input error
+    pub fn invalid_input<S: Into<String>>(msg: S) -> Self {
+        InferenceError::InvalidInput(msg.into())
+    }
+}
+
+/// Structured accessors for the Java layer
+impl InferenceError {
+    /// Stable numeric code for the variant (codes are never reused)
+    pub fn code(&self) -> i32 {
+        match self {
+            InferenceError::ModelNotFound(_) => 1,
+            InferenceError::InvalidImageData(_) => 2,
+            InferenceError::SessionCreationFailed(_) => 3,
+            InferenceError::ModelLoadingFailed(_) => 4,
+            InferenceError::InferenceFailed(_) => 5,
+            InferenceError::OutputProcessingFailed(_) => 6,
+            InferenceError::LabelsLoadingFailed(_) => 7,
+            InferenceError::MemoryError(_) => 8,
+            InferenceError::ShapeMismatch(_) => 9,
+            InferenceError::NoResult(_) => 10,
+            InferenceError::Deadline(_) => 11,
+            InferenceError::InvalidOutput(_) => 12,
+            InferenceError::InvalidConfig(_) => 13,
+            InferenceError::InvalidInput(_) => 14,
+        }
+    }
+
+    /// Variant name, e.g. `"ModelNotFound"`
+    pub fn variant(&self) -> &'static str {
+        match self {
+            InferenceError::ModelNotFound(_) => "ModelNotFound",
+            InferenceError::InvalidImageData(_) => "InvalidImageData",
+            InferenceError::SessionCreationFailed(_) => "SessionCreationFailed",
+            InferenceError::ModelLoadingFailed(_) => "ModelLoadingFailed",
+            InferenceError::InferenceFailed(_) => "InferenceFailed",
+            InferenceError::OutputProcessingFailed(_) => "OutputProcessingFailed",
+            InferenceError::LabelsLoadingFailed(_) => "LabelsLoadingFailed",
+            InferenceError::MemoryError(_) => "MemoryError",
+            InferenceError::ShapeMismatch(_) => "ShapeMismatch",
+            InferenceError::NoResult(_) => "NoResult",
+            InferenceError::Deadline(_) => "Deadline",
+            InferenceError::InvalidOutput(_) => "InvalidOutput",
+            InferenceError::InvalidConfig(_) => "InvalidConfig",
+            InferenceError::InvalidInput(_) => "InvalidInput",
+        }
+    }
+
+    /// `{"code":..,"variant":..,"message":..}` with the display message
+    pub fn to_json(&self) -> String {
+        error_json(self.code(), self.variant(), &self.to_string())
+    }
+}
+
+/// Serialize an error's code, variant name and message as a JSON object
+pub fn error_json(code: i32, variant: &str, message: &str) -> String {
+    format!(
+        "{{\"code\":{},\"variant\":\"{}\",\"message\":\"{}\"}}",
+        code,
+        variant,
+        json::escape(message)
+    )
 }
 
 /// Convert from various error types
@@ -0,0 +1,105 @@
+/// On-device accuracy check of the active model against a labeled image manifest
+use crate::errors::{InferenceError, InferenceResult};
+use crate::inference::InferenceEngine;
+use crate::types::EvaluationReport;
+use std::path::Path;
+
+/// Manifest-driven top-1/top-5 evaluator
+pub struct Evaluator;
+
+impl Evaluator {
+    /// Classify every `imagePath,trueClassId` line of the manifest (relative paths are resolved
+    /// against the manifest's directory). Unreadable images and malformed lines are skipped and reported.
+    pub fn evaluate_manifest(manifest_path: &str) -> InferenceResult<EvaluationReport> {
+        let manifest = std::fs::read_to_string(manifest_path).map_err(|e| {
+            InferenceError::invalid_image(format!("Failed to read manifest '{}': {}", manifest_path, e))
+        })?;
+        let base_dir = Path::new(manifest_path).parent().unwrap_or(Path::new(""));
+
+        let mut report = EvaluationReport::default();
+        for line in manifest.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let Some((image_path, class_id)) = Self::parse_line(line) else {
+                report.skipped.push((line.to_string(), "expected imagePath,trueClassId".to_string()));
+                continue;
+            };
+
+            let image_path = base_dir.join(image_path);
+            let result = match InferenceEngine::run_inference_from_path(&image_path.to_string_lossy()) {
+                Ok(result) if result.is_classification => result,
+                Ok(_) => {
+                    report.skipped.push((line.to_string(), "output is not a classification".to_string()));
+                    continue;
+                }
+                Err(e) => {
+                    report.skipped.push((line.to_string(), e.to_string()));
+                    continue;
+                }
+            };
+
+            report.evaluated += 1;
+            if result.top_prediction().is_some_and(|top| top.class_id == class_id) {
+                report.top1_correct += 1;
+            }
+            if result.top_predictions.iter().take(5).any(|prediction| prediction.class_id == class_id) {
+                report.top5_correct += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Split `imagePath,trueClassId` on the last comma so paths may contain commas
+    fn parse_line(line: &str) -> Option<(&str, usize)> {
+        let (image_path, class_id) = line.rsplit_once(',')?;
+        let image_path = image_path.trim();
+        if image_path.is_empty() {
+            return None;
+        }
+        Some((image_path, class_id.trim().parse().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{encode_png, lock_global_state, onnx, require_ort, write_temp_file};
+
+    #[test]
+    fn test_unreadable_entries_are_skipped() {
+        let _guard = lock_global_state();
+        let manifest = write_temp_file("eval_missing.csv", b"missing_a.png,1\nnot a line\nmissing_b.png,2\n");
+
+        let report = Evaluator::evaluate_manifest(&manifest).unwrap();
+        assert_eq!(report.evaluated, 0);
+        assert_eq!(report.skipped.len(), 3);
+        assert_eq!(report.skipped[1].0, "not a line");
+        assert_eq!(report.top1_accuracy(), 0.0);
+        assert!(report.to_json().contains("\"evaluated\":0"));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_two_image_manifest() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("eval.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let first = write_temp_file("eval_a.png", &encode_png(32, 32, [250, 10, 10]));
+        let second = write_temp_file("eval_b.png", &encode_png(32, 32, [10, 10, 250]));
+
+        // Label the first image with its own top-1 class and the second with a different one
+        let top_class = |path: &str| InferenceEngine::run_inference_from_path(path).unwrap().top_predictions[0].class_id;
+        let first_class = top_class(&first);
+        let wrong_class = (top_class(&second) + 500) % 1000;
+        let manifest = write_temp_file(
+            "eval_manifest.csv",
+            format!("{},{}\n{},{}\n", first, first_class, second, wrong_class).as_bytes(),
+        );
+
+        let report = Evaluator::evaluate_manifest(&manifest).unwrap();
+        assert_eq!(report.evaluated, 2);
+        assert!(report.skipped.is_empty());
+        assert_eq!(report.top1_accuracy(), 0.5);
+    }
+}
@@ -0,0 +1,104 @@
+/// FlatBuffers serialization of inference results (schema: schemas/inference_result.fbs)
+use crate::types::InferenceResult;
+use flatbuffers::{FlatBufferBuilder, VOffsetT, WIPOffset};
+
+// Prediction table slots (vtable offsets follow schema field order)
+const PREDICTION_CLASS_ID: VOffsetT = 4;
+const PREDICTION_CLASS_NAME: VOffsetT = 6;
+const PREDICTION_CONFIDENCE: VOffsetT = 8;
+
+// InferenceResult table slots
+const RESULT_SHAPE: VOffsetT = 4;
+const RESULT_IS_CLASSIFICATION: VOffsetT = 6;
+const RESULT_INFERENCE_TIME_MS: VOffsetT = 8;
+const RESULT_PREPROCESSING_TIME_MS: VOffsetT = 10;
+const RESULT_POSTPROCESSING_TIME_MS: VOffsetT = 12;
+const RESULT_TOTAL_TIME_MS: VOffsetT = 14;
+const RESULT_PREDICTIONS: VOffsetT = 16;
+
+/// Serialize an inference result (shape, timings, predictions) into a FlatBuffer.
+/// Raw output data is not included; use the float array accessors for that.
+pub fn serialize_result(result: &InferenceResult) -> Vec<u8> {
+    let mut builder = FlatBufferBuilder::with_capacity(256);
+
+    let predictions: Vec<WIPOffset<flatbuffers::TableFinishedWIPOffset>> = result
+        .top_predictions
+        .iter()
+        .map(|prediction| {
+            let class_name = builder.create_string(&prediction.class_name);
+            let start = builder.start_table();
+            builder.push_slot::<u32>(PREDICTION_CLASS_ID, prediction.class_id as u32, 0);
+            builder.push_slot_always(PREDICTION_CLASS_NAME, class_name);
+            builder.push_slot::<f32>(PREDICTION_CONFIDENCE, prediction.confidence, 0.0);
+            builder.end_table(start)
+        })
+        .collect();
+    let predictions = builder.create_vector(&predictions);
+
+    let shape: Vec<u32> = result.shape.iter().map(|&dim| dim as u32).collect();
+    let shape = builder.create_vector(&shape);
+
+    let start = builder.start_table();
+    builder.push_slot_always(RESULT_SHAPE, shape);
+    builder.push_slot::<bool>(RESULT_IS_CLASSIFICATION, result.is_classification, false);
+    builder.push_slot::<f32>(RESULT_INFERENCE_TIME_MS, result.inference_time_ms, 0.0);
+    builder.push_slot::<f32>(RESULT_PREPROCESSING_TIME_MS, result.preprocessing_time_ms, 0.0);
+    builder.push_slot::<f32>(RESULT_POSTPROCESSING_TIME_MS, result.postprocessing_time_ms, 0.0);
+    builder.push_slot::<f32>(RESULT_TOTAL_TIME_MS, result.total_time_ms, 0.0);
+    builder.push_slot_always(RESULT_PREDICTIONS, predictions);
+    let root = builder.end_table(start);
+
+    builder.finish(root, None);
+    builder.finished_data().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::ClassificationResult;
+    use flatbuffers::{Follow, ForwardsUOffset, Table, Vector};
+
+    #[test]
+    fn test_flatbuffer_round_trip() {
+        let result = InferenceResult::new_with_timing(
+            vec![0.1, 0.9],
+            vec![1, 2],
+            true,
+            vec![
+                ClassificationResult::new(1, "golden retriever".to_string(), 0.9),
+                ClassificationResult::new(0, "tench".to_string(), 0.1),
+            ],
+            12.5,
+            3.0,
+            0.5,
+        );
+
+        let buf = serialize_result(&result);
+
+        // Decode with the raw table API, mirroring what flatc-generated readers do
+        unsafe {
+            let root = <ForwardsUOffset<Table>>::follow(&buf, 0);
+            let shape = root.get::<ForwardsUOffset<Vector<u32>>>(RESULT_SHAPE, None).unwrap();
+            assert_eq!(shape.iter().collect::<Vec<_>>(), vec![1, 2]);
+            assert_eq!(root.get::<bool>(RESULT_IS_CLASSIFICATION, Some(false)), Some(true));
+            assert_eq!(root.get::<f32>(RESULT_INFERENCE_TIME_MS, Some(0.0)), Some(12.5));
+            assert_eq!(root.get::<f32>(RESULT_PREPROCESSING_TIME_MS, Some(0.0)), Some(3.0));
+            assert_eq!(root.get::<f32>(RESULT_POSTPROCESSING_TIME_MS, Some(0.0)), Some(0.5));
+            assert_eq!(root.get::<f32>(RESULT_TOTAL_TIME_MS, Some(0.0)), Some(16.0));
+
+            let predictions = root
+                .get::<ForwardsUOffset<Vector<ForwardsUOffset<Table>>>>(RESULT_PREDICTIONS, None)
+                .unwrap();
+            assert_eq!(predictions.len(), 2);
+
+            let top = predictions.get(0);
+            assert_eq!(top.get::<u32>(PREDICTION_CLASS_ID, Some(0)), Some(1));
+            assert_eq!(top.get::<ForwardsUOffset<&str>>(PREDICTION_CLASS_NAME, None), Some("golden retriever"));
+            assert_eq!(top.get::<f32>(PREDICTION_CONFIDENCE, Some(0.0)), Some(0.9));
+
+            let second = predictions.get(1);
+            assert_eq!(second.get::<u32>(PREDICTION_CLASS_ID, Some(0)), Some(0));
+            assert_eq!(second.get::<ForwardsUOffset<&str>>(PREDICTION_CLASS_NAME, None), Some("tench"));
+        }
+    }
+}
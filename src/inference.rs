@@ -1,51 +1,218 @@
 /// Core ONNX inference functionality
-use crate::constants::{IMAGE_HEIGHT, IMAGE_WIDTH, IMAGENET_MEAN, IMAGENET_STD, TOP_K_PREDICTIONS, MIN_CLASSIFICATION_CLASSES};
-use crate::errors::{InferenceError, InferenceResult};
-use crate::labels::LabelsManager;
-use crate::types::{ClassificationResult, InferenceResult as InferenceOutput};
+use crate::arena::CpuArena;
+use crate::config::{ClassificationHead, ConfigManager, ExecutionProviderKind, PostprocessConfig, PreprocessConfig, QuantParams, ResizeMode, SessionConfig};
+use crate::constants::{GZIP_MAGIC, IMAGE_HEIGHT, IMAGE_WIDTH, MAX_CACHED_MODELS, MAX_INFLATED_MODEL_BYTES, OUTPUT_MARSHAL_CHUNK, TOP_K_PREDICTIONS};
+use crate::errors::{InferenceError, InferenceResult, error_json};
+use crate::keep_warm::KeepWarm;
+use crate::labels::{LabelResolution, LabelsManager};
+use crate::listener::ResultListener;
+use crate::postprocess::Postprocessor;
+use crate::preprocess::{ImageInput, ImagePreprocessor, LetterboxInfo, PixelFormat, Prepared, QuantizedData, QuantizedTensor, Roi};
+use crate::runtime_log::RuntimeLog;
+use crate::trace::TimingTrace;
+use crate::tta::{Tta, TtaMode};
+use crate::model_proto;
+use crate::types::{BenchmarkStats, ClassificationResult, GroupPrediction, HeadPredictions, InferenceResult as InferenceOutput, LoadedModelInfo, ModelInfo, OutputTensor, TensorInfo, TensorStats};
 use ndarray::Array4;
-use ort::{session::Session, value::Value};
-use std::sync::Mutex;
-use std::time::Instant;
+use ort::execution_providers::{CPUExecutionProvider, ExecutionProvider, NNAPIExecutionProvider, XNNPACKExecutionProvider};
+use ort::logging::LogLevel;
+use ort::session::builder::SessionBuilder;
+use ort::session::{RunOptions, run_options::OutputSelector};
+use ort::tensor::TensorElementType;
+use ort::{session::Session, value::{DynValue, Value, ValueType}};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::time::{Duration, Instant};
 
-/// Static storage for last inference result
-static LAST_RESULT: Mutex<Option<InferenceOutput>> = Mutex::new(None);
+/// Per-run state the JNI entry points share between calls: the last result, input, error and
+/// load attempt, and the result cache. It is not the only global state:
+///
+/// - Loaded sessions stay in `CACHED_SESSION`. That lock is held for the whole of a run, and
+///   holding this one that long would block every getter until the run finishes.
+/// - Labels stay in `LabelsManager`'s statics. They are resolved while a run holds the session
+///   lock, so folding them in here would either serialize label getters behind runs or make runs
+///   take this lock under the session lock.
+/// - Pre/postprocessing and session options stay in `ConfigManager`, which runs snapshot once.
+pub(crate) struct Engine {
+    last_result: Option<InferenceOutput>,
+    /// Last preprocessed float input tensor and the config that made it, for debug snapshots (only
+    /// kept while input capture is on; `None` after a quantized run)
+    last_input: Option<(Array4<f32>, PreprocessConfig)>,
+    /// Keep a copy of each recorded float input for `save_debug_input`
+    input_capture_enabled: bool,
+    /// Last error message and the `InferenceError` behind it (`None` for plain messages)
+    last_error: Option<(String, Option<InferenceError>)>,
+    /// Path and outcome (`None` = success) of the most recent load attempt
+    last_load_attempt: Option<(String, Option<String>)>,
+    /// Reuse the previous result when `run_inference` gets byte-identical input
+    result_cache_enabled: bool,
+    /// The previous `run_inference` result and what produced it (only kept while the cache is on)
+    cached_run: Option<(RunKey, InferenceOutput)>,
+}
 
-/// Static storage for last error message
-static LAST_ERROR: Mutex<Option<String>> = Mutex::new(None);
+/// Everything a `run_inference` result depends on: the input bytes' hash, the active model, the
+/// pre/postprocessing options and the labels state. Two runs with equal keys produce the same result.
+#[derive(Debug, Clone, PartialEq)]
+struct RunKey {
+    input_hash: u64,
+    model_path: Option<String>,
+    preprocess: PreprocessConfig,
+    postprocess: PostprocessConfig,
+    /// `LabelsManager::generation` of the labels class names were taken from
+    labels_generation: u64,
+    strict_labels: bool,
+    expected_class_count: usize,
+}
 
-/// Static storage for single cached ONNX session
-static CACHED_SESSION: Mutex<Option<(String, Session)>> = Mutex::new(None);
+impl Engine {
+    const fn new() -> Self {
+        Self {
+            last_result: None,
+            last_input: None,
+            input_capture_enabled: false,
+            last_error: None,
+            last_load_attempt: None,
+            result_cache_enabled: false,
+            cached_run: None,
+        }
+    }
+}
 
-/// ONNX inference engine
-pub struct InferenceEngine;
+/// The process-wide engine state
+static ENGINE: Mutex<Engine> = Mutex::new(Engine::new());
 
-impl InferenceEngine {
-    /// Preprocess image bytes into normalized tensor
-    fn preprocess_image(image_bytes: &[u8]) -> InferenceResult<Array4<f32>> {
-        // Load image from bytes
-        let img = image::load_from_memory(image_bytes)
-            .map_err(|e| InferenceError::invalid_image(format!("Failed to load image from bytes: {}", e)))?;
+/// Lock the engine state, recovering it if a previous holder panicked (every field is
+/// replaced whole, so a panic can't leave one half-written)
+fn engine() -> MutexGuard<'static, Engine> {
+    ENGINE.lock().unwrap_or_else(|poisoned| {
+        ENGINE.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// A loaded ONNX session together with the options it was built with
+struct CachedModel {
+    path: String,
+    session_config: SessionConfig,
+    session: Session,
+    /// Determined from the first output's declared shape when the model loads
+    looks_like_classifier: bool,
+    /// Class axis length of the first output's declared shape, when it is static
+    class_count: Option<usize>,
+    /// Size of the serialized model, used as an approximate memory footprint
+    size_bytes: usize,
+    /// Index into `session.inputs` of the input that receives the image tensor
+    image_input: usize,
+    /// Inputs, outputs, metadata and provider captured at load time
+    info: ModelInfo,
+}
+
+impl CachedModel {
+    /// What preprocessing needs to know about the image input
+    fn input_spec(&self) -> InputSpec {
+        let input_type = &self.session.inputs[self.image_input].input_type;
+        InputSpec {
+            shape: input_type.tensor_shape().map(|shape| shape.to_vec()),
+            element_type: input_type.tensor_type(),
+        }
+    }
+}
+
+/// Declared shape (negative dims are dynamic) and element type of a model's image input, which
+/// preprocessing follows
+#[derive(Debug, Clone, Default)]
+struct InputSpec {
+    shape: Option<Vec<i64>>,
+    element_type: Option<TensorElementType>,
+}
+
+/// Loaded sessions in load order, plus which one `run_inference` uses
+struct ModelCache {
+    models: Vec<CachedModel>,
+    active: Option<usize>,
+}
+
+impl ModelCache {
+    const fn new() -> Self {
+        Self {
+            models: Vec::new(),
+            active: None,
+        }
+    }
 
-        // Resize to required dimensions
-        let resized = img.resize_exact(IMAGE_WIDTH, IMAGE_HEIGHT, image::imageops::FilterType::Lanczos3);
-        let rgb_img = resized.to_rgb8();
+    fn active(&self) -> Option<&CachedModel> {
+        self.active.and_then(|index| self.models.get(index))
+    }
 
-        // Create normalized tensor
-        let mut input_array = Array4::<f32>::zeros((1, 3, IMAGE_HEIGHT as usize, IMAGE_WIDTH as usize));
+    fn active_mut(&mut self) -> Option<&mut CachedModel> {
+        self.active.and_then(|index| self.models.get_mut(index))
+    }
 
-        for (x, y, pixel) in rgb_img.enumerate_pixels() {
-            let [r, g, b] = pixel.0;
-            
-            // Normalize using ImageNet statistics
-            input_array[[0, 0, y as usize, x as usize]] = (r as f32 / 255.0 - IMAGENET_MEAN[0]) / IMAGENET_STD[0];
-            input_array[[0, 1, y as usize, x as usize]] = (g as f32 / 255.0 - IMAGENET_MEAN[1]) / IMAGENET_STD[1];
-            input_array[[0, 2, y as usize, x as usize]] = (b as f32 / 255.0 - IMAGENET_MEAN[2]) / IMAGENET_STD[2];
+    /// Add a model and make it active, evicting the oldest one when the cache is full
+    fn insert(&mut self, model: CachedModel) {
+        self.models.retain(|cached| cached.path != model.path);
+        if self.models.len() >= MAX_CACHED_MODELS {
+            self.models.remove(0);
         }
+        self.models.push(model);
+        self.set_active(Some(self.models.len() - 1));
+    }
+
+    /// Switch the active model, telling `LabelsManager` its class count so labels resolve against it
+    fn set_active(&mut self, index: Option<usize>) {
+        self.active = index;
+        LabelsManager::set_model_class_count(self.active().and_then(|cached| cached.class_count));
+    }
+}
+
+/// Static storage for cached ONNX sessions
+static CACHED_SESSION: Mutex<ModelCache> = Mutex::new(ModelCache::new());
+
+/// Lock the session cache, recovering it if a previous holder panicked.
+/// The cache holds no half-updated invariants across a panic (entries are only
+/// pushed/removed whole), so the inner value is still safe to use.
+fn lock_cache() -> MutexGuard<'static, ModelCache> {
+    CACHED_SESSION.lock().unwrap_or_else(|poisoned| {
+        CACHED_SESSION.clear_poison();
+        poisoned.into_inner()
+    })
+}
+
+/// Quote a CSV field containing a comma, quote or line break (RFC 4180), doubling embedded quotes
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// An input tensor's shape with its values
+type ShapedInput = (Vec<i64>, TensorData);
+
+/// Input tensor values in the element type the model's image input expects
+enum TensorData {
+    F32(Vec<f32>),
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+}
 
-        Ok(input_array)
+impl TensorData {
+    /// Wrap the values with their shape as an ORT input tensor
+    fn into_value(self, shape: Vec<i64>) -> InferenceResult<DynValue> {
+        let value = match self {
+            TensorData::F32(data) => Value::from_array((shape, data)).map(|tensor| tensor.into_dyn()),
+            TensorData::U8(data) => Value::from_array((shape, data)).map(|tensor| tensor.into_dyn()),
+            TensorData::I8(data) => Value::from_array((shape, data)).map(|tensor| tensor.into_dyn()),
+        };
+        value.map_err(|e| InferenceError::inference_failed(format!("Failed to create input tensor: {:?}", e)))
     }
+}
+
+/// ONNX inference engine
+pub struct InferenceEngine;
 
+impl InferenceEngine {
     /// Apply softmax activation to raw logits
     fn softmax(input: &[f32]) -> Vec<f32> {
         let max_val = input.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
@@ -54,193 +221,2755 @@ impl InferenceEngine {
         exp_values.iter().map(|&x| x / sum).collect()
     }
 
-    /// Get top K predictions from probabilities
-    fn get_top_predictions(probabilities: &[f32], k: usize) -> Vec<ClassificationResult> {
-        let mut indexed_probs: Vec<(usize, f32)> = probabilities
-            .iter()
-            .enumerate()
-            .map(|(i, &prob)| (i, prob))
-            .collect();
-            
-        indexed_probs.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    /// Apply softmax to each `cols`-wide row of a row-major matrix independently
+    fn softmax_2d(input: &[f32], cols: usize) -> Vec<f32> {
+        input.chunks(cols.max(1)).flat_map(Self::softmax).collect()
+    }
+
+    /// Top-1 prediction ranked on raw logits, for outputs too large for a full softmax. The
+    /// confidence is still the class's softmax probability, computed in one pass without
+    /// materializing the distribution; the logit goes in `raw_score`.
+    fn top_prediction_without_softmax(
+        logits: &[f32],
+        config: &PostprocessConfig,
+        model_classes: Option<usize>,
+    ) -> Vec<ClassificationResult> {
+        let mut top = Self::get_top_predictions(logits, 1, config, model_classes);
+        let max_val = logits.iter().fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+        let sum: f32 = logits.iter().map(|&x| (x - max_val).exp()).sum();
+        for prediction in &mut top {
+            prediction.raw_score = Some(prediction.confidence);
+            prediction.confidence = (prediction.confidence - max_val).exp() / sum;
+        }
+        top
+    }
+
+    /// Softmax over `logits`, or `None` when the output is larger than `max_classes`
+    fn softmax_within_limit(logits: &[f32], max_classes: Option<usize>) -> Option<Vec<f32>> {
+        match max_classes {
+            Some(max_classes) if logits.len() > max_classes => None,
+            _ => Some(Self::softmax(logits)),
+        }
+    }
+
+    /// Get top K predictions from probabilities, restricted to the class allowlist when one is set.
+    /// Labels resolve against `model_classes`, the class count of the model that produced them.
+    fn get_top_predictions(
+        probabilities: &[f32],
+        k: usize,
+        config: &PostprocessConfig,
+        model_classes: Option<usize>,
+    ) -> Vec<ClassificationResult> {
+        let ranked = match config.class_allowlist.as_deref() {
+            Some(allowed) => Postprocessor::top_k_among(probabilities, k, allowed),
+            None => Postprocessor::top_k(probabilities, k),
+        };
+        let labels = LabelsManager::labels_for(model_classes);
+        ranked
+            .into_iter()
+            .map(|(idx, prob)| {
+                let label = labels.get(idx).cloned().unwrap_or_else(|| LabelsManager::label_for(idx, model_classes));
+                ClassificationResult::new(idx, label, prob)
+            })
+            .collect()
+    }
 
-        indexed_probs
+    /// Softmax each head's slice of `logits` independently and rank its top `k` classes.
+    /// Class names are the labels at the head's offset, so one labels file can cover every head.
+    fn head_predictions(logits: &[f32], heads: &[ClassificationHead], k: usize) -> InferenceResult<Vec<HeadPredictions>> {
+        heads
             .iter()
-            .take(k)
-            .map(|&(idx, prob)| {
-                ClassificationResult::new(idx, LabelsManager::get_label(idx), prob)
+            .map(|head| {
+                let slice = logits.get(head.offset..head.offset + head.size).ok_or_else(|| {
+                    InferenceError::output_processing_failed(format!(
+                        "Head '{}' covers outputs {}..{} but the model outputs {} values",
+                        head.name,
+                        head.offset,
+                        head.offset + head.size,
+                        logits.len()
+                    ))
+                })?;
+                let predictions = Postprocessor::top_k(&Self::softmax(slice), k)
+                    .into_iter()
+                    .map(|(idx, prob)| ClassificationResult::new(idx, LabelsManager::get_label(head.offset + idx), prob))
+                    .collect();
+                Ok(HeadPredictions {
+                    head: head.name.clone(),
+                    predictions,
+                })
             })
             .collect()
     }
 
-    /// Load ONNX model from file and cache it (replaces any existing cached model)
+    /// Top-1 class of each row of an `[N, C]` output (after dropping a leading batch dim of 1),
+    /// softmaxed per row, labelled for a model with `model_classes` classes. `None` if the output
+    /// isn't two-dimensional.
+    fn region_predictions(data: &[f32], shape: &[usize], model_classes: Option<usize>) -> Option<Vec<ClassificationResult>> {
+        let unbatched = match shape {
+            [1, rest @ ..] => rest,
+            _ => shape,
+        };
+        let [_, classes] = *unbatched else {
+            return None;
+        };
+        let probabilities = Self::softmax_2d(data, classes);
+        Some(
+            probabilities
+                .chunks(classes)
+                .filter_map(|row| Postprocessor::top_k(row, 1).first().copied())
+                .map(|(idx, prob)| ClassificationResult::new(idx, LabelsManager::label_for(idx, model_classes), prob))
+                .collect(),
+        )
+    }
+
+    /// Verify a preprocessed tensor shape against the model's declared input shape
+    fn check_input_shape(model_shape: &[i64], input_shape: &[i64]) -> InferenceResult<()> {
+        let compatible = model_shape.len() == input_shape.len()
+            && model_shape
+                .iter()
+                .zip(input_shape)
+                .all(|(&expected, &actual)| expected < 0 || expected == actual);
+
+        if compatible {
+            Ok(())
+        } else {
+            Err(InferenceError::shape_mismatch(format!(
+                "model expects {:?}, preprocessed input is {:?}",
+                model_shape, input_shape
+            )))
+        }
+    }
+
+    /// Pick the input that receives the image: the configured name if set,
+    /// otherwise the first rank-4 input, otherwise input 0
+    fn select_image_input(inputs: &[(&str, Option<&[i64]>)], preferred: Option<&str>) -> InferenceResult<usize> {
+        if let Some(name) = preferred {
+            return inputs.iter().position(|&(input, _)| input == name).ok_or_else(|| {
+                let names: Vec<&str> = inputs.iter().map(|&(input, _)| input).collect();
+                InferenceError::model_loading_failed(format!("Model has no input named '{}' (inputs: {:?})", name, names))
+            });
+        }
+
+        Ok(inputs
+            .iter()
+            .position(|&(_, shape)| shape.is_some_and(|dims| dims.len() == 4))
+            .unwrap_or(0))
+    }
+
+    /// Get the image input's name, failing clearly if the model needs inputs we can't provide
+    fn image_input_name(session: &Session, image_input: usize) -> InferenceResult<String> {
+        let name = session.inputs[image_input].name.clone();
+        let others: Vec<&str> = session
+            .inputs
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| index != image_input)
+            .map(|(_, input)| input.name.as_str())
+            .collect();
+
+        if others.is_empty() {
+            Ok(name)
+        } else {
+            Err(InferenceError::inference_failed(format!(
+                "Model requires inputs {:?} besides image input '{}'; only the image input can be provided",
+                others, name
+            )))
+        }
+    }
+
+    /// Create a session builder with the configured threading/memory options
+    fn session_builder(config: &SessionConfig) -> InferenceResult<(SessionBuilder, &'static str)> {
+        // Make sure ORT log messages are captured before the first session exists
+        RuntimeLog::init()?;
+
+        let builder = Session::builder()
+            .map_err(|e| InferenceError::session_failed(format!("Failed to create ONNX session builder: {:?}", e)))?;
+
+        if config.deterministic {
+            // Single thread, sequential execution and no CPU arena for run-to-run reproducibility
+            let cpu = CPUExecutionProvider::default();
+            let builder = builder
+                .with_intra_threads(1)
+                .and_then(|b| b.with_inter_threads(1))
+                .and_then(|b| b.with_parallel_execution(false))
+                .and_then(|b| b.with_deterministic_compute(true))
+                .and_then(|b| b.with_execution_providers([cpu.clone().with_arena_allocator(false).build()]))
+                .map_err(|e| {
+                    InferenceError::session_failed(format!("Failed to apply deterministic session options: {:?}", e))
+                })?;
+            return Ok((builder, cpu.name()));
+        }
+
+        let builder = match config.arena {
+            Some(arena) => {
+                CpuArena::register(arena)?;
+                builder
+                    .with_env_allocators()
+                    .map_err(|e| InferenceError::session_failed(format!("Failed to use the shared CPU arena: {:?}", e)))?
+            }
+            None => builder,
+        };
+
+        let mut builder = if config.inter_op_threads == 0 {
+            builder
+        } else {
+            // Inter-op threads only take effect in parallel execution mode
+            builder
+                .with_parallel_execution(true)
+                .and_then(|b| b.with_inter_threads(config.inter_op_threads))
+                .map_err(|e| InferenceError::session_failed(format!("Failed to set inter-op threads: {:?}", e)))?
+        };
+
+        let provider = Self::register_provider(&mut builder, &config.execution_providers);
+        Ok((builder, provider))
+    }
+
+    /// Try each requested execution provider in order and return the name of the first that
+    /// registers (CPU ends the chain). If none does, the session stays on ORT's CPU provider.
+    fn register_provider(builder: &mut SessionBuilder, chain: &[ExecutionProviderKind]) -> &'static str {
+        let cpu = CPUExecutionProvider::default().name();
+        for &kind in chain {
+            let (name, registered) = match kind {
+                ExecutionProviderKind::Cpu => return cpu,
+                ExecutionProviderKind::Nnapi => {
+                    let nnapi = NNAPIExecutionProvider::default();
+                    (nnapi.name(), nnapi.register(builder))
+                }
+                ExecutionProviderKind::Xnnpack => {
+                    let xnnpack = XNNPACKExecutionProvider::default();
+                    (xnnpack.name(), xnnpack.register(builder))
+                }
+            };
+            match registered {
+                Ok(()) => {
+                    RuntimeLog::push(LogLevel::Info, &format!("Registered {}", name));
+                    return name;
+                }
+                Err(e) => RuntimeLog::push(LogLevel::Warning, &format!("{} unavailable, trying the next provider: {}", name, e)),
+            }
+        }
+        cpu
+    }
+
+    /// Load ONNX model from file, cache it and make it the active model
     pub fn load_model(model_path: &str) -> InferenceResult<()> {
+        let result = Self::try_load_model(model_path, ConfigManager::session_config());
+        Self::record_load_attempt(model_path, &result);
+        result
+    }
+
+    /// Load a model trying `providers` in order for this load only (the first that registers
+    /// wins, CPU if none does) and return the provider its session runs on
+    pub fn load_model_with_providers(model_path: &str, providers: Vec<ExecutionProviderKind>) -> InferenceResult<String> {
+        let session_config = SessionConfig {
+            execution_providers: providers,
+            ..ConfigManager::session_config()
+        };
+        let result = Self::try_load_model(model_path, session_config);
+        Self::record_load_attempt(model_path, &result);
+        result?;
+        Ok(Self::active_execution_provider().unwrap_or_default())
+    }
+
+    /// Load an ONNX model from `length` bytes at `offset` of an open file descriptor (e.g. an
+    /// Android `AssetFileDescriptor`), cache it and make it the active model. Gzip-compressed
+    /// models are inflated as in `load_model`. The descriptor is only read, never closed.
+    pub fn load_model_from_fd(fd: i32, offset: i64, length: i64) -> InferenceResult<()> {
+        // File descriptors get reused, so a cached entry under the same key may be another model
+        let source = format!("fd:{}@{}+{}", fd, offset, length);
+        let result = Self::read_fd_range(fd, offset, length)
+            .and_then(|model_bytes| Self::decompress_model(&source, model_bytes))
+            .and_then(|model_bytes| Self::commit_model(&source, &model_bytes, ConfigManager::session_config()));
+        Self::record_load_attempt(&source, &result);
+        result
+    }
+
+    /// Read exactly `length` bytes starting at `offset` without moving the descriptor's file position.
+    /// The range is checked against the file's size before anything is allocated, so a bogus length
+    /// from the caller can't trigger a huge allocation.
+    fn read_fd_range(fd: i32, offset: i64, length: i64) -> InferenceResult<Vec<u8>> {
+        if fd < 0 {
+            return Err(InferenceError::model_loading_failed(format!("Invalid file descriptor: {}", fd)));
+        }
+        if offset < 0 || length <= 0 {
+            return Err(InferenceError::model_loading_failed(format!(
+                "Invalid model range: offset {} and length {} (offset must be >= 0, length > 0)",
+                offset, length
+            )));
+        }
+
+        // SAFETY: `stat` is plain old data that fstat fills in; it is only read after fstat succeeds
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(fd, &mut stat) } != 0 {
+            return Err(InferenceError::model_loading_failed(format!(
+                "Failed to stat file descriptor {}: {}",
+                fd,
+                std::io::Error::last_os_error()
+            )));
+        }
+        let file_size = stat.st_size;
+        if offset.checked_add(length).is_none_or(|end| end > file_size) {
+            return Err(InferenceError::model_loading_failed(format!(
+                "Model range at offset {} with length {} is past the end of the {}-byte file",
+                offset, length, file_size
+            )));
+        }
+        let length = usize::try_from(length)
+            .map_err(|_| InferenceError::model_loading_failed(format!("Model length {} is too large", length)))?;
+
+        let mut model_bytes = Vec::new();
+        model_bytes
+            .try_reserve_exact(length)
+            .map_err(|e| InferenceError::memory_error(format!("Cannot allocate {} bytes for the model: {}", length, e)))?;
+        model_bytes.resize(length, 0);
+        let mut filled = 0;
+        while filled < length {
+            // Inside the range checked above, so this only fails where `off_t` is 32-bit
+            let position = i64::try_from(filled)
+                .ok()
+                .and_then(|filled| offset.checked_add(filled))
+                .and_then(|position| libc::off_t::try_from(position).ok())
+                .ok_or_else(|| InferenceError::model_loading_failed(format!("Read position past offset {} overflows", offset)))?;
+            // SAFETY: the destination is the unfilled tail of `model_bytes`, which has exactly `length - filled` bytes
+            let read = unsafe {
+                libc::pread(
+                    fd,
+                    model_bytes[filled..].as_mut_ptr().cast(),
+                    length - filled,
+                    position,
+                )
+            };
+            match read {
+                0 => {
+                    return Err(InferenceError::model_loading_failed(format!(
+                        "File descriptor {} ended after {} of {} model bytes",
+                        fd, filled, length
+                    )));
+                }
+                n if n < 0 => {
+                    let error = std::io::Error::last_os_error();
+                    if error.kind() == std::io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(InferenceError::model_loading_failed(format!(
+                        "Failed to read model from file descriptor {}: {}",
+                        fd, error
+                    )));
+                }
+                n => filled += n as usize,
+            }
+        }
+        Ok(model_bytes)
+    }
+
+    /// Remember how the latest load attempt ended, for `no_model_error`
+    fn record_load_attempt(source: &str, result: &InferenceResult<()>) {
+        engine().last_load_attempt = Some((source.to_string(), result.as_ref().err().map(ToString::to_string)));
+    }
+
+    /// Error for running without an active model, saying whether a load was attempted and how it ended
+    fn no_model_error() -> InferenceError {
+        let attempt = engine().last_load_attempt.clone();
+        match attempt {
+            Some((path, Some(error))) => InferenceError::model_not_found(format!(
+                "No model loaded (last load attempt: {} failed with {})",
+                path, error
+            )),
+            Some((path, None)) => InferenceError::model_not_found(format!(
+                "No model loaded (last load attempt: {} succeeded but is no longer active)",
+                path
+            )),
+            None => InferenceError::model_not_found("No model loaded. Call load_model first."),
+        }
+    }
+
+    fn try_load_model(model_path: &str, session_config: SessionConfig) -> InferenceResult<()> {
         // Check if model file exists
         if !std::path::Path::new(model_path).exists() {
             return Err(InferenceError::model_not_found(model_path));
         }
 
-        // Check if this model is already cached
+        // Check if this model is already cached with the same session options
         {
-            if let Ok(cached_session) = CACHED_SESSION.lock() {
-                if let Some((cached_path, _)) = cached_session.as_ref() {
-                    if cached_path == model_path {
-                        return Ok(()); // Same model already loaded
-                    }
-                }
+            let mut cache = lock_cache();
+            if let Some(index) = cache
+                .models
+                .iter()
+                .position(|cached| cached.path == model_path && cached.session_config == session_config)
+            {
+                cache.set_active(Some(index));
+                return Ok(()); // Same model already loaded
             }
         }
 
         // Read model bytes
         let model_bytes = std::fs::read(model_path)
             .map_err(|e| InferenceError::model_loading_failed(format!("Failed to read model file {}: {}", model_path, e)))?;
+        let model_bytes = Self::decompress_model(model_path, model_bytes)?;
+
+        Self::commit_model(model_path, &model_bytes, session_config)
+    }
+
+    /// Inflate a gzip-compressed model, recognized by the gzip magic bytes or a `.gz` path; other
+    /// models pass through untouched. Without the `gzip` feature compressed models fail clearly.
+    fn decompress_model(model_path: &str, model_bytes: Vec<u8>) -> InferenceResult<Vec<u8>> {
+        Self::decompress_model_within(model_path, model_bytes, MAX_INFLATED_MODEL_BYTES)
+    }
+
+    /// `decompress_model`, failing once the inflated model would exceed `max_bytes`
+    fn decompress_model_within(model_path: &str, model_bytes: Vec<u8>, max_bytes: u64) -> InferenceResult<Vec<u8>> {
+        if !model_bytes.starts_with(&GZIP_MAGIC) && !model_path.ends_with(".gz") {
+            return Ok(model_bytes);
+        }
+
+        #[cfg(feature = "gzip")]
+        {
+            use std::io::Read;
+            let mut inflated = Vec::new();
+            // Read one byte past the limit so an exactly-at-limit model still loads
+            flate2::read::GzDecoder::new(&model_bytes[..])
+                .take(max_bytes + 1)
+                .read_to_end(&mut inflated)
+                .map_err(|e| InferenceError::model_loading_failed(format!("Failed to decompress model {}: {}", model_path, e)))?;
+            if inflated.len() as u64 > max_bytes {
+                return Err(InferenceError::model_loading_failed(format!(
+                    "Model {} inflates to more than {} bytes",
+                    model_path, max_bytes
+                )));
+            }
+            Ok(inflated)
+        }
+        #[cfg(not(feature = "gzip"))]
+        {
+            let _ = max_bytes;
+            Err(InferenceError::model_loading_failed(format!(
+                "Model {} is gzip-compressed; build with the `gzip` feature to load it",
+                model_path
+            )))
+        }
+    }
 
+    /// Build a session from in-memory model bytes and cache it as the active model under `model_path`
+    fn commit_model(model_path: &str, model_bytes: &[u8], session_config: SessionConfig) -> InferenceResult<()> {
         // Create ONNX session
-        let session = Session::builder()
-            .map_err(|e| InferenceError::session_failed(format!("Failed to create ONNX session builder: {:?}", e)))?
-            .commit_from_memory(&model_bytes)
+        let (builder, execution_provider) = Self::session_builder(&session_config)?;
+        let session = builder
+            .commit_from_memory(model_bytes)
             .map_err(|e| InferenceError::model_loading_failed(format!("Failed to load model from memory: {:?}", e)))?;
 
-        // Cache the session (replacing any existing cached session)
-        if let Ok(mut cached_session) = CACHED_SESSION.lock() {
-            *cached_session = Some((model_path.to_string(), session));
-        } else {
-            return Err(InferenceError::memory_error("Failed to acquire session cache mutex"));
-        }
+        let declared_inputs: Vec<(&str, Option<&[i64]>)> = session
+            .inputs
+            .iter()
+            .map(|input| (input.name.as_str(), input.input_type.tensor_shape().map(|shape| &shape[..])))
+            .collect();
+        let image_input = Self::select_image_input(&declared_inputs, session_config.input_name.as_deref())?;
+
+        let first_output_shape = session.outputs.first().and_then(|output| output.output_type.tensor_shape());
+        let looks_like_classifier = first_output_shape.is_some_and(|shape| Postprocessor::looks_like_classifier(shape));
+        let class_count = first_output_shape.and_then(|shape| Postprocessor::declared_class_count(shape));
+
+        let tensor_info = |name: &str, value_type: &ValueType| TensorInfo {
+            name: name.to_string(),
+            value_type: value_type.to_string(),
+            shape: value_type.tensor_shape().map(|shape| shape.to_vec()),
+        };
+        let info = ModelInfo {
+            path: model_path.to_string(),
+            inputs: session.inputs.iter().map(|input| tensor_info(&input.name, &input.input_type)).collect(),
+            outputs: session.outputs.iter().map(|output| tensor_info(&output.name, &output.output_type)).collect(),
+            opset: model_proto::default_opset(model_bytes),
+            producer: session.metadata().and_then(|metadata| metadata.producer()).unwrap_or_default(),
+            provider: execution_provider.to_string(),
+        };
+
+        // Cache the session (replacing an entry for the same path built with other options)
+        lock_cache().insert(CachedModel {
+            path: model_path.to_string(),
+            session_config,
+            session,
+            looks_like_classifier,
+            class_count,
+            size_bytes: model_bytes.len(),
+            image_input,
+            info,
+        });
+        Self::check_label_resolution(class_count);
 
         Ok(())
     }
 
-    /// Run inference using the currently cached session
+    /// Check loaded labels against a newly loaded model's class count, logging a warning when they differ
+    fn check_label_resolution(class_count: Option<usize>) {
+        if let Some(classes) = class_count {
+            LabelsManager::validate_class_count(classes);
+        }
+        if let LabelResolution::Mismatch { label_count, model_classes } = LabelsManager::resolve(class_count) {
+            RuntimeLog::push(
+                LogLevel::Warning,
+                &format!(
+                    "{} labels loaded but model outputs {} classes; labels are ignored and class_N names are used instead",
+                    label_count, model_classes
+                ),
+            );
+        }
+    }
+
+    /// Swap in new labels only if they parse and match the active model's class count (when known)
+    pub fn replace_labels(bytes: &[u8]) -> InferenceResult<usize> {
+        let class_count = lock_cache().active().and_then(|cached| cached.class_count);
+        LabelsManager::replace_labels(bytes, class_count)
+    }
+
+    /// How labels resolve for the active model's output classes
+    pub fn label_resolution() -> LabelResolution {
+        let class_count = lock_cache().active().and_then(|cached| cached.class_count);
+        LabelsManager::resolve(class_count)
+    }
+
+    /// Run inference using the currently cached session.
+    ///
+    /// Classification logits go through the calibration set with
+    /// `ConfigManager::set_calibration` (if any) before softmax; `data` keeps the raw logits.
+    ///
+    /// Classification outputs larger than the limit set with
+    /// `ConfigManager::set_max_softmax_classes` skip softmax: the result then has no
+    /// probabilities and a single top prediction, the argmax, whose `raw_score` holds its logit.
     pub fn run_inference(image_bytes: &[u8]) -> InferenceResult<InferenceOutput> {
-        // Preprocess image with timing
+        Self::run_inference_with_deadline(image_bytes, None)
+    }
+
+    /// Run inference and return only the top-1 label (empty if the output isn't a classification)
+    pub fn classify(image_bytes: &[u8]) -> InferenceResult<String> {
+        let result = Self::run_inference(image_bytes)?;
+        Ok(result.top_prediction().map(|top| top.class_name.clone()).unwrap_or_default())
+    }
+
+    /// Run inference on packed 4-byte pixels (e.g. a Bitmap or camera buffer) without encoding them first
+    pub fn run_inference_pixels(pixels: &[u8], width: u32, height: u32, format: PixelFormat) -> InferenceResult<InferenceOutput> {
+        let input = ImageInput::Pixels { data: pixels, width, height, format };
+        Self::run_pipeline(input, None, None, true)
+    }
+
+    /// Run inference with the cached model at `index` (in `loaded_model_info` order). The model is
+    /// looked up and run within one hold of the session lock, and the active model is left as it was.
+    /// The result is published like any other run; the input isn't recorded.
+    pub fn run_inference_by_index(index: usize, image_bytes: &[u8]) -> InferenceResult<InferenceOutput> {
+        let result = Self::run_model_at(&mut lock_cache(), index, ImageInput::Encoded(image_bytes))?;
+        Self::publish(&result);
+        Ok(result)
+    }
+
+    /// Read an encoded image from disk and run inference on it
+    pub fn run_inference_from_path(image_path: &str) -> InferenceResult<InferenceOutput> {
+        let image_bytes = std::fs::read(image_path)
+            .map_err(|e| InferenceError::invalid_image(format!("Failed to read image file '{}': {}", image_path, e)))?;
+        Self::run_inference(&image_bytes)
+    }
+
+    /// Run inference, giving up before `session.run` if preprocessing already used up `deadline`.
+    ///
+    /// This is a best-effort pre-check only: ORT's run can't be interrupted, so a run that
+    /// starts within the deadline may still finish after it.
+    pub fn run_inference_with_deadline(image_bytes: &[u8], deadline: Option<Duration>) -> InferenceResult<InferenceOutput> {
+        if !engine().result_cache_enabled {
+            return Self::run_pipeline(ImageInput::Encoded(image_bytes), None, deadline, true);
+        }
+
+        let key = Self::run_key(image_bytes);
+        if let Some(result) = Self::cached_run(&key) {
+            return Ok(result);
+        }
+        let result = Self::run_pipeline(ImageInput::Encoded(image_bytes), None, deadline, true)?;
+        Self::store_run(key, &result);
+        Ok(result)
+    }
+
+    /// Turn the result cache for `run_inference` on or off (off drops the cached result).
+    ///
+    /// Matching is exact: the input bytes are hashed, so a frame that differs in a single byte
+    /// (e.g. sensor noise, a re-encoded JPEG) always runs the model. Changing the active model, any
+    /// pre/postprocessing option, the labels or the label mode also misses. A hit reports zero
+    /// timings, since nothing ran.
+    pub fn set_result_cache(on: bool) {
+        let mut engine = engine();
+        engine.result_cache_enabled = on;
+        if !on {
+            engine.cached_run = None;
+        }
+    }
+
+    /// Turn debug input capture on or off (off drops the captured input). While it is on, each
+    /// recorded float input is copied for `save_debug_input`; it is off by default so runs don't
+    /// pay for a tensor copy nobody reads.
+    pub fn set_input_capture(on: bool) {
+        let mut engine = engine();
+        engine.input_capture_enabled = on;
+        if !on {
+            engine.last_input = None;
+        }
+    }
+
+    /// Cache key for running `image_bytes` with the current model and options. The bytes are hashed
+    /// with std's SipHash, which is fast enough next to decoding and needs no extra dependency.
+    fn run_key(image_bytes: &[u8]) -> RunKey {
+        let mut hasher = DefaultHasher::new();
+        image_bytes.hash(&mut hasher);
+        RunKey {
+            input_hash: hasher.finish(),
+            model_path: Self::get_loaded_model_path(),
+            preprocess: ConfigManager::preprocess_config(),
+            postprocess: ConfigManager::postprocess_config(),
+            labels_generation: LabelsManager::generation(),
+            strict_labels: LabelsManager::is_strict(),
+            expected_class_count: LabelsManager::expected_class_count(),
+        }
+    }
+
+    /// The cached result for `key`, flagged as cached with zeroed timings and made the last result
+    fn cached_run(key: &RunKey) -> Option<InferenceOutput> {
+        let mut engine = engine();
+        let (cached_key, result) = engine.cached_run.as_ref()?;
+        if cached_key != key {
+            return None;
+        }
+        let result = InferenceOutput {
+            cached: true,
+            inference_time_ms: 0.0,
+            preprocessing_time_ms: 0.0,
+            postprocessing_time_ms: 0.0,
+            total_time_ms: 0.0,
+            ..result.clone()
+        };
+        engine.last_result = Some(result.clone());
+        drop(engine);
+        ResultListener::notify(&result);
+        Some(result)
+    }
+
+    /// Remember `result` as the output for `key` while the cache is on
+    fn store_run(key: RunKey, result: &InferenceOutput) {
+        let mut engine = engine();
+        if engine.result_cache_enabled {
+            engine.cached_run = Some((key, result.clone()));
+        }
+    }
+
+    /// Run inference without touching the engine's shared state: the result is only returned, and
+    /// the last result, last input, timing trace and result cache are left as they were. Errors are
+    /// returned too, never stored for `get_last_error`.
+    ///
+    /// Concurrent calls are safe and each gets its own result, though `session.run` itself still
+    /// runs one call at a time on the shared session.
+    pub fn run_inference_pure(image_bytes: &[u8]) -> InferenceResult<InferenceOutput> {
+        Self::run_pipeline(ImageInput::Encoded(image_bytes), None, None, false)
+    }
+
+    /// Run inference on a region of the decoded image (must lie within its bounds)
+    pub fn run_inference_on_roi(image_bytes: &[u8], roi: Roi) -> InferenceResult<InferenceOutput> {
+        Self::run_pipeline(ImageInput::Encoded(image_bytes), Some(roi), None, true)
+    }
+
+    /// Classify every test-time augmentation view and rank the averaged probabilities.
+    /// Views are preprocessed for the active model's input (as `run_inference` does) and all run
+    /// within one hold of the session lock. `data` holds the views' mean logits; timings are
+    /// summed over all views.
+    pub fn run_inference_tta(image_bytes: &[u8], mode: TtaMode) -> InferenceResult<InferenceOutput> {
         let preprocess_start = Instant::now();
-        let input_array = Self::preprocess_image(image_bytes)?;
-        let input_data = input_array.into_raw_vec();
+        let prepared = Self::prepare_tta_views(image_bytes, mode, &Self::active_input_spec())?;
         let preprocessing_time_ms = preprocess_start.elapsed().as_secs_f32() * 1000.0;
 
-        let mut cached_session = CACHED_SESSION.lock()
-            .map_err(|_| InferenceError::memory_error("Failed to acquire session cache mutex"))?;
+        let (outputs, model_classes) = {
+            let mut cache = lock_cache();
+            let model = cache.active_mut().ok_or_else(Self::no_model_error)?;
+            let outputs = prepared
+                .tensor
+                .into_iter()
+                .map(|(input_shape, input)| Self::run_model(model, input_shape, input, 0.0, None, true))
+                .collect::<InferenceResult<Vec<_>>>()?;
+            (outputs, model.class_count)
+        };
 
-        if let Some((_cached_path, session)) = cached_session.as_mut() {
-            // Create input tensor
-            let input_tensor = Value::from_array(([1, 3, IMAGE_HEIGHT as i64, IMAGE_WIDTH as i64], input_data))
-                .map_err(|e| InferenceError::inference_failed(format!("Failed to create input tensor: {:?}", e)))?;
+        let postprocess_start = Instant::now();
+        let distributions = outputs
+            .iter()
+            .map(|output| output.probabilities.clone())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| InferenceError::output_processing_failed("Test-time augmentation requires a classification output"))?;
+        let logits: Vec<Vec<f32>> = outputs.iter().map(|output| output.data.clone()).collect();
+        let (Some(probabilities), Some(data)) = (Tta::average(&distributions), Tta::average(&logits)) else {
+            return Err(InferenceError::output_processing_failed("Augmented views produced outputs of different sizes"));
+        };
+        let top_predictions = Self::get_top_predictions(
+            &probabilities,
+            TOP_K_PREDICTIONS,
+            &ConfigManager::postprocess_config(),
+            model_classes,
+        );
 
-            // Run inference with timing
-            let inference_start = Instant::now();
-            let input_name = session.inputs[0].name.clone();
-            let inputs = ort::inputs![input_name.as_str() => input_tensor];
-            let outputs = session
-                .run(inputs)
-                .map_err(|e| InferenceError::inference_failed(format!("Inference execution failed: {:?}", e)))?;
-            let inference_time_ms = inference_start.elapsed().as_secs_f32() * 1000.0;
-
-            // Process output with timing
-            let postprocess_start = Instant::now();
-            if let Some(output) = outputs.values().next() {
-                let shape = output.shape().iter().map(|&x| x as usize).collect::<Vec<_>>();
-                let (_output_shape, data_slice) = output
-                    .try_extract_tensor::<f32>()
-                    .map_err(|e| InferenceError::output_processing_failed(format!("Failed to extract tensor data: {:?}", e)))?;
-                let data = data_slice.to_vec();
-
-                // Determine if this is a classification model and compute predictions
-                let (is_classification, top_predictions) = if data.len() >= MIN_CLASSIFICATION_CLASSES {
-                    let probabilities = Self::softmax(&data);
-                    let predictions = Self::get_top_predictions(&probabilities, TOP_K_PREDICTIONS);
-                    (true, predictions)
-                } else {
-                    (false, Vec::new())
-                };
-
-                let postprocessing_time_ms = postprocess_start.elapsed().as_secs_f32() * 1000.0;
-
-                let result = InferenceOutput::new_with_timing(
-                    data, 
-                    shape, 
-                    is_classification, 
-                    top_predictions,
-                    inference_time_ms,
-                    preprocessing_time_ms,
-                    postprocessing_time_ms
-                );
-
-                // Store result for later retrieval (for JNI compatibility)
-                if let Ok(mut last_result) = LAST_RESULT.lock() {
-                    *last_result = Some(result.clone());
+        let inference_time_ms = outputs.iter().map(|output| output.inference_time_ms).sum();
+        let postprocessing_time_ms = outputs.iter().map(|output| output.postprocessing_time_ms).sum::<f32>()
+            + postprocess_start.elapsed().as_secs_f32() * 1000.0;
+        let mut result = InferenceOutput::new_with_timing(
+            data,
+            outputs[0].shape.clone(),
+            true,
+            top_predictions,
+            inference_time_ms,
+            preprocessing_time_ms,
+            postprocessing_time_ms,
+        )
+        .with_probabilities(probabilities);
+        (result.orig_width, result.orig_height) = prepared.source_size;
+
+        Self::publish(&result);
+        Ok(result)
+    }
+
+    /// Run the image through each cached model at `indices` (in `loaded_model_info` order) and rank the
+    /// mean of their softmax outputs, weighted as set with `ConfigManager::set_ensemble_weights`.
+    /// Logits of different models aren't comparable, so `data` holds the averaged probabilities too;
+    /// timings are summed over the members. Every member must classify over the same number of
+    /// classes (checked up front for static class counts), and labels resolve against that count
+    /// rather than the active model's. All members run within one hold of the session lock, so none can be evicted midway,
+    /// and the active model is left as it was.
+    pub fn run_ensemble(indices: &[usize], image_bytes: &[u8]) -> InferenceResult<InferenceOutput> {
+        let weights = match ConfigManager::postprocess_config().ensemble_weights {
+            Some(weights) if weights.len() != indices.len() => {
+                return Err(InferenceError::invalid_config(format!(
+                    "{} ensemble weights set for {} models",
+                    weights.len(),
+                    indices.len()
+                )));
+            }
+            Some(weights) => weights,
+            None => vec![1.0; indices.len()],
+        };
+        if indices.is_empty() {
+            return Err(InferenceError::model_not_found("Ensemble needs at least one model index"));
+        }
+
+        // Each member preprocesses for its own input, since input sizes can differ between models
+        let outputs = {
+            let mut cache = lock_cache();
+            if let Some(&index) = indices.iter().find(|&&index| index >= cache.models.len()) {
+                return Err(Self::no_model_at(index, cache.models.len()));
+            }
+            // Members with different declared class counts are rejected before anything runs
+            let mut declared = indices.iter().filter_map(|&index| Some((index, cache.models[index].class_count?)));
+            if let Some(first) = declared.next() {
+                for member in declared {
+                    Self::check_ensemble_class_count(first, member)?;
                 }
+            }
+            indices
+                .iter()
+                .map(|&index| Self::run_model_at(&mut cache, index, ImageInput::Encoded(image_bytes)))
+                .collect::<InferenceResult<Vec<_>>>()?
+        };
 
-                Ok(result)
-            } else {
-                Err(InferenceError::output_processing_failed("No output from model"))
+        let result = Self::combine_ensemble(&outputs, indices, &weights)?;
+        Self::publish(&result);
+        Ok(result)
+    }
+
+    /// Weighted mean of the ensemble members' probabilities (`outputs[i]` came from the model at
+    /// `indices[i]`), ranked, with timings summed over the members
+    fn combine_ensemble(outputs: &[InferenceOutput], indices: &[usize], weights: &[f32]) -> InferenceResult<InferenceOutput> {
+        let postprocess_start = Instant::now();
+        let mut distributions = Vec::with_capacity(outputs.len());
+        for (output, &index) in outputs.iter().zip(indices) {
+            let probabilities = output.probabilities.clone().ok_or_else(|| {
+                InferenceError::invalid_output(format!("ensemble model at index {} has no classification output", index))
+            })?;
+            if let Some(first) = distributions.first().map(Vec::len) {
+                Self::check_ensemble_class_count((indices[0], first), (index, probabilities.len()))?;
             }
-        } else {
-            Err(InferenceError::model_not_found("No model loaded. Call load_model first."))
+            distributions.push(probabilities);
         }
+        let probabilities = Tta::weighted_average(&distributions, weights)
+            .ok_or_else(|| InferenceError::invalid_config("ensemble weights sum to zero"))?;
+        // Labelled for the members' shared class count, not the active model's
+        let top_predictions = Self::get_top_predictions(
+            &probabilities,
+            TOP_K_PREDICTIONS,
+            &ConfigManager::postprocess_config(),
+            Some(probabilities.len()),
+        );
+
+        let preprocessing_time_ms = outputs.iter().map(|output| output.preprocessing_time_ms).sum();
+        let inference_time_ms = outputs.iter().map(|output| output.inference_time_ms).sum();
+        let postprocessing_time_ms = outputs.iter().map(|output| output.postprocessing_time_ms).sum::<f32>()
+            + postprocess_start.elapsed().as_secs_f32() * 1000.0;
+        let mut result = InferenceOutput::new_with_timing(
+            probabilities.clone(),
+            outputs[0].shape.clone(),
+            true,
+            top_predictions,
+            inference_time_ms,
+            preprocessing_time_ms,
+            postprocessing_time_ms,
+        )
+        .with_probabilities(probabilities);
+        (result.orig_width, result.orig_height) = (outputs[0].orig_width, outputs[0].orig_height);
+        Ok(result)
     }
 
-    /// Check if any model is currently loaded in cache
-    pub fn is_model_loaded() -> bool {
-        if let Ok(cached_session) = CACHED_SESSION.lock() {
-            cached_session.is_some()
-        } else {
-            false
+    /// Error unless two ensemble members, given as `(model index, class count)`, classify over the same classes
+    fn check_ensemble_class_count(first: (usize, usize), member: (usize, usize)) -> InferenceResult<()> {
+        if first.1 == member.1 {
+            return Ok(());
         }
+        Err(InferenceError::invalid_output(format!(
+            "ensemble models must share a class count: model {} has {} classes, model {} has {}",
+            first.0, first.1, member.0, member.1
+        )))
     }
 
-    /// Get the path of the currently loaded model
-    pub fn get_loaded_model_path() -> Option<String> {
-        if let Ok(cached_session) = CACHED_SESSION.lock() {
-            cached_session.as_ref().map(|(path, _)| path.clone())
+    /// Preprocess `input` for the cached model at `index` and run it, without recording anything or
+    /// changing the active model. The caller holds the session lock throughout.
+    fn run_model_at(cache: &mut ModelCache, index: usize, input: ImageInput) -> InferenceResult<InferenceOutput> {
+        let loaded = cache.models.len();
+        let model = cache.models.get_mut(index).ok_or_else(|| Self::no_model_at(index, loaded))?;
+
+        let preprocess_start = Instant::now();
+        let (input_shape, prepared) = Self::prepare_input(input, None, &model.input_spec(), false)?;
+        let preprocessing_time_ms = preprocess_start.elapsed().as_secs_f32() * 1000.0;
+
+        let Prepared { tensor, source_size, letterbox } = prepared;
+        let mut result = Self::run_model(model, input_shape, tensor, preprocessing_time_ms, letterbox, false)?;
+        (result.orig_width, result.orig_height) = source_size;
+        Ok(result)
+    }
+
+    /// Error for a model index past the `loaded` cached models
+    fn no_model_at(index: usize, loaded: usize) -> InferenceError {
+        InferenceError::model_not_found(format!("No cached model at index {} ({} loaded)", index, loaded))
+    }
+
+    /// Preprocess (optionally cropping to `roi`), run the active session and post-process.
+    /// `record` keeps the input and result for the last-result accessors and timing trace.
+    fn run_pipeline(
+        input: ImageInput,
+        roi: Option<Roi>,
+        deadline: Option<Duration>,
+        record: bool,
+    ) -> InferenceResult<InferenceOutput> {
+        // Preprocess image with timing
+        let preprocess_start = Instant::now();
+        let (input_shape, prepared) = Self::prepare_input(input, roi, &Self::active_input_spec(), record)?;
+        let preprocessing_elapsed = preprocess_start.elapsed();
+        let preprocessing_time_ms = preprocessing_elapsed.as_secs_f32() * 1000.0;
+
+        if let Some(deadline) = deadline
+            && preprocessing_elapsed > deadline
+        {
+            return Err(InferenceError::deadline(format!(
+                "preprocessing took {:.2}ms of a {:.2}ms budget",
+                preprocessing_time_ms,
+                deadline.as_secs_f32() * 1000.0
+            )));
+        }
+
+        // A too-small image only warns: the run goes ahead, with the warning on the result
+        let Prepared { tensor, source_size, letterbox } = prepared;
+        let warnings = ConfigManager::preprocess_config()
+            .min_image_dimension
+            .and_then(|min_dimension| ImagePreprocessor::resolution_warning(source_size.0, source_size.1, min_dimension))
+            .into_iter()
+            .collect();
+        Self::run_session(input_shape, tensor, preprocessing_time_ms, letterbox, Some(source_size), warnings, record)
+    }
+
+    /// The active model's image input, or an empty spec when no model is loaded
+    fn active_input_spec() -> InputSpec {
+        lock_cache().active().map(CachedModel::input_spec).unwrap_or_default()
+    }
+
+    /// Decode and preprocess an image for a model's image input described by `spec` (resize dims,
+    /// channel count and quantization follow the model). The image's size and letterbox transform
+    /// come back with the tensor; `record_input` also keeps its stats for the last-input accessors,
+    /// and an f32 tensor for debug snapshots while input capture is on.
+    fn prepare_input(
+        input: ImageInput,
+        roi: Option<Roi>,
+        spec: &InputSpec,
+        record_input: bool,
+    ) -> InferenceResult<(Vec<i64>, Prepared<TensorData>)> {
+        let (config, quantized) = Self::input_config(spec);
+        let (input_shape, prepared) = if let Some((quant, signed)) = quantized {
+            let prepared = ImagePreprocessor::preprocess_image_quantized(input, roi, &config, quant, signed)?;
+            if record_input {
+                ImagePreprocessor::record(None);
+                // There is no float tensor to snapshot, so don't leave an older input behind
+                engine().last_input = None;
+            }
+            let Prepared { tensor, source_size, letterbox } = prepared;
+            let (input_shape, tensor) = Self::quantized_input(tensor);
+            (input_shape, Prepared { tensor, source_size, letterbox })
         } else {
-            None
+            let prepared = ImagePreprocessor::preprocess_input(input, roi, &config)?;
+            if record_input {
+                ImagePreprocessor::record(prepared.tensor.as_slice().and_then(TensorStats::from_values));
+                let mut engine = engine();
+                if engine.input_capture_enabled {
+                    engine.last_input = Some((prepared.tensor.clone(), config.clone()));
+                }
+            }
+            let Prepared { tensor, source_size, letterbox } = prepared;
+            let input_shape: Vec<i64> = tensor.shape().iter().map(|&d| d as i64).collect();
+            (input_shape, Prepared { tensor: TensorData::F32(tensor.into_raw_vec()), source_size, letterbox })
+        };
+        Ok((input_shape, prepared))
+    }
+
+    /// Preprocessing config for a model's image input described by `spec`: `ModelInput` resizes to
+    /// the declared dims and single-channel inputs take luma. Also returns the quantization and
+    /// signedness when the input is integer and quantization is configured.
+    fn input_config(spec: &InputSpec) -> (PreprocessConfig, Option<(QuantParams, bool)>) {
+        let mut config = ConfigManager::preprocess_config();
+        if let ResizeMode::ModelInput { .. } = config.resize_mode
+            && let Some(declared_shape) = &spec.shape
+        {
+            config.resize_mode = config.resize_mode.with_model_dims(declared_shape);
         }
+        // Single-channel models get luma computed straight from the resized pixels
+        config.grayscale = matches!(spec.shape.as_deref(), Some([_, 1, _, _]));
+        // Integer image inputs with known quantization take the fused quantize path
+        let quantized = match (config.quantization, spec.element_type) {
+            (Some(quant), Some(TensorElementType::Uint8)) => Some((quant, false)),
+            (Some(quant), Some(TensorElementType::Int8)) => Some((quant, true)),
+            _ => None,
+        };
+        (config, quantized)
     }
 
-    /// Get the last inference result (for JNI compatibility)
-    pub fn get_last_result() -> Option<InferenceOutput> {
-        LAST_RESULT.lock().ok()?.as_ref().cloned()
+    /// Shape and values of a quantized input tensor, ready for `TensorData::into_value`
+    fn quantized_input(tensor: QuantizedTensor) -> ShapedInput {
+        let input_shape = tensor.shape.iter().map(|&d| d as i64).collect();
+        let data = match tensor.data {
+            QuantizedData::U8(data) => TensorData::U8(data),
+            QuantizedData::I8(data) => TensorData::I8(data),
+        };
+        (input_shape, data)
     }
 
-    /// Store error message for JNI retrieval
-    pub fn store_error(error: &str) {
-        if let Ok(mut last_error) = LAST_ERROR.lock() {
-            *last_error = Some(error.to_string());
+    /// Decode `image_bytes` and preprocess every augmentation view for a model's image input
+    /// described by `spec`, as `prepare_input` does for a single image
+    fn prepare_tta_views(
+        image_bytes: &[u8],
+        mode: TtaMode,
+        spec: &InputSpec,
+    ) -> InferenceResult<Prepared<Vec<ShapedInput>>> {
+        let (config, quantized) = Self::input_config(spec);
+        if let Some((quant, signed)) = quantized {
+            let Prepared { tensor, source_size, letterbox } =
+                ImagePreprocessor::preprocess_tta_quantized(image_bytes, mode, &config, quant, signed)?;
+            let tensor = tensor.into_iter().map(Self::quantized_input).collect();
+            return Ok(Prepared { tensor, source_size, letterbox });
         }
+        let Prepared { tensor, source_size, letterbox } = ImagePreprocessor::preprocess_tta(image_bytes, mode, &config)?;
+        let tensor = tensor
+            .into_iter()
+            .map(|view| {
+                let input_shape = view.shape().iter().map(|&d| d as i64).collect();
+                (input_shape, TensorData::F32(view.into_raw_vec()))
+            })
+            .collect();
+        Ok(Prepared { tensor, source_size, letterbox })
     }
 
-    /// Get last error message (for JNI compatibility)
-    pub fn get_last_error() -> Option<String> {
-        LAST_ERROR.lock().ok()?.as_ref().cloned()
+    /// Dry run: decode and preprocess `image_bytes` and check the tensor against the active model's
+    /// declared input shape without running the model. Returns the input shape that would be fed.
+    pub fn validate_input(image_bytes: &[u8]) -> InferenceResult<Vec<i64>> {
+        let spec = Self::active_input_spec();
+        let (input_shape, _) = Self::prepare_input(ImageInput::Encoded(image_bytes), None, &spec, false)?;
+        if !Self::is_model_loaded() {
+            return Err(Self::no_model_error());
+        }
+        if let Some(model_shape) = spec.shape {
+            Self::check_input_shape(&model_shape, &input_shape)?;
+        }
+        Ok(input_shape)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Run the active session on a caller-normalized tensor, skipping image preprocessing.
+    /// `shape` must describe `data` exactly (e.g. `[1, 3, 224, 224]`).
+    pub fn run_inference_tensor(data: Vec<f32>, shape: &[usize]) -> InferenceResult<InferenceOutput> {
+        let expected = shape
+            .iter()
+            .try_fold(1usize, |count, &dim| count.checked_mul(dim))
+            .ok_or_else(|| InferenceError::invalid_input(format!("shape {:?} holds too many values", shape)))?;
+        if shape.is_empty() || expected != data.len() {
+            return Err(InferenceError::shape_mismatch(format!(
+                "shape {:?} holds {} values but {} were provided",
+                shape,
+                expected,
+                data.len()
+            )));
+        }
 
-    #[test]
-    fn test_softmax() {
-        let input = vec![1.0, 2.0, 3.0];
-        let output = InferenceEngine::softmax(&input);
-        
-        // Check sum equals 1.0
-        let sum: f32 = output.iter().sum();
-        assert!((sum - 1.0).abs() < 1e-6);
-        
-        // Check monotonicity (larger input -> larger output)
-        assert!(output[0] < output[1]);
-        assert!(output[1] < output[2]);
+        let input_shape: Vec<i64> = shape.iter().map(|&d| d as i64).collect();
+        Self::run_session(input_shape, TensorData::F32(data), 0.0, None, None, Vec::new(), true)
+    }
+
+    /// Run the active session on an input tensor and post-process, attaching the input's size and
+    /// `warnings`; with `record`, the result is then published (see `publish`) once the session lock
+    /// is released
+    fn run_session(
+        input_shape: Vec<i64>,
+        input_data: TensorData,
+        preprocessing_time_ms: f32,
+        letterbox: Option<LetterboxInfo>,
+        source_size: Option<(u32, u32)>,
+        warnings: Vec<String>,
+        record: bool,
+    ) -> InferenceResult<InferenceOutput> {
+        let mut result = Self::run_active_session(input_shape, input_data, preprocessing_time_ms, letterbox)?;
+        (result.orig_width, result.orig_height) = source_size.unwrap_or_default();
+        result.warnings = warnings;
+        if record {
+            Self::publish(&result);
+        }
+        Ok(result)
+    }
+
+    /// Store a completed result for the accessors, trace its timings and notify the result listener
+    fn publish(result: &InferenceOutput) {
+        // Store result for later retrieval (for JNI compatibility)
+        engine().last_result = Some(result.clone());
+        TimingTrace::record(result);
+        ResultListener::notify(result);
+    }
+
+    /// Run the active session on an input tensor and post-process, holding the session lock throughout
+    fn run_active_session(
+        input_shape: Vec<i64>,
+        input_data: TensorData,
+        preprocessing_time_ms: f32,
+        letterbox: Option<LetterboxInfo>,
+    ) -> InferenceResult<InferenceOutput> {
+        let mut cache = lock_cache();
+        let model = cache.active_mut().ok_or_else(Self::no_model_error)?;
+        Self::run_model(model, input_shape, input_data, preprocessing_time_ms, letterbox, true)
+    }
+
+    /// Run `model` on an input tensor and post-process; the caller holds the session lock. Labels
+    /// resolve against `model`'s own class count, and only the `active` model's run updates the
+    /// shared label count warning.
+    fn run_model(
+        model: &mut CachedModel,
+        input_shape: Vec<i64>,
+        input_data: TensorData,
+        preprocessing_time_ms: f32,
+        letterbox: Option<LetterboxInfo>,
+        active: bool,
+    ) -> InferenceResult<InferenceOutput> {
+        let CachedModel { session, image_input, class_count: model_classes, .. } = model;
+        let model_classes = *model_classes;
+        let input_name = Self::image_input_name(session, *image_input)?;
+
+        // Fixed spatial dims must match the preprocessed size; dynamic dims (-1) accept any size
+        if let Some(model_shape) = session.inputs[*image_input].input_type.tensor_shape() {
+            Self::check_input_shape(model_shape, &input_shape)?;
+        }
+
+        // Create input tensor using the actual preprocessed H/W
+        let input_tensor = input_data.into_value(input_shape)?;
+
+        // Only compute the requested outputs, if any; ORT prunes nodes that don't feed them
+        let postprocess_config = ConfigManager::postprocess_config();
+        let requested = postprocess_config.requested_outputs.as_deref();
+        if let Some(names) = requested {
+            Self::check_requested_outputs(session, names)?;
+        }
+        let run_options = requested
+            .map(|names| {
+                let selector = names
+                    .iter()
+                    .fold(OutputSelector::no_default(), |selector, name| selector.with(name.as_str()));
+                RunOptions::new()
+                    .map(|options| options.with_outputs(selector))
+                    .map_err(|e| InferenceError::inference_failed(format!("Failed to create run options: {:?}", e)))
+            })
+            .transpose()?;
+
+        // Run inference with timing
+        let inference_start = Instant::now();
+        let inputs = ort::inputs![input_name.as_str() => input_tensor];
+        let outputs = match &run_options {
+            Some(options) => session.run_with_options(inputs, options),
+            None => session.run(inputs),
+        }
+        .map_err(|e| InferenceError::inference_failed(format!("Inference execution failed: {:?}", e)))?;
+        let inference_time_ms = inference_start.elapsed().as_secs_f32() * 1000.0;
+
+        // Process output with timing
+        let postprocess_start = Instant::now();
+        let output_shapes: Vec<(String, Vec<usize>)> = outputs
+            .iter()
+            .filter_map(|(name, output)| {
+                let shape = output.dtype().tensor_shape()?;
+                Some((name.to_string(), shape.iter().map(|&dim| dim as usize).collect()))
+            })
+            .collect();
+        let primary = match requested {
+            Some(names) => outputs.get(&names[0]).map(|output| (names[0].as_str(), output)),
+            None => outputs.keys().next().and_then(|name| Some((name, outputs.get(name)?))),
+        };
+        if let Some((output_name, output)) = primary {
+            let (shape, data) = Self::extract_output(output)?;
+            Self::check_output_not_empty(output_name, &shape, &data)?;
+            let extra_outputs = requested
+                .unwrap_or_default()
+                .iter()
+                .skip(1)
+                .filter_map(|name| Some((name, outputs.get(name)?)))
+                .map(|(name, output)| {
+                    let (shape, data) = Self::extract_output(output)?;
+                    Ok(OutputTensor { name: name.clone(), shape, data })
+                })
+                .collect::<InferenceResult<Vec<_>>>()?;
+
+            // Classification requires a single class axis once size-1 dims are squeezed, so `[1, C]`
+            // and `[1, C, 1, 1]` qualify; an unpooled `[1, C, 7, 7]` classifier map is an error. C must
+            // reach the expected class count (1000 by default), so smaller heads need it lowered first
+            let class_count = Postprocessor::classification_class_count(&shape, LabelsManager::expected_class_count())?;
+            let is_classification = class_count.is_some();
+            let calibrated = match &postprocess_config.calibration {
+                Some(calibration) if is_classification => Some(Postprocessor::calibrate(&data, calibration)?),
+                _ => None,
+            };
+            let logits = calibrated.as_deref().unwrap_or(&data);
+            let probabilities = if let Some(num_classes) = class_count {
+                if active {
+                    LabelsManager::validate_class_count(num_classes);
+                }
+                Self::softmax_within_limit(logits, postprocess_config.max_softmax_classes)
+            } else {
+                None
+            };
+            let top_predictions = match probabilities.as_deref() {
+                Some(probs) => Self::get_top_predictions(probs, TOP_K_PREDICTIONS, &postprocess_config, model_classes),
+                // Softmax was skipped: rank the logits, which keeps the same order
+                None if is_classification => Self::top_prediction_without_softmax(logits, &postprocess_config, model_classes),
+                None => Vec::new(),
+            };
+
+            let region_predictions = if postprocess_config.region_predictions {
+                Self::region_predictions(&data, &shape, model_classes)
+            } else {
+                None
+            };
+
+            let postprocessing_time_ms = postprocess_start.elapsed().as_secs_f32() * 1000.0;
+
+            let mut result = InferenceOutput::new_with_timing(
+                data, 
+                shape, 
+                is_classification, 
+                top_predictions,
+                inference_time_ms,
+                preprocessing_time_ms,
+                postprocessing_time_ms
+            );
+            if let Some(probabilities) = probabilities {
+                result = result.with_probabilities(probabilities);
+            }
+            result.letterbox = letterbox;
+            result.region_predictions = region_predictions;
+            result.output_name = output_name.to_string();
+            result.extra_outputs = extra_outputs;
+            result.output_shapes = output_shapes;
+
+            Ok(result)
+        } else {
+            Err(InferenceError::output_processing_failed("No output from model"))
+        }
+    }
+
+    /// Fail clearly if a requested output isn't declared by the model
+    fn check_requested_outputs(session: &Session, names: &[String]) -> InferenceResult<()> {
+        match names.iter().find(|name| !session.outputs.iter().any(|output| &output.name == *name)) {
+            Some(missing) => {
+                let available: Vec<&str> = session.outputs.iter().map(|output| output.name.as_str()).collect();
+                Err(InferenceError::output_processing_failed(format!(
+                    "Requested output '{}' not found (outputs: {:?})",
+                    missing, available
+                )))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Copy an f32 output tensor out of ORT
+    fn extract_output(output: &DynValue) -> InferenceResult<(Vec<usize>, Vec<f32>)> {
+        let shape = output.shape().iter().map(|&x| x as usize).collect::<Vec<_>>();
+        let (_output_shape, data_slice) = output
+            .try_extract_tensor::<f32>()
+            .map_err(|e| InferenceError::output_processing_failed(format!("Failed to extract tensor data: {:?}", e)))?;
+        Ok((shape, data_slice.to_vec()))
+    }
+
+    /// Reject an output tensor with no elements (e.g. a `[1, 0]` output), usually a sign the graph's
+    /// final node was pruned, so it isn't mistaken for a missing output
+    fn check_output_not_empty(name: &str, shape: &[usize], data: &[f32]) -> InferenceResult<()> {
+        if data.is_empty() {
+            return Err(InferenceError::invalid_output(format!(
+                "empty output tensor '{}' with shape {:?}",
+                name, shape
+            )));
+        }
+        Ok(())
+    }
+
+    /// Time `iterations` runs of the active model on one preprocessed image, after a warmup run.
+    /// Outputs are discarded, so the stored last result and input are left untouched.
+    pub fn benchmark(image_bytes: &[u8], iterations: usize) -> InferenceResult<BenchmarkStats> {
+        if iterations == 0 {
+            return Err(InferenceError::inference_failed("Benchmark needs at least one iteration"));
+        }
+
+        // The same input a real run would feed: channel count, quantization and resize dims follow the model
+        let (input_shape, prepared) =
+            Self::prepare_input(ImageInput::Encoded(image_bytes), None, &Self::active_input_spec(), false)?;
+
+        let mut cache = lock_cache();
+        let CachedModel { session, image_input, .. } = cache
+            .active_mut()
+            .ok_or_else(Self::no_model_error)?;
+        let input_name = Self::image_input_name(session, *image_input)?;
+        if let Some(model_shape) = session.inputs[*image_input].input_type.tensor_shape() {
+            Self::check_input_shape(model_shape, &input_shape)?;
+        }
+
+        let input_tensor = prepared.tensor.into_value(input_shape)?;
+        Self::time_runs(session, &input_name, &input_tensor, iterations)
+    }
+
+    /// Time only `session.run` on a zero input of the active model's declared shape, built once,
+    /// so decode and resize stay out of model comparisons. One untimed warmup run comes first.
+    pub fn benchmark_model_only(iterations: usize) -> InferenceResult<BenchmarkStats> {
+        if iterations == 0 {
+            return Err(InferenceError::inference_failed("Benchmark needs at least one iteration"));
+        }
+
+        let mut cache = lock_cache();
+        let CachedModel { session, image_input, .. } = cache.active_mut().ok_or_else(Self::no_model_error)?;
+        let input_name = Self::image_input_name(session, *image_input)?;
+        let (shape, input_data) = Self::zero_input(&session.inputs[*image_input].input_type)?;
+        let input_tensor = input_data.into_value(shape)?;
+        Self::time_runs(session, &input_name, &input_tensor, iterations)
+    }
+
+    /// Run `session` on `input` once as a warmup, then time `iterations` more runs
+    fn time_runs(session: &mut Session, input_name: &str, input: &DynValue, iterations: usize) -> InferenceResult<BenchmarkStats> {
+        let mut samples = Vec::with_capacity(iterations);
+        for run in 0..=iterations {
+            let inference_start = Instant::now();
+            session
+                .run(ort::inputs![input_name => input])
+                .map_err(|e| InferenceError::inference_failed(format!("Inference execution failed: {:?}", e)))?;
+
+            // Run 0 is the warmup
+            if run > 0 {
+                samples.push(inference_start.elapsed().as_secs_f32() * 1000.0);
+            }
+        }
+
+        Ok(BenchmarkStats::from_samples(samples))
+    }
+
+    /// Zeros in the image input's declared shape and element type. Dynamic dims of a rank-4 input
+    /// become 1 for the batch, 3 for channels and the standard input size for height and width;
+    /// the channel axis is the last one when only that axis is a static 1 or 3 (NHWC), else axis 1.
+    /// Dynamic dims of inputs of any other rank become 1.
+    fn zero_input(input_type: &ValueType) -> InferenceResult<(Vec<i64>, TensorData)> {
+        let declared = input_type
+            .tensor_shape()
+            .ok_or_else(|| InferenceError::inference_failed("The image input is not a tensor"))?;
+        let (height, width) = (i64::from(IMAGE_HEIGHT), i64::from(IMAGE_WIDTH));
+        let is_channels = |dim: i64| dim == 1 || dim == 3;
+        let defaults = match declared[..] {
+            [_, channels, _, last] if !is_channels(channels) && is_channels(last) => vec![1, height, width, 3],
+            [_, _, _, _] => vec![1, 3, height, width],
+            _ => vec![1; declared.len()],
+        };
+        let shape: Vec<i64> = declared.iter().zip(defaults).map(|(&dim, default)| if dim < 0 { default } else { dim }).collect();
+        let len = shape
+            .iter()
+            .try_fold(1usize, |len, &dim| len.checked_mul(usize::try_from(dim).ok()?))
+            .ok_or_else(|| InferenceError::memory_error(format!("Zero input of shape {:?} is too large", shape)))?;
+        let input_data = match input_type.tensor_type() {
+            Some(TensorElementType::Uint8) => TensorData::U8(vec![0; len]),
+            Some(TensorElementType::Int8) => TensorData::I8(vec![0; len]),
+            _ => TensorData::F32(vec![0.0; len]),
+        };
+        Ok((shape, input_data))
+    }
+
+    /// Run the active model once on a zero input of its declared shape (dynamic dims filled as in
+    /// `zero_input`) and discard the output. Used by `KeepWarm`; stores nothing.
+    ///
+    /// Skipped while another call holds the session cache: a run in progress keeps the session
+    /// warm by itself, and waiting for it would only queue this run in front of the next real one.
+    pub fn warm_up() -> InferenceResult<()> {
+        let mut cache = match CACHED_SESSION.try_lock() {
+            Ok(cache) => cache,
+            Err(TryLockError::WouldBlock) => return Ok(()),
+            Err(TryLockError::Poisoned(poisoned)) => {
+                CACHED_SESSION.clear_poison();
+                poisoned.into_inner()
+            }
+        };
+        let CachedModel { session, image_input, .. } = cache.active_mut().ok_or_else(Self::no_model_error)?;
+        let input_name = Self::image_input_name(session, *image_input)?;
+        let (shape, input_data) = Self::zero_input(&session.inputs[*image_input].input_type)?;
+
+        let input_tensor = input_data.into_value(shape)?;
+        session
+            .run(ort::inputs![input_name.as_str() => input_tensor])
+            .map_err(|e| InferenceError::inference_failed(format!("Warmup run failed: {:?}", e)))?;
+        Ok(())
+    }
+
+    /// Stop the keep-alive and drop every cached session
+    pub fn clear_cache() {
+        KeepWarm::stop();
+        let mut cache = lock_cache();
+        cache.models.clear();
+        cache.set_active(None);
+        drop(cache);
+        engine().cached_run = None;
+    }
+
+    /// Check if any model is currently loaded in cache
+    pub fn is_model_loaded() -> bool {
+        lock_cache().active().is_some()
+    }
+
+    /// Get the path of the currently loaded model
+    pub fn get_loaded_model_path() -> Option<String> {
+        lock_cache().active().map(|cached| cached.path.clone())
+    }
+
+    /// Get the paths of all cached models, oldest first
+    pub fn loaded_models() -> Vec<String> {
+        Self::loaded_model_info().into_iter().map(|info| info.path).collect()
+    }
+
+    /// Get path, approximate size and active flag for every cached model, oldest first
+    pub fn loaded_model_info() -> Vec<LoadedModelInfo> {
+        let cache = lock_cache();
+        cache
+            .models
+            .iter()
+            .enumerate()
+            .map(|(index, cached)| LoadedModelInfo {
+                path: cached.path.clone(),
+                size_bytes: cached.size_bytes,
+                active: cache.active == Some(index),
+            })
+            .collect()
+    }
+
+    /// Check whether the cached model's declared output looks like class logits
+    pub fn model_looks_like_classifier() -> bool {
+        lock_cache().active().is_some_and(|cached| cached.looks_like_classifier)
+    }
+
+    /// Number of classes the active model's classification output has (`None` for
+    /// non-classification models, or with no model loaded)
+    pub fn num_classes() -> Option<usize> {
+        lock_cache()
+            .active()
+            .filter(|cached| cached.looks_like_classifier)
+            .and_then(|cached| cached.class_count)
+    }
+
+    /// Name of the execution provider the active model's session was built with
+    pub fn active_execution_provider() -> Option<String> {
+        lock_cache().active().map(|cached| cached.info.provider.clone())
+    }
+
+    /// Inputs, outputs, opset, producer and provider of the active model
+    pub fn active_model_info() -> Option<ModelInfo> {
+        lock_cache().active().map(|cached| cached.info.clone())
+    }
+
+    /// Get the last inference result (for JNI compatibility)
+    pub fn get_last_result() -> Option<InferenceOutput> {
+        engine().last_result.clone()
+    }
+
+    /// Write the last captured input, denormalized back to RGB with the config it was made with,
+    /// as a PNG file. Needs input capture (`set_input_capture`) on during the run.
+    pub fn save_debug_input(out_path: &str) -> InferenceResult<()> {
+        let (tensor, config) = engine().last_input.clone().ok_or_else(|| {
+            InferenceError::no_result("no captured input yet, turn on input capture and run inference first")
+        })?;
+
+        Self::denormalize_to_rgb(&tensor, &config)
+            .save_with_format(out_path, image::ImageFormat::Png)
+            .map_err(|e| InferenceError::output_processing_failed(format!("Failed to write debug image '{}': {}", out_path, e)))
+    }
+
+    /// Invert `config`'s normalization on a `[1, 3, H, W]` input tensor, giving the RGB image
+    /// the model saw (after resizing). Values are rounded and clamped to 0..=255.
+    pub fn denormalize_to_rgb(tensor: &Array4<f32>, config: &PreprocessConfig) -> image::RgbImage {
+        ImagePreprocessor::denormalize_to_rgb(tensor, &config.normalization, config.data_order)
+    }
+
+    /// Top `k` class groups of the last classification result, by summed probability
+    pub fn top_groups(k: usize) -> InferenceResult<Vec<GroupPrediction>> {
+        let groups = ConfigManager::postprocess_config()
+            .class_groups
+            .ok_or_else(|| InferenceError::output_processing_failed("No class groups set"))?;
+        let probabilities = Self::get_last_result()
+            .and_then(|result| result.probabilities)
+            .ok_or_else(|| InferenceError::no_result("no classification result yet, run inference first"))?;
+
+        let totals = Postprocessor::group_probabilities(&probabilities, &groups);
+        Ok(Postprocessor::top_k(&totals, k)
+            .into_iter()
+            .map(|(group_id, confidence)| GroupPrediction {
+                group_id,
+                group_name: groups.names[group_id].clone(),
+                confidence,
+            })
+            .collect())
+    }
+
+    /// Top-k predictions per configured classification head, from the last result's raw output
+    pub fn top_predictions_per_head(k: usize) -> InferenceResult<Vec<HeadPredictions>> {
+        let heads = ConfigManager::postprocess_config().classification_heads;
+        if heads.is_empty() {
+            return Err(InferenceError::output_processing_failed("No classification heads set"));
+        }
+        let result = Self::get_last_result().ok_or_else(|| InferenceError::no_result("no result yet, run inference first"))?;
+        Self::head_predictions(&result.data, &heads, k)
+    }
+
+    /// Write the last result's full class distribution as `class_id,class_name,confidence` CSV rows,
+    /// most confident first. Returns the number of rows written.
+    pub fn export_distribution_csv(out_path: &str) -> InferenceResult<usize> {
+        let probabilities = Self::get_last_result()
+            .and_then(|result| result.probabilities)
+            .ok_or_else(|| InferenceError::no_result("no classification result yet, run inference first"))?;
+
+        std::fs::write(out_path, Self::distribution_csv(&probabilities))
+            .map_err(|e| InferenceError::output_processing_failed(format!("Failed to write CSV '{}': {}", out_path, e)))?;
+        Ok(probabilities.len())
+    }
+
+    /// Format a class distribution as CSV with a header row, sorted by descending confidence
+    fn distribution_csv(probabilities: &[f32]) -> String {
+        let labels = LabelsManager::get_labels();
+        let mut csv = String::from("class_id,class_name,confidence\n");
+        for (class_id, confidence) in Postprocessor::top_k(probabilities, probabilities.len()) {
+            let name = labels.get(class_id).cloned().unwrap_or_else(|| LabelsManager::get_label(class_id));
+            csv.push_str(&format!("{},{},{}\n", class_id, csv_field(&name), confidence));
+        }
+        csv
+    }
+
+    /// Check that a caller-provided buffer can hold the whole output
+    pub fn check_output_capacity(output_len: usize, capacity: usize) -> InferenceResult<()> {
+        if output_len <= capacity {
+            Ok(())
+        } else {
+            Err(InferenceError::output_processing_failed(format!(
+                "output has {} values but the buffer only holds {}",
+                output_len, capacity
+            )))
+        }
+    }
+
+    /// Length of the Java array that receives `len` output values (Java arrays are indexed by `int`)
+    pub fn java_array_len(len: usize) -> InferenceResult<i32> {
+        i32::try_from(len).map_err(|_| {
+            InferenceError::output_processing_failed(format!(
+                "failed to marshal output to Java: len={} exceeds the maximum Java array length",
+                len
+            ))
+        })
+    }
+
+    /// Index ranges of `len` output values, copied to Java one JNI region call each
+    pub fn output_chunks(len: usize) -> impl Iterator<Item = std::ops::Range<usize>> {
+        (0..len)
+            .step_by(OUTPUT_MARSHAL_CHUNK)
+            .map(move |start| start..(start + OUTPUT_MARSHAL_CHUNK).min(len))
+    }
+
+    /// Attach JNI marshalling time to the last stored result
+    pub fn record_jni_time(jni_time_ms: f32) {
+        if let Some(result) = engine().last_result.as_mut() {
+            result.jni_time_ms = jni_time_ms;
+        }
+    }
+
+    /// Store error message for JNI retrieval
+    pub fn store_error(error: &str) {
+        Self::store_error_message(error, None);
+    }
+
+    /// Store an error message together with the `InferenceError` it describes
+    pub fn store_error_detail(message: &str, error: &InferenceError) {
+        Self::store_error_message(message, Some(error.clone()));
+    }
+
+    fn store_error_message(message: &str, detail: Option<InferenceError>) {
+        engine().last_error = Some((message.to_string(), detail));
+    }
+
+    /// Get last error message (for JNI compatibility)
+    pub fn get_last_error() -> Option<String> {
+        engine().last_error.as_ref().map(|(message, _)| message.clone())
+    }
+
+    /// Last error as `{"code":..,"variant":..,"message":..}`; plain messages without an
+    /// `InferenceError` behind them report code 0 and variant `"Other"`
+    pub fn last_error_json() -> Option<String> {
+        let (message, detail) = engine().last_error.clone()?;
+        let (code, variant) = detail.map_or((0, "Other"), |error| (error.code(), error.variant()));
+        Some(error_json(code, variant, &message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ArenaConfig, ArenaExtendStrategy, Calibration, NormalizationPreset};
+    use crate::test_utils::{encode_png, lock_global_state, onnx, require_ort, write_temp_file};
+    use crate::constants::MIN_CLASSIFICATION_CLASSES;
+    use crate::types::PostprocessMode;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_softmax() {
+        let input = vec![1.0, 2.0, 3.0];
+        let output = InferenceEngine::softmax(&input);
+        
+        // Check sum equals 1.0
+        let sum: f32 = output.iter().sum();
+        assert!((sum - 1.0).abs() < 1e-6);
+        
+        // Check monotonicity (larger input -> larger output)
+        assert!(output[0] < output[1]);
+        assert!(output[1] < output[2]);
     }
 
     #[test]
     fn test_top_predictions() {
         let probs = vec![0.1, 0.7, 0.2];
-        let predictions = InferenceEngine::get_top_predictions(&probs, 2);
+        let predictions = InferenceEngine::get_top_predictions(&probs, 2, &PostprocessConfig::default(), None);
         
         assert_eq!(predictions.len(), 2);
         assert_eq!(predictions[0].class_id, 1); // Index of highest prob (0.7)
         assert_eq!(predictions[1].class_id, 2); // Index of second highest (0.2)
     }
+
+    #[test]
+    fn test_top_predictions_label_against_the_model_that_ran() {
+        let _guard = lock_global_state();
+        LabelsManager::load_labels_from_content("dog\ncat\nbird\n").unwrap();
+        // The active model has 4 classes, so its own predictions get `class_N` names
+        LabelsManager::set_model_class_count(Some(4));
+        let probs = [0.1, 0.7, 0.2];
+
+        let own = InferenceEngine::get_top_predictions(&probs, 1, &PostprocessConfig::default(), Some(3));
+        let active = InferenceEngine::get_top_predictions(&probs, 1, &PostprocessConfig::default(), Some(4));
+        LabelsManager::set_model_class_count(None);
+        LabelsManager::clear_labels();
+
+        assert_eq!(own[0].class_name, "cat");
+        assert_eq!(active[0].class_name, "class_1");
+    }
+
+    #[test]
+    fn test_allowlist_excludes_other_classes() {
+        let probs = vec![0.05, 0.4, 0.3, 0.15, 0.1];
+        let config = PostprocessConfig {
+            class_allowlist: Some(vec![4, 0, 3, 99]),
+            ..PostprocessConfig::default()
+        };
+
+        let predictions = InferenceEngine::get_top_predictions(&probs, 5, &config, None);
+        let ids: Vec<usize> = predictions.iter().map(|p| p.class_id).collect();
+        assert_eq!(ids, vec![3, 4, 0]);
+    }
+
+    #[test]
+    fn test_bundled_fixture_matches_builder() {
+        // Regenerate tests/fixtures/tiny_classifier.onnx if the builder changes
+        let fixture = include_bytes!("../tests/fixtures/tiny_classifier.onnx");
+        assert_eq!(fixture.as_slice(), onnx::tiny_classifier(1000).as_slice());
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_deterministic_inference_is_reproducible() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("deterministic.onnx", &onnx::tiny_classifier(1000));
+        ConfigManager::set_deterministic(true);
+        InferenceEngine::load_model(&model_path).unwrap();
+
+        let image = encode_png(64, 48, [120, 60, 200]);
+        let first = InferenceEngine::run_inference(&image).unwrap();
+        let second = InferenceEngine::run_inference(&image).unwrap();
+        ConfigManager::set_deterministic(false);
+
+        assert_eq!(first.data, second.data);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_concurrent_pure_runs_leave_last_result_alone() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("pure.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        engine().last_result = None;
+
+        let images = [encode_png(32, 32, [250, 10, 10]), encode_png(32, 32, [10, 10, 250])];
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let runs: Vec<_> = images
+                .iter()
+                .map(|image| scope.spawn(|| InferenceEngine::run_inference_pure(image).unwrap()))
+                .collect();
+            runs.into_iter().map(|run| run.join().unwrap()).collect()
+        });
+        assert!(InferenceEngine::get_last_result().is_none());
+
+        assert_ne!(results[0].data, results[1].data);
+        for (image, result) in images.iter().zip(&results) {
+            assert_eq!(InferenceEngine::run_inference(image).unwrap().data, result.data);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_inference_with_inter_op_threads() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("inter_op.onnx", &onnx::tiny_classifier(1000));
+        ConfigManager::set_inter_op_threads(2);
+        let loaded = InferenceEngine::load_model(&model_path);
+        let result = InferenceEngine::run_inference(&encode_png(40, 40, [10, 200, 30]));
+        ConfigManager::set_inter_op_threads(0);
+
+        loaded.unwrap();
+        assert_eq!(result.unwrap().shape, vec![1, 1000]);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_loaded_models_lists_every_cached_model() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let first = write_temp_file("cached_a.onnx", &onnx::tiny_classifier(1000));
+        let second = write_temp_file("cached_b.onnx", &onnx::tiny_classifier(1001));
+        InferenceEngine::load_model(&first).unwrap();
+        InferenceEngine::load_model(&second).unwrap();
+
+        let models = InferenceEngine::loaded_models();
+        assert!(models.contains(&first));
+        assert!(models.contains(&second));
+
+        let info = InferenceEngine::loaded_model_info();
+        let active: Vec<_> = info.iter().filter(|model| model.active).collect();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].path, second);
+        assert!(active[0].size_bytes > 0);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_run_inference_by_index() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let first = write_temp_file("by_index_a.onnx", &onnx::tiny_classifier(1000));
+        let second = write_temp_file("by_index_b.onnx", &onnx::tiny_classifier(1001));
+        InferenceEngine::load_model(&first).unwrap();
+        InferenceEngine::load_model(&second).unwrap();
+        let paths = InferenceEngine::loaded_models();
+        let first_index = paths.iter().position(|path| *path == first).unwrap();
+
+        let image = encode_png(32, 32, [10, 20, 30]);
+        let result = InferenceEngine::run_inference_by_index(first_index, &image).unwrap();
+        assert_eq!(result.data.len(), 1000);
+        assert_eq!(InferenceEngine::get_last_result().map(|last| last.data), Some(result.data));
+        // The last loaded model stays active
+        assert_eq!(InferenceEngine::get_loaded_model_path(), Some(second));
+
+        let err = InferenceEngine::run_inference_by_index(paths.len(), &image).unwrap_err();
+        assert!(matches!(err, InferenceError::ModelNotFound(_)));
+        assert!(err.to_string().contains(&format!("No cached model at index {}", paths.len())));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_run_by_index_labels_with_that_models_class_count() {
+        require_ort!();
+        let _guard = lock_global_state();
+        InferenceEngine::clear_cache();
+
+        let labels: String = (0..1000).map(|i| format!("label_{}\n", i)).collect();
+        LabelsManager::load_labels_from_content(&labels).unwrap();
+        let matching = write_temp_file("by_index_labels_a.onnx", &onnx::tiny_classifier(1000));
+        let other = write_temp_file("by_index_labels_b.onnx", &onnx::tiny_classifier(1001));
+        InferenceEngine::load_model(&matching).unwrap();
+        InferenceEngine::load_model(&other).unwrap();
+        let warning = LabelsManager::get_label_warning();
+        let index = InferenceEngine::loaded_models().iter().position(|path| *path == matching).unwrap();
+
+        let result = InferenceEngine::run_inference_by_index(index, &encode_png(32, 32, [10, 20, 30])).unwrap();
+        let top = &result.top_predictions[0];
+        LabelsManager::clear_labels();
+
+        // The 1000-class model's predictions use the loaded labels although the active model has 1001
+        assert_eq!(top.class_name, format!("label_{}", top.class_id));
+        assert!(warning.is_some());
+        assert_eq!(LabelsManager::get_label_warning(), warning);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_ensemble_averages_member_probabilities() {
+        require_ort!();
+        let _guard = lock_global_state();
+        InferenceEngine::clear_cache();
+
+        let first = write_temp_file("ensemble_a.onnx", &onnx::tiny_classifier(1000));
+        let second = write_temp_file("ensemble_b.onnx", &onnx::pooled_classifier(1000));
+        InferenceEngine::load_model(&first).unwrap();
+        InferenceEngine::load_model(&second).unwrap();
+        let image = encode_png(32, 32, [200, 40, 90]);
+        let a = InferenceEngine::run_inference_by_index(0, &image).unwrap().probabilities.unwrap();
+        let b = InferenceEngine::run_inference_by_index(1, &image).unwrap().probabilities.unwrap();
+
+        let result = InferenceEngine::run_ensemble(&[0, 1], &image).unwrap();
+        let averaged = result.probabilities.unwrap();
+        assert!(averaged.iter().zip(a.iter().zip(&b)).all(|(&p, (&pa, &pb))| (p - (pa + pb) / 2.0).abs() < 1e-6));
+        assert_eq!(InferenceEngine::get_loaded_model_path(), Some(second.clone()));
+
+        ConfigManager::set_ensemble_weights(Some(vec![3.0, 1.0]));
+        let weighted = InferenceEngine::run_ensemble(&[0, 1], &image).unwrap().probabilities.unwrap();
+        ConfigManager::set_ensemble_weights(None);
+        assert!(weighted.iter().zip(a.iter().zip(&b)).all(|(&p, (&pa, &pb))| (p - (3.0 * pa + pb) / 4.0).abs() < 1e-6));
+
+        let mismatched = write_temp_file("ensemble_c.onnx", &onnx::tiny_classifier(1001));
+        InferenceEngine::load_model(&mismatched).unwrap();
+        let err = InferenceEngine::run_ensemble(&[0, 2], &image).unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidOutput(_)));
+        assert!(err.to_string().contains("must share a class count"));
+        assert!(InferenceEngine::run_ensemble(&[0, 3], &image).is_err());
+    }
+
+    #[test]
+    fn test_combine_ensemble_weights_member_probabilities() {
+        let _guard = lock_global_state();
+        let member = |probabilities: Vec<f32>, inference_ms: f32| {
+            InferenceOutput::new_with_timing(vec![], vec![1, probabilities.len()], true, vec![], inference_ms, 1.0, 0.0)
+                .with_probabilities(probabilities)
+        };
+        let outputs = [member(vec![0.2, 0.8], 2.0), member(vec![0.6, 0.4], 3.0)];
+
+        let result = InferenceEngine::combine_ensemble(&outputs, &[0, 1], &[3.0, 1.0]).unwrap();
+        let probabilities = result.probabilities.unwrap();
+        assert!((probabilities[0] - 0.3).abs() < 1e-6 && (probabilities[1] - 0.7).abs() < 1e-6);
+        assert_eq!(result.top_predictions[0].class_id, 1);
+        assert_eq!(result.inference_time_ms, 5.0);
+
+        // The combined ranking is labelled for the members' 2 classes even while a 3-class model is active
+        LabelsManager::load_labels_from_content("cat\ndog\n").unwrap();
+        LabelsManager::set_model_class_count(Some(3));
+        let labelled = InferenceEngine::combine_ensemble(&outputs, &[0, 1], &[1.0, 1.0]).unwrap();
+        LabelsManager::set_model_class_count(None);
+        LabelsManager::clear_labels();
+        assert_eq!(labelled.top_predictions[0].class_name, "dog");
+
+        let uneven = [member(vec![0.5, 0.5], 1.0), member(vec![0.2, 0.3, 0.5], 1.0)];
+        let err = InferenceEngine::combine_ensemble(&uneven, &[0, 2], &[1.0, 1.0]).unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidOutput(ref msg) if msg.contains("must share a class count")));
+        let err = InferenceEngine::combine_ensemble(&outputs, &[0, 1], &[0.0, 0.0]).unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidConfig(_)));
+
+        // A weight count that doesn't match the models is a configuration error, caught before any run
+        ConfigManager::set_ensemble_weights(Some(vec![1.0]));
+        let err = InferenceEngine::run_ensemble(&[0, 1], &[]).unwrap_err();
+        ConfigManager::set_ensemble_weights(None);
+        assert!(matches!(err, InferenceError::InvalidConfig(ref msg) if msg.contains("1 ensemble weights set for 2 models")));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_num_classes_of_known_classifier() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("num_classes.onnx", &onnx::tiny_classifier(1001));
+        InferenceEngine::load_model(&model_path).unwrap();
+        assert_eq!(InferenceEngine::num_classes(), Some(1001));
+
+        InferenceEngine::clear_cache();
+        assert_eq!(InferenceEngine::num_classes(), None);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_inference_with_tight_arena() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("arena.onnx", &onnx::tiny_classifier(1000));
+        ConfigManager::set_arena_config(Some(ArenaConfig {
+            extend_strategy: ArenaExtendStrategy::SameAsRequested,
+            initial_chunk_bytes: 64 * 1024,
+        }));
+        let loaded = InferenceEngine::load_model(&model_path);
+        let result = InferenceEngine::run_inference(&encode_png(40, 40, [60, 90, 120]));
+        ConfigManager::set_arena_config(None);
+
+        loaded.unwrap();
+        assert_eq!(result.unwrap().shape, vec![1, 1000]);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_active_provider_reported_after_load() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("provider.onnx", &onnx::tiny_classifier(1000));
+        ConfigManager::set_execution_provider(ExecutionProviderKind::Nnapi);
+        let loaded = InferenceEngine::load_model(&model_path);
+        ConfigManager::set_execution_provider(ExecutionProviderKind::Cpu);
+
+        loaded.unwrap();
+        let provider = InferenceEngine::active_execution_provider().unwrap();
+        assert!(!provider.is_empty());
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_provider_chain_ending_in_cpu_always_loads() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("provider_chain.onnx", &onnx::tiny_classifier(1000));
+        let chain = vec![ExecutionProviderKind::Nnapi, ExecutionProviderKind::Xnnpack, ExecutionProviderKind::Cpu];
+        let provider = InferenceEngine::load_model_with_providers(&model_path, chain).unwrap();
+
+        assert!(!provider.is_empty());
+        assert_eq!(InferenceEngine::active_execution_provider(), Some(provider));
+        assert!(InferenceEngine::run_inference(&encode_png(32, 32, [5, 6, 7])).is_ok());
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_model_info_populated_after_load() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("model_info.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+
+        let info = InferenceEngine::active_model_info().unwrap();
+        assert_eq!(info.path, model_path);
+        assert_eq!(info.inputs.len(), 1);
+        assert_eq!(info.inputs[0].shape.as_deref(), Some(&[1, 3, -1, -1][..]));
+        assert_eq!(info.outputs[0].shape.as_deref(), Some(&[1, 1000][..]));
+        assert_eq!(info.opset, Some(13));
+        assert_eq!(info.producer, "onnx_inference_tests");
+        assert!(!info.provider.is_empty());
+        assert!(info.to_json().contains("\"opset\":13"));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_requested_output_subset() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("two_outputs.onnx", &onnx::pooled_classifier(10));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let image = encode_png(32, 32, [10, 20, 30]);
+
+        ConfigManager::set_requested_outputs(vec!["pooled".to_string()]);
+        let pooled_only = InferenceEngine::run_inference(&image);
+        ConfigManager::set_requested_outputs(vec!["logits".to_string(), "missing".to_string()]);
+        let missing = InferenceEngine::run_inference(&image);
+        ConfigManager::set_requested_outputs(Vec::new());
+
+        let pooled_only = pooled_only.unwrap();
+        assert_eq!(pooled_only.output_name, "pooled");
+        assert_eq!(pooled_only.shape, vec![1, 3]);
+        assert!(pooled_only.extra_outputs.is_empty());
+        assert!(missing.unwrap_err().to_string().contains("Requested output 'missing' not found"));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_output_shapes_cover_every_output() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("output_shapes.onnx", &onnx::pooled_classifier(10));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let result = InferenceEngine::run_inference(&encode_png(32, 32, [10, 20, 30])).unwrap();
+
+        assert_eq!(result.shape, vec![1, 10]);
+        assert_eq!(
+            result.output_shapes,
+            vec![("logits".to_string(), vec![1, 10]), ("pooled".to_string(), vec![1, 3])]
+        );
+    }
+
+    #[test]
+    fn test_validate_input_rejects_undecodable_image() {
+        let _guard = lock_global_state();
+        let err = InferenceEngine::validate_input(b"not an image").unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidImageData(_)));
+    }
+
+    #[test]
+    fn test_validate_input_leaves_shared_state_alone() {
+        let _guard = lock_global_state();
+        InferenceEngine::clear_cache();
+        ImagePreprocessor::record(None);
+        engine().last_input = None;
+        engine().last_error = None;
+
+        // Preprocessing would record input stats if this were a real run
+        ConfigManager::set_resize_mode(ResizeMode::Letterbox);
+        let err = InferenceEngine::validate_input(&encode_png(64, 48, [1, 2, 3])).unwrap_err();
+        ConfigManager::set_resize_mode(PreprocessConfig::default().resize_mode);
+
+        assert!(matches!(err, InferenceError::ModelNotFound(_)), "{:?}", err);
+        assert!(ImagePreprocessor::last_input_stats().is_none());
+        assert!(engine().last_input.is_none());
+        assert!(engine().last_error.is_none());
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_validate_input_checks_model_shape() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("validate.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        assert_eq!(InferenceEngine::validate_input(&encode_png(64, 48, [1, 2, 3])).unwrap(), vec![1, 3, 224, 224]);
+
+        // Stretch always yields 224x224, which a fixed 320x320 input rejects
+        let fixed = onnx::ModelBuilder::new()
+            .input("input", &[onnx::Dim::Fixed(1), onnx::Dim::Fixed(3), onnx::Dim::Fixed(320), onnx::Dim::Fixed(320)])
+            .output("output", &[onnx::Dim::Fixed(1), onnx::Dim::Fixed(3), onnx::Dim::Fixed(320), onnx::Dim::Fixed(320)])
+            .node("Relu", &["input"], &["output"], &[])
+            .build();
+        InferenceEngine::load_model(&write_temp_file("validate_fixed.onnx", &fixed)).unwrap();
+        let err = InferenceEngine::validate_input(&encode_png(64, 48, [1, 2, 3])).unwrap_err();
+        assert!(matches!(err, InferenceError::ShapeMismatch(_)));
+    }
+
+    #[test]
+    fn test_read_fd_range() {
+        use std::os::fd::AsRawFd;
+
+        let path = write_temp_file("fd_range.bin", b"header|model bytes|trailer");
+        let file = std::fs::File::open(&path).unwrap();
+        let fd = file.as_raw_fd();
+
+        assert_eq!(InferenceEngine::read_fd_range(fd, 7, 11).unwrap(), b"model bytes");
+        assert!(InferenceEngine::read_fd_range(-1, 0, 4).unwrap_err().to_string().contains("Invalid file descriptor"));
+        assert!(InferenceEngine::read_fd_range(fd, 0, 0).unwrap_err().to_string().contains("Invalid model range"));
+        assert!(InferenceEngine::read_fd_range(fd, 20, 100).unwrap_err().to_string().contains("past the end of the 26-byte file"));
+        assert!(InferenceEngine::read_fd_range(fd, i64::MAX, 1).unwrap_err().to_string().contains("past the end"));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_load_model_from_fd() {
+        require_ort!();
+        use std::os::fd::AsRawFd;
+        let _guard = lock_global_state();
+
+        // Embed the model between other bytes, as in an uncompressed APK asset
+        let model = onnx::tiny_classifier(1000);
+        let mut packed = b"asset prefix".to_vec();
+        packed.extend_from_slice(&model);
+        packed.extend_from_slice(b"suffix");
+        let path = write_temp_file("fd_model.bin", &packed);
+        let file = std::fs::File::open(&path).unwrap();
+
+        InferenceEngine::load_model_from_fd(file.as_raw_fd(), 12, model.len() as i64).unwrap();
+        let result = InferenceEngine::run_inference(&encode_png(40, 40, [30, 60, 90])).unwrap();
+        assert_eq!(result.shape, vec![1, 1000]);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_benchmark_runs_requested_iterations() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("benchmark.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        engine().last_result = None;
+
+        let stats = InferenceEngine::benchmark(&encode_png(32, 32, [1, 2, 3]), 7).unwrap();
+        assert_eq!(stats.iterations, 7);
+        assert!(stats.min_ms <= stats.p50_ms && stats.p95_ms <= stats.max_ms);
+        assert!(InferenceEngine::get_last_result().is_none());
+
+        // A single-channel model is fed the luma tensor a real run builds, not an RGB one
+        let grayscale = write_temp_file("benchmark_gray.onnx", &onnx::grayscale_classifier(1000));
+        InferenceEngine::load_model(&grayscale).unwrap();
+        assert_eq!(InferenceEngine::benchmark(&encode_png(32, 32, [1, 2, 3]), 2).unwrap().iterations, 2);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_model_only_benchmark_runs_requested_iterations() {
+        assert!(InferenceEngine::benchmark_model_only(0).is_err());
+
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("benchmark_model_only.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+
+        let stats = InferenceEngine::benchmark_model_only(5).unwrap();
+        assert_eq!(stats.iterations, 5);
+        assert!(stats.min_ms <= stats.mean_ms && stats.p95_ms <= stats.max_ms);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_tensor_input_matches_image_path() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("tensor_input.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+
+        let image = encode_png(48, 48, [70, 140, 210]);
+        let from_image = InferenceEngine::run_inference(&image).unwrap();
+
+        let tensor = ImagePreprocessor::preprocess_image(&image, &ConfigManager::preprocess_config()).unwrap();
+        let shape = tensor.shape().to_vec();
+        let from_tensor = InferenceEngine::run_inference_tensor(tensor.into_raw_vec(), &shape).unwrap();
+
+        assert_eq!(from_tensor.data, from_image.data);
+        assert_eq!(from_tensor.preprocessing_time_ms, 0.0);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_result_reports_original_image_size() {
+        require_ort!();
+        let _guard = lock_global_state();
+        let model_path = write_temp_file("orig_size.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+
+        let result = InferenceEngine::run_inference(&encode_png(37, 21, [10, 20, 30])).unwrap();
+        assert_eq!((result.orig_width, result.orig_height), (37, 21));
+        assert_eq!(result.shape, vec![1, 1000]);
+
+        let tensor = InferenceEngine::run_inference_tensor(vec![0.0; 3 * 8 * 8], &[1, 3, 8, 8]).unwrap();
+        assert_eq!((tensor.orig_width, tensor.orig_height), (0, 0));
+    }
+
+    #[test]
+    fn test_tensor_input_rejects_element_count_mismatch() {
+        let err = InferenceEngine::run_inference_tensor(vec![0.0; 10], &[1, 3, 2, 2]).unwrap_err();
+        assert!(matches!(err, InferenceError::ShapeMismatch(_)));
+
+        // A product that overflows is an error, not a panic
+        let err = InferenceEngine::run_inference_tensor(vec![0.0; 10], &[usize::MAX, 2]).unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidInput(_)));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_result_carries_logits_and_probabilities() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("logits_probs.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let result = InferenceEngine::run_inference(&encode_png(32, 32, [90, 150, 30])).unwrap();
+
+        let probabilities = result.probabilities.as_ref().expect("classification result has probabilities");
+        assert_eq!(probabilities.len(), result.data.len());
+        assert_eq!(probabilities, &InferenceEngine::softmax(&result.data));
+        assert!((probabilities.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_classify_returns_top_label() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("classify.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let labels: Vec<String> = (0..1000)
+            .map(|i| if i == 2 { "goldfish".to_string() } else { format!("label_{}", i) })
+            .collect();
+        LabelsManager::load_labels_from_content(&labels.join("\n")).unwrap();
+
+        // A pure red image pools to one feature vector whose best logit (tied every 7 classes) is first at class 2
+        let label = InferenceEngine::classify(&encode_png(16, 16, [255, 0, 0]));
+        LabelsManager::clear_labels();
+        assert_eq!(label.unwrap(), "goldfish");
+
+        let features = write_temp_file("classify_features.onnx", &onnx::tiny_classifier(10));
+        InferenceEngine::load_model(&features).unwrap();
+        assert_eq!(InferenceEngine::classify(&encode_png(16, 16, [255, 0, 0])).unwrap(), "");
+    }
+
+    #[test]
+    fn test_empty_output_is_invalid_output() {
+        let err = InferenceEngine::check_output_not_empty("logits", &[1, 0], &[]).unwrap_err();
+        assert_eq!(err.variant(), "InvalidOutput");
+        assert_eq!(err.to_string(), "Invalid model output: empty output tensor 'logits' with shape [1, 0]");
+
+        assert!(InferenceEngine::check_output_not_empty("logits", &[1, 2], &[0.1, 0.9]).is_ok());
+    }
+
+    #[test]
+    fn test_heads_softmax_independently() {
+        let heads = [
+            ClassificationHead::new(0, 3, "color".to_string()).unwrap(),
+            ClassificationHead::new(3, 2, "material".to_string()).unwrap(),
+        ];
+        let logits = [0.0, 3.0, 1.0, 5.0, 5.0];
+        let per_head = InferenceEngine::head_predictions(&logits, &heads, 2).unwrap();
+
+        assert_eq!(per_head[0].head, "color");
+        let color = InferenceEngine::softmax(&logits[..3]);
+        assert_eq!(per_head[0].predictions[0].class_id, 1);
+        assert_eq!(per_head[0].predictions[0].confidence, color[1]);
+        assert_eq!(per_head[0].predictions[1].class_id, 2);
+
+        // The second head's softmax ignores the first head's logits
+        let material: Vec<f32> = per_head[1].predictions.iter().map(|p| p.confidence).collect();
+        assert_eq!(material, vec![0.5, 0.5]);
+
+        let overrun = [ClassificationHead::new(4, 2, "type".to_string()).unwrap()];
+        assert!(InferenceEngine::head_predictions(&logits, &overrun, 2).is_err());
+    }
+
+    #[test]
+    fn test_region_predictions_rank_each_row() {
+        let logits = [
+            2.0, 0.0, 0.0, 0.0, //
+            0.0, 0.0, 0.0, 4.0, //
+            1.0, 3.0, 3.0, 0.0,
+        ];
+        let regions = InferenceEngine::region_predictions(&logits, &[3, 4], None).unwrap();
+
+        let top: Vec<usize> = regions.iter().map(|region| region.class_id).collect();
+        assert_eq!(top, vec![0, 3, 1]);
+        assert_eq!(regions[1].confidence, InferenceEngine::softmax(&logits[4..8])[3]);
+        assert!(InferenceEngine::region_predictions(&logits, &[1, 12], None).is_none());
+        assert_eq!(InferenceEngine::region_predictions(&logits, &[1, 3, 4], None).unwrap().len(), 3);
+
+        // Only the batch dim is dropped: a single region or a single class is still a row
+        let single = InferenceEngine::region_predictions(&logits[4..8], &[1, 1, 4], None).unwrap();
+        assert_eq!(single.iter().map(|region| region.class_id).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(InferenceEngine::region_predictions(&logits[..3], &[1, 3, 1], None).unwrap().len(), 3);
+        assert!(InferenceEngine::region_predictions(&logits, &[1, 3, 4, 1], None).is_none());
+    }
+
+    #[test]
+    fn test_softmax_skipped_above_class_limit() {
+        let logits = [1.0, 3.0, 2.0];
+        assert_eq!(InferenceEngine::softmax_within_limit(&logits, None), Some(InferenceEngine::softmax(&logits)));
+        assert_eq!(InferenceEngine::softmax_within_limit(&logits, Some(3)), Some(InferenceEngine::softmax(&logits)));
+        assert_eq!(InferenceEngine::softmax_within_limit(&logits, Some(2)), None);
+
+        let top = InferenceEngine::top_prediction_without_softmax(&logits, &PostprocessConfig::default(), None);
+        assert_eq!((top.len(), top[0].class_id, top[0].raw_score), (1, 1, Some(3.0)));
+        assert!((top[0].confidence - InferenceEngine::softmax(&logits)[1]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_calibration_scales_and_biases_logits() {
+        let logits = [2.0, 1.0, 0.0];
+        let uncalibrated = InferenceEngine::softmax(&logits);
+
+        // Temperature 2 halves every logit, flattening the distribution
+        let temperature_only = Calibration::new(2.0, Vec::new()).unwrap();
+        let flattened = InferenceEngine::softmax(&Postprocessor::calibrate(&logits, &temperature_only).unwrap());
+        assert_eq!(flattened, InferenceEngine::softmax(&[1.0, 0.5, 0.0]));
+        assert!(flattened[0] < uncalibrated[0] && flattened[2] > uncalibrated[2]);
+
+        // A +1 bias on the last class makes it tie with the first
+        let biased = Calibration::new(2.0, vec![0.0, 0.0, 1.0]).unwrap();
+        let probabilities = InferenceEngine::softmax(&Postprocessor::calibrate(&logits, &biased).unwrap());
+        assert!((probabilities[0] - probabilities[2]).abs() < 1e-6);
+
+        let mismatched = Calibration::new(1.0, vec![0.0; 2]).unwrap();
+        assert!(Postprocessor::calibrate(&logits, &mismatched).is_err());
+        assert!(Calibration::new(0.0, Vec::new()).is_none());
+        assert!(Calibration::new(1.0, vec![f32::NAN]).is_none());
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_large_output_reports_raw_argmax() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("softmax_limit.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        ConfigManager::set_max_softmax_classes(Some(100));
+        let result = InferenceEngine::run_inference(&encode_png(32, 32, [90, 150, 30]));
+        ConfigManager::set_max_softmax_classes(None);
+        let result = result.unwrap();
+
+        assert!(result.is_classification);
+        assert!(result.probabilities.is_none());
+        let argmax = Postprocessor::top_k(&result.data, 1)[0];
+        assert_eq!(result.top_predictions.len(), 1);
+        assert_eq!(result.top_predictions[0].class_id, argmax.0);
+        assert_eq!(result.top_predictions[0].raw_score, Some(argmax.1));
+        let probability = InferenceEngine::softmax(&result.data)[argmax.0];
+        assert!((result.top_predictions[0].confidence - probability).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_gzip_model_bytes_inflate() {
+        let model = onnx::tiny_classifier(1000);
+        assert_eq!(InferenceEngine::decompress_model("plain.onnx", model.clone()).unwrap(), model);
+
+        let compressed = gzip(&model);
+        let inflated = InferenceEngine::decompress_model("model.onnx.gz", compressed.clone());
+        if cfg!(feature = "gzip") {
+            assert_eq!(inflated.unwrap(), model);
+            // Detected by its magic bytes alone, as for a descriptor with no file name
+            assert_eq!(InferenceEngine::decompress_model("fd:3@0+10", compressed.clone()).unwrap(), model);
+            let capped = InferenceEngine::decompress_model_within("model.onnx.gz", compressed, model.len() as u64 - 1);
+            assert!(capped.unwrap_err().to_string().contains("inflates to more than"));
+        } else {
+            assert!(inflated.unwrap_err().to_string().contains("`gzip` feature"));
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_gzipped_model_matches_original() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model = onnx::tiny_classifier(1000);
+        let plain_path = write_temp_file("gzip_plain.onnx", &model);
+        let gzip_path = write_temp_file("gzip_model.onnx.gz", &gzip(&model));
+        let image = encode_png(32, 32, [200, 40, 90]);
+
+        InferenceEngine::load_model(&plain_path).unwrap();
+        let plain = InferenceEngine::run_inference(&image).unwrap();
+        InferenceEngine::load_model(&gzip_path).unwrap();
+        let inflated = InferenceEngine::run_inference(&image).unwrap();
+
+        assert_eq!(plain.data, inflated.data);
+    }
+
+    /// Gzip `bytes` (a gzip header alone when the feature is off, which is enough to be detected)
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        #[cfg(feature = "gzip")]
+        {
+            use std::io::Write;
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(bytes).unwrap();
+            encoder.finish().unwrap()
+        }
+        #[cfg(not(feature = "gzip"))]
+        {
+            let _ = bytes;
+            GZIP_MAGIC.to_vec()
+        }
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_postprocess_mode_follows_branch() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("postprocess_mode.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let image = encode_png(32, 32, [90, 150, 30]);
+        let mode = || InferenceEngine::run_inference(&image).map(|result| result.postprocess_mode);
+
+        let top_k = mode();
+        ConfigManager::set_max_softmax_classes(Some(100));
+        let argmax = mode();
+        ConfigManager::set_max_softmax_classes(None);
+        LabelsManager::set_expected_class_count(2000);
+        let raw = mode();
+        LabelsManager::set_expected_class_count(MIN_CLASSIFICATION_CLASSES);
+
+        assert_eq!(top_k.unwrap(), PostprocessMode::TopK);
+        assert_eq!(argmax.unwrap(), PostprocessMode::Argmax);
+        assert_eq!(raw.unwrap(), PostprocessMode::Raw);
+    }
+
+    #[test]
+    fn test_postprocess_mode_selection() {
+        // The steps `run_model` takes after the session, on a `[1, 4]` output
+        let logits = vec![0.5, 2.0, -1.0, 0.0];
+        let shape = vec![1, 4];
+        let mode = |min_classes: usize, max_softmax_classes: Option<usize>| {
+            let class_count = Postprocessor::classification_class_count(&shape, min_classes).unwrap();
+            let probabilities = class_count.and_then(|_| InferenceEngine::softmax_within_limit(&logits, max_softmax_classes));
+            let is_classification = class_count.is_some();
+            let result = InferenceOutput::new_with_timing(logits.clone(), shape.clone(), is_classification, vec![], 0.0, 0.0, 0.0);
+            match probabilities {
+                Some(probabilities) => result.with_probabilities(probabilities).postprocess_mode,
+                None => result.postprocess_mode,
+            }
+        };
+
+        assert_eq!(mode(4, None), PostprocessMode::TopK);
+        assert_eq!(mode(4, Some(3)), PostprocessMode::Argmax);
+        assert_eq!(mode(5, None), PostprocessMode::Raw);
+    }
+
+    #[test]
+    fn test_nchw_logits_classify_at_the_expected_class_count() {
+        let _guard = lock_global_state();
+
+        // `run_model` classifies against the expected class count, so a 3-class `[1, 3, 1, 1]`
+        // output is only classification once that count is lowered to match
+        let classify = |shape: &[usize]| Postprocessor::classification_class_count(shape, LabelsManager::expected_class_count());
+        assert_eq!(classify(&[1, 3, 1, 1]).unwrap(), None);
+        assert_eq!(classify(&[1, 1000, 1, 1]).unwrap(), Some(1000));
+
+        LabelsManager::set_expected_class_count(3);
+        let three_classes = classify(&[1, 3, 1, 1]);
+        let unpooled = classify(&[1, 3, 7, 7]);
+        LabelsManager::set_expected_class_count(MIN_CLASSIFICATION_CLASSES);
+
+        assert_eq!(three_classes.unwrap(), Some(3));
+        assert!(matches!(unpooled, Err(InferenceError::InvalidOutput(_))));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_run_classifies_pooled_nchw_logits() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        // Global average pool keeping its spatial dims: one `[1, 3, 1, 1]` logit per channel
+        let model = onnx::ModelBuilder::new()
+            .input("input", &[onnx::Dim::Fixed(1), onnx::Dim::Fixed(3), onnx::Dim::Dynamic("height"), onnx::Dim::Dynamic("width")])
+            .output("logits", &[onnx::Dim::Fixed(1), onnx::Dim::Fixed(3), onnx::Dim::Fixed(1), onnx::Dim::Fixed(1)])
+            .node("ReduceMean", &["input"], &["logits"], &[("axes", onnx::Attr::Ints(vec![2, 3])), ("keepdims", onnx::Attr::Int(1))])
+            .build();
+        InferenceEngine::load_model(&write_temp_file("nchw_logits.onnx", &model)).unwrap();
+        let image = encode_png(16, 16, [90, 140, 200]);
+
+        let below_threshold = InferenceEngine::run_inference(&image).unwrap();
+        LabelsManager::set_expected_class_count(3);
+        let classified = InferenceEngine::run_inference(&image);
+        LabelsManager::set_expected_class_count(MIN_CLASSIFICATION_CLASSES);
+
+        assert!(!below_threshold.is_classification);
+        let classified = classified.unwrap();
+        assert_eq!(classified.shape, vec![1, 3, 1, 1]);
+        assert!(classified.is_classification);
+        assert_eq!(classified.probabilities.unwrap().len(), 3);
+        // Blue normalizes highest, so its channel wins
+        assert_eq!(classified.top_predictions[0].class_id, 2);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_tta_on_uniform_image_matches_single_crop() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("tta.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let image = encode_png(64, 48, [90, 140, 200]);
+
+        let single = InferenceEngine::run_inference(&image).unwrap();
+        let tta = InferenceEngine::run_inference_tta(&image, TtaMode::TenCrop).unwrap();
+
+        let (single_probs, tta_probs) = (single.probabilities.unwrap(), tta.probabilities.unwrap());
+        assert_eq!(single_probs.len(), tta_probs.len());
+        assert!(single_probs.iter().zip(&tta_probs).all(|(a, b)| (a - b).abs() < 1e-5));
+        assert_eq!(single.top_predictions[0].class_id, tta.top_predictions[0].class_id);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_tta_views_follow_the_model_input_spec() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        // A single-channel input only accepts views preprocessed to luma
+        let model_path = write_temp_file("tta_gray.onnx", &onnx::grayscale_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let image = encode_png(64, 48, [90, 140, 200]);
+
+        let single = InferenceEngine::run_inference(&image).unwrap();
+        let tta = InferenceEngine::run_inference_tta(&image, TtaMode::TenCrop).unwrap();
+
+        let (single_probs, tta_probs) = (single.probabilities.unwrap(), tta.probabilities.unwrap());
+        assert!(single_probs.iter().zip(&tta_probs).all(|(a, b)| (a - b).abs() < 1e-5));
+        assert_eq!(single.top_predictions[0].class_id, tta.top_predictions[0].class_id);
+    }
+
+    #[test]
+    fn test_no_model_error_reports_last_load_attempt() {
+        let _guard = lock_global_state();
+
+        engine().last_load_attempt = None;
+        let never_loaded = InferenceEngine::no_model_error().to_string();
+        assert!(never_loaded.contains("Call load_model first"));
+
+        assert!(InferenceEngine::load_model("/missing/model.onnx").is_err());
+        let load_failed = InferenceEngine::no_model_error().to_string();
+        assert!(load_failed.contains("last load attempt: /missing/model.onnx failed with Model file not found: /missing/model.onnx"));
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_small_image_result_carries_resolution_warning() {
+        require_ort!();
+        let _guard = lock_global_state();
+        let model_path = write_temp_file("min_resolution.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        ConfigManager::set_min_image_dimension(Some(32));
+
+        let small = InferenceEngine::run_inference(&encode_png(16, 16, [10, 20, 30]));
+        let large = InferenceEngine::run_inference(&encode_png(64, 64, [10, 20, 30]));
+        ConfigManager::set_min_image_dimension(None);
+        let small = small.unwrap();
+        assert_eq!(small.warnings.len(), 1);
+        assert!(small.warnings[0].contains("16x16 image is below the 32px minimum side"));
+        // Each result carries only its own warnings
+        assert!(large.unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn test_result_listener_sees_completed_inference() {
+        let _guard = lock_global_state();
+        InferenceEngine::clear_cache();
+        let image = encode_png(8, 8, [4, 5, 6]);
+
+        // No model is loaded, so the result comes from the result cache, which still notifies
+        InferenceEngine::set_result_cache(true);
+        let seeded = InferenceOutput::new(vec![0.5, 0.5], vec![1, 2], false, vec![], 1.0, 1.0, 1.0, 3.0);
+        InferenceEngine::store_run(InferenceEngine::run_key(&image), &seeded);
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let seen = Arc::clone(&calls);
+        ResultListener::register(Arc::new(move |result: &InferenceOutput| {
+            assert!(result.cached);
+            seen.fetch_add(1, Ordering::SeqCst);
+        }));
+        let result = InferenceEngine::run_inference(&image);
+        assert!(ResultListener::unregister());
+        InferenceEngine::set_result_cache(false);
+
+        assert!(result.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!ResultListener::unregister());
+        // Unregistering dropped the hook and what it captured (the listener's global ref, over JNI)
+        assert_eq!(Arc::strong_count(&calls), 1);
+
+        // So does replacing it with another registration
+        let seen = Arc::clone(&calls);
+        ResultListener::register(Arc::new(move |_: &InferenceOutput| drop(Arc::clone(&seen))));
+        assert_eq!(Arc::strong_count(&calls), 2);
+        ResultListener::register(Arc::new(|_: &InferenceOutput| {}));
+        assert_eq!(Arc::strong_count(&calls), 1);
+        assert!(ResultListener::unregister());
+    }
+
+    #[test]
+    fn test_result_cache_serves_identical_bytes_without_running() {
+        let _guard = lock_global_state();
+        InferenceEngine::clear_cache();
+        let image = encode_png(8, 8, [1, 2, 3]);
+
+        InferenceEngine::set_result_cache(true);
+        let seeded = InferenceOutput::new(vec![0.25, 0.75], vec![1, 2], false, vec![], 1.0, 1.0, 1.0, 3.0);
+        InferenceEngine::store_run(InferenceEngine::run_key(&image), &seeded);
+
+        // No model is loaded, so only the cache can produce a result
+        let result = InferenceEngine::run_inference(&image).unwrap();
+        assert!(result.cached);
+        assert_eq!(result.data, seeded.data);
+        assert_eq!((result.inference_time_ms, result.total_time_ms), (0.0, 0.0));
+        assert!(InferenceEngine::get_last_result().unwrap().cached);
+        assert!(InferenceEngine::run_inference(&encode_png(8, 8, [1, 2, 4])).is_err());
+
+        // New labels or a different label mode can rename classes, so they miss too
+        LabelsManager::set_strict(true);
+        assert!(InferenceEngine::run_inference(&image).is_err());
+        LabelsManager::set_strict(false);
+        assert!(InferenceEngine::run_inference(&image).unwrap().cached);
+        LabelsManager::load_labels_from_content("cat\ndog").unwrap();
+        assert!(InferenceEngine::run_inference(&image).is_err());
+        LabelsManager::clear_labels();
+
+        InferenceEngine::set_result_cache(false);
+        assert!(InferenceEngine::run_inference(&image).is_err());
+    }
+
+    #[test]
+    fn test_deadline_exceeded_before_run() {
+        let _guard = lock_global_state();
+        let image = encode_png(64, 64, [30, 30, 30]);
+
+        let err = InferenceEngine::run_inference_with_deadline(&image, Some(Duration::ZERO)).unwrap_err();
+        assert!(matches!(err, InferenceError::Deadline(_)));
+    }
+
+    #[test]
+    fn test_poisoned_session_cache_recovers() {
+        let _guard = lock_global_state();
+
+        let _ = std::thread::spawn(|| {
+            let _cache = CACHED_SESSION.lock().unwrap();
+            panic!("poison the session cache");
+        })
+        .join();
+        assert!(CACHED_SESSION.is_poisoned());
+
+        // The next call recovers the cache instead of reporting a mutex failure
+        let result = InferenceEngine::run_inference(&encode_png(16, 16, [1, 2, 3]));
+        assert!(!matches!(result, Err(InferenceError::MemoryError(_))));
+        assert!(!CACHED_SESSION.is_poisoned());
+    }
+
+    #[test]
+    fn test_zero_input_follows_declared_layout() {
+        use ort::tensor::{Shape, SymbolicDimensions};
+        let zero_input = |ty: TensorElementType, dims: &[i64]| {
+            let input_type = ValueType::Tensor { ty, shape: Shape::from(dims), dimension_symbols: SymbolicDimensions::empty(dims.len()) };
+            InferenceEngine::zero_input(&input_type)
+        };
+        let shape_of = |dims: &[i64]| zero_input(TensorElementType::Float32, dims).unwrap().0;
+
+        assert_eq!(shape_of(&[-1, 3, -1, -1]), vec![1, 3, 224, 224]);
+        assert_eq!(shape_of(&[-1, -1, -1, 3]), vec![1, 224, 224, 3]);
+        assert_eq!(shape_of(&[1, 1, 96, -1]), vec![1, 1, 96, 224]);
+        assert_eq!(shape_of(&[-1, 150_528]), vec![1, 150_528]);
+
+        let (shape, data) = zero_input(TensorElementType::Uint8, &[1, 2, 2, 3]).unwrap();
+        assert_eq!(shape, vec![1, 2, 2, 3]);
+        assert!(matches!(data, TensorData::U8(ref data) if data.len() == 12));
+        let overflow = zero_input(TensorElementType::Float32, &[i64::MAX, i64::MAX, 3, 3]);
+        assert!(matches!(overflow, Err(InferenceError::MemoryError(_))));
+    }
+
+    #[test]
+    fn test_warm_up_skips_while_cache_is_busy() {
+        let _guard = lock_global_state();
+        InferenceEngine::clear_cache();
+        assert!(InferenceEngine::warm_up().is_err());
+
+        // With the cache held (as by a running inference) the keep-alive returns at once
+        let _cache = lock_cache();
+        assert!(InferenceEngine::warm_up().is_ok());
+    }
+
+    #[test]
+    fn test_roi_out_of_bounds_is_rejected() {
+        let _guard = lock_global_state();
+        let image = encode_png(64, 64, [80, 80, 80]);
+        let roi = Roi::from_signed(32, 32, 64, 16).unwrap();
+
+        let err = InferenceEngine::run_inference_on_roi(&image, roi).unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidImageData(_)));
+        assert!(err.to_string().contains("outside the 64x64 image"));
+
+        assert!(Roi::from_signed(-1, 0, 10, 10).is_none());
+        assert!(Roi::from_signed(0, 0, 10, 0).is_none());
+    }
+
+    #[test]
+    fn test_save_debug_input() {
+        let _guard = lock_global_state();
+        let out_path = write_temp_file("debug_input.png", &[]);
+
+        engine().last_input = None;
+        assert!(matches!(InferenceEngine::save_debug_input(&out_path), Err(InferenceError::NoResult(_))));
+
+        // Made with CLIP normalization: saving must use that, not the current config
+        let image = encode_png(32, 32, [200, 40, 90]);
+        let config = PreprocessConfig { normalization: NormalizationPreset::Clip.normalization(), ..Default::default() };
+        let tensor = ImagePreprocessor::preprocess_image(&image, &config).unwrap();
+        engine().last_input = Some((tensor, config));
+
+        InferenceEngine::save_debug_input(&out_path).unwrap();
+        let saved = image::open(&out_path).unwrap().to_rgb8();
+        assert_eq!(saved.dimensions(), (224, 224));
+        assert_eq!(saved.get_pixel(100, 100).0, [200, 40, 90]);
+
+        InferenceEngine::set_input_capture(false);
+        assert!(engine().last_input.is_none());
+    }
+
+    #[test]
+    fn test_denormalize_round_trips_preprocessing() {
+        // 224x224 is the stretch target, so no resampling happens
+        let gradient = image::RgbImage::from_fn(224, 224, |x, y| image::Rgb([x as u8, y as u8, (x + y) as u8 / 2]));
+        let mut png = Vec::new();
+        gradient.write_to(&mut std::io::Cursor::new(&mut png), image::ImageOutputFormat::Png).unwrap();
+
+        for preset in [NormalizationPreset::ImageNet, NormalizationPreset::Clip] {
+            let config = PreprocessConfig {
+                normalization: preset.normalization(),
+                ..Default::default()
+            };
+            let tensor = ImagePreprocessor::preprocess_image(&png, &config).unwrap();
+            let restored = InferenceEngine::denormalize_to_rgb(&tensor, &config);
+
+            assert_eq!(restored.dimensions(), gradient.dimensions());
+            let max_error = restored
+                .as_raw()
+                .iter()
+                .zip(gradient.as_raw())
+                .map(|(&a, &b)| a.abs_diff(b))
+                .max()
+                .unwrap();
+            assert!(max_error <= 1, "{:?}: max error {}", preset, max_error);
+        }
+    }
+
+    #[test]
+    fn test_export_distribution_csv() {
+        let _guard = lock_global_state();
+        let out_path = write_temp_file("distribution.csv", &[]);
+
+        engine().last_result = None;
+        assert!(matches!(InferenceEngine::export_distribution_csv(&out_path), Err(InferenceError::NoResult(_))));
+
+        LabelsManager::load_labels_from_content("cat\ndog, puppy\nbird\n").unwrap();
+        let result = InferenceOutput::new(vec![0.0; 3], vec![1, 3], true, vec![], 0.0, 0.0, 0.0, 0.0)
+            .with_probabilities(vec![0.2, 0.7, 0.1]);
+        engine().last_result = Some(result);
+
+        assert_eq!(InferenceEngine::export_distribution_csv(&out_path).unwrap(), 3);
+        let csv = std::fs::read_to_string(&out_path).unwrap();
+        LabelsManager::clear_labels();
+
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0], "class_id,class_name,confidence");
+        assert_eq!(rows[1], "1,\"dog, puppy\",0.7");
+        assert_eq!(rows[3], "2,bird,0.1");
+    }
+
+    #[test]
+    fn test_last_error_json_reports_variant() {
+        let _guard = lock_global_state();
+
+        InferenceEngine::store_error_detail("Failed to load model: gone", &InferenceError::model_not_found("gone"));
+        assert_eq!(
+            InferenceEngine::last_error_json().unwrap(),
+            "{\"code\":1,\"variant\":\"ModelNotFound\",\"message\":\"Failed to load model: gone\"}"
+        );
+
+        InferenceEngine::store_error("Invalid log level: 9");
+        assert!(InferenceEngine::last_error_json().unwrap().starts_with("{\"code\":0,\"variant\":\"Other\""));
+        assert_eq!(InferenceEngine::get_last_error().unwrap(), "Invalid log level: 9");
+    }
+
+    #[test]
+    fn test_record_jni_time() {
+        let _guard = lock_global_state();
+        let result = InferenceOutput::new_with_timing(vec![0.0; 4], vec![1, 4], false, Vec::new(), 2.0, 1.0, 1.0);
+        engine().last_result = Some(result);
+
+        InferenceEngine::record_jni_time(0.75);
+        let stored = InferenceEngine::get_last_result().unwrap();
+        assert_eq!(stored.jni_time_ms, 0.75);
+        assert_eq!(stored.total_time_ms, 4.0);
+        assert_eq!(stored.end_to_end_time_ms(), 4.75);
+    }
+
+    #[test]
+    fn test_output_buffer_too_small() {
+        assert!(InferenceEngine::check_output_capacity(1000, 1000).is_ok());
+        assert!(InferenceEngine::check_output_capacity(1000, 4096).is_ok());
+
+        let err = InferenceEngine::check_output_capacity(1000, 999).unwrap_err();
+        assert!(err.to_string().contains("output has 1000 values but the buffer only holds 999"));
+    }
+
+    #[test]
+    fn test_output_marshalling_chunks() {
+        let len = OUTPUT_MARSHAL_CHUNK * 2 + 5;
+        let chunks: Vec<_> = InferenceEngine::output_chunks(len).collect();
+        assert_eq!(
+            chunks,
+            vec![0..OUTPUT_MARSHAL_CHUNK, OUTPUT_MARSHAL_CHUNK..OUTPUT_MARSHAL_CHUNK * 2, OUTPUT_MARSHAL_CHUNK * 2..len]
+        );
+        assert_eq!(InferenceEngine::output_chunks(0).count(), 0);
+        assert_eq!(InferenceEngine::output_chunks(1000).collect::<Vec<_>>(), vec![0..1000]);
+
+        assert_eq!(InferenceEngine::java_array_len(1000).unwrap(), 1000);
+        let err = InferenceEngine::java_array_len(i32::MAX as usize + 1).unwrap_err();
+        assert!(err.to_string().contains("failed to marshal output to Java: len=2147483648"));
+    }
+
+    #[test]
+    fn test_select_image_input_skips_non_image_first_input() {
+        let scale: &[i64] = &[1];
+        let image: &[i64] = &[1, 3, -1, -1];
+        let inputs = [("scale", Some(scale)), ("image", Some(image))];
+
+        assert_eq!(InferenceEngine::select_image_input(&inputs, None).unwrap(), 1);
+        assert_eq!(InferenceEngine::select_image_input(&inputs, Some("scale")).unwrap(), 0);
+        assert!(InferenceEngine::select_image_input(&inputs, Some("pixels")).is_err());
+        // No rank-4 input: fall back to the first one
+        assert_eq!(InferenceEngine::select_image_input(&inputs[..1], None).unwrap(), 0);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_extra_required_input_fails_clearly() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        // First input is a scalar gain, the image comes second
+        let model = onnx::ModelBuilder::new()
+            .input("gain", &[onnx::Dim::Fixed(1)])
+            .input("image", &[onnx::Dim::Fixed(1), onnx::Dim::Fixed(3), onnx::Dim::Dynamic("h"), onnx::Dim::Dynamic("w")])
+            .output("scaled", &[onnx::Dim::Fixed(1), onnx::Dim::Fixed(3), onnx::Dim::Dynamic("h"), onnx::Dim::Dynamic("w")])
+            .node("Mul", &["image", "gain"], &["scaled"], &[])
+            .build();
+        let model_path = write_temp_file("two_inputs.onnx", &model);
+        InferenceEngine::load_model(&model_path).unwrap();
+
+        let err = InferenceEngine::run_inference(&encode_png(8, 8, [1, 2, 3])).unwrap_err();
+        assert!(err.to_string().contains("requires inputs [\"gain\"] besides image input 'image'"));
+    }
+
+    #[test]
+    fn test_check_input_shape() {
+        // Dynamic spatial dims accept a non-square tensor
+        assert!(InferenceEngine::check_input_shape(&[1, 3, -1, -1], &[1, 3, 100, 200]).is_ok());
+        // Fixed dims reject it
+        let err = InferenceEngine::check_input_shape(&[1, 3, 224, 224], &[1, 3, 100, 200]).unwrap_err();
+        assert!(matches!(err, InferenceError::ShapeMismatch(_)));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,201 @@
+/// Polling-based background inference for callers that can't register a JNI callback
+use crate::inference::InferenceEngine;
+use crate::listener::ResultListener;
+use crate::types::InferenceResult as InferenceOutput;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+/// State of a submitted inference job
+#[derive(Debug, Clone)]
+pub enum JobState {
+    Pending,
+    /// Boxed so pending/failed entries in the job table stay small
+    Done(Box<InferenceOutput>),
+    Failed(String),
+}
+
+/// A queued job: its id, the encoded image to run and the caller's request id to echo back.
+/// Jobs outlive the JNI call that started them, so only owned Rust data is queued or stored, never
+/// a JNI reference; the only global ref on this path is the result listener's.
+type JobRequest = (u32, Vec<u8>, Option<i64>);
+
+/// Static storage for job states by id (created on first use)
+static JOBS: Mutex<Option<HashMap<u32, JobState>>> = Mutex::new(None);
+
+/// Channel to the worker thread that runs queued jobs in submission order
+static WORKER: Mutex<Option<Sender<JobRequest>>> = Mutex::new(None);
+
+/// Next job id; ids start at 1 so 0 can signal "not started" on the Java side
+static NEXT_JOB_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Background job table
+pub struct JobQueue;
+
+impl JobQueue {
+    /// Queue inference on `image_bytes` and return the job id to poll.
+    /// A `request_id` is attached to the job's result for the caller's own bookkeeping.
+    pub fn start(image_bytes: Vec<u8>, request_id: Option<i64>) -> u32 {
+        let job_id = NEXT_JOB_ID.fetch_add(1, Ordering::SeqCst);
+        Self::set_state(job_id, JobState::Pending);
+
+        let mut worker = WORKER.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let sender = worker.get_or_insert_with(Self::spawn_worker);
+        if sender.send((job_id, image_bytes, request_id)).is_err() {
+            *worker = None;
+            Self::set_state(job_id, JobState::Failed("Inference worker is not running".to_string()));
+        }
+
+        job_id
+    }
+
+    /// Get the current state of a job (`None` if unknown or already released)
+    pub fn poll(job_id: u32) -> Option<JobState> {
+        JOBS.lock().ok()?.as_ref()?.get(&job_id).cloned()
+    }
+
+    /// Forget a job and free its stored result; returns false if the id is unknown
+    pub fn release(job_id: u32) -> bool {
+        JOBS.lock()
+            .ok()
+            .and_then(|mut jobs| jobs.as_mut()?.remove(&job_id))
+            .is_some()
+    }
+
+    fn set_state(job_id: u32, state: JobState) {
+        if let Ok(mut jobs) = JOBS.lock() {
+            jobs.get_or_insert_with(HashMap::new).insert(job_id, state);
+        }
+    }
+
+    /// Start the worker thread; it exits when the sender is dropped
+    fn spawn_worker() -> Sender<JobRequest> {
+        let (sender, receiver) = mpsc::channel::<JobRequest>();
+        thread::spawn(move || {
+            for (job_id, image_bytes, request_id) in receiver {
+                // Skip jobs released before they started
+                if !matches!(Self::poll(job_id), Some(JobState::Pending)) {
+                    continue;
+                }
+                // Pure, so a background job never replaces the last result of the caller's own runs
+                let result = InferenceEngine::run_inference_pure(&image_bytes).map(|result| match request_id {
+                    Some(id) => result.with_request_id(id),
+                    None => result,
+                });
+                let state = match &result {
+                    Ok(result) => JobState::Done(Box::new(result.clone())),
+                    Err(e) => JobState::Failed(e.to_string()),
+                };
+                // Don't resurrect (or report) a job released while it was running
+                let stored = match JOBS.lock() {
+                    Ok(mut jobs) => match jobs.as_mut().and_then(|jobs| jobs.get_mut(&job_id)) {
+                        Some(slot) => {
+                            *slot = state;
+                            true
+                        }
+                        None => false,
+                    },
+                    Err(_) => false,
+                };
+                if stored && let Ok(result) = result {
+                    ResultListener::notify(&result);
+                }
+            }
+        });
+        sender
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::InferenceEngine;
+    use crate::test_utils::{encode_png, lock_global_state, onnx, require_ort, write_temp_file};
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_job_runs_to_completion_and_releases() {
+        let _guard = lock_global_state();
+        let job_id = JobQueue::start(encode_png(16, 16, [9, 9, 9]), None);
+        let state = wait_for(job_id);
+
+        // Without a loaded model the job fails, but it must still leave the pending state
+        assert!(matches!(state, Some(JobState::Done(_)) | Some(JobState::Failed(_))));
+        assert!(JobQueue::release(job_id));
+        assert!(JobQueue::poll(job_id).is_none());
+        assert!(!JobQueue::release(job_id));
+    }
+
+    #[test]
+    fn test_finished_and_cancelled_jobs_leave_no_state_behind() {
+        let _guard = lock_global_state();
+        // No model, so jobs fail fast and the hook is never mid-call when it's unregistered
+        InferenceEngine::clear_cache();
+        // Stands in for the listener's global ref: the hook owns a clone for as long as it's registered
+        let listener_ref = Arc::new(());
+        let hook_ref = Arc::clone(&listener_ref);
+        ResultListener::register(Arc::new(move |_: &InferenceOutput| drop(Arc::clone(&hook_ref))));
+
+        let finished = JobQueue::start(encode_png(16, 16, [9, 9, 9]), None);
+        wait_for(finished);
+        assert!(JobQueue::release(finished));
+        let cancelled = JobQueue::start(encode_png(16, 16, [9, 9, 9]), None);
+        assert!(JobQueue::release(cancelled));
+        // Jobs run in order, so this one finishing means the cancelled one was skipped
+        let last = JobQueue::start(encode_png(16, 16, [9, 9, 9]), None);
+        wait_for(last);
+        JobQueue::release(last);
+
+        assert!(JOBS.lock().unwrap().as_ref().is_none_or(HashMap::is_empty));
+        assert!(ResultListener::unregister());
+        assert_eq!(Arc::strong_count(&listener_ref), 1);
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_request_id_round_trips() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        let model_path = write_temp_file("job_request_id.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+        let notified = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&notified);
+        ResultListener::register(Arc::new(move |result: &InferenceOutput| {
+            assert_eq!(result.request_id, Some(42));
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        let job_id = JobQueue::start(encode_png(16, 16, [9, 9, 9]), Some(42));
+        let Some(JobState::Done(result)) = wait_for(job_id) else {
+            panic!("job did not complete");
+        };
+        JobQueue::release(job_id);
+        // The listener runs on the worker right after the state is stored
+        let started = Instant::now();
+        while notified.load(Ordering::SeqCst) == 0 && started.elapsed() < Duration::from_secs(10) {
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert!(ResultListener::unregister());
+
+        assert_eq!(result.request_id, Some(42));
+        assert_eq!(notified.load(Ordering::SeqCst), 1);
+    }
+
+    /// Poll until the job leaves the pending state (or ten seconds pass)
+    fn wait_for(job_id: u32) -> Option<JobState> {
+        let started = Instant::now();
+        loop {
+            match JobQueue::poll(job_id) {
+                Some(JobState::Pending) if started.elapsed() < Duration::from_secs(10) => {
+                    thread::sleep(Duration::from_millis(5))
+                }
+                state => return state,
+            }
+        }
+    }
+}
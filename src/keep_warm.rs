@@ -0,0 +1,95 @@
+/// Background keep-alive that periodically runs the active model so ORT keeps its buffers hot
+use crate::inference::InferenceEngine;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The running keep-alive thread and the channel that stops it
+struct KeepWarmThread {
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+/// Static storage for the keep-alive thread, if one is running
+static KEEP_WARM: Mutex<Option<KeepWarmThread>> = Mutex::new(None);
+
+/// Keep-alive threads currently alive (lets tests check that stopping joins the thread)
+#[cfg(test)]
+static LIVE_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Periodic warmup runs on a background thread
+pub struct KeepWarm;
+
+impl KeepWarm {
+    /// Run a warmup inference every `interval`, replacing any running keep-alive.
+    /// Warmup runs never touch the stored last result or input.
+    pub fn start(interval: Duration) {
+        let mut keep_warm = KEEP_WARM.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(running) = keep_warm.take() {
+            Self::join(running);
+        }
+
+        let (stop, stop_signal) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || {
+            #[cfg(test)]
+            LIVE_THREADS.fetch_add(1, Ordering::SeqCst);
+
+            // Wake up every interval until stopped (or the sender is dropped)
+            while let Err(RecvTimeoutError::Timeout) = stop_signal.recv_timeout(interval) {
+                // Nothing to keep warm without a model; a failed run is retried next interval
+                let _ = InferenceEngine::warm_up();
+            }
+
+            #[cfg(test)]
+            LIVE_THREADS.fetch_sub(1, Ordering::SeqCst);
+        });
+        *keep_warm = Some(KeepWarmThread { stop, handle });
+    }
+
+    /// Stop the keep-alive and wait for its thread to exit; returns false if none was running
+    pub fn stop() -> bool {
+        let running = KEEP_WARM.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        match running {
+            Some(running) => {
+                Self::join(running);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn join(running: KeepWarmThread) {
+        // A send error only means the thread already exited
+        let _ = running.stop.send(());
+        let _ = running.handle.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::lock_global_state;
+
+    #[test]
+    fn test_enable_disable_joins_thread() {
+        let _guard = lock_global_state();
+
+        KeepWarm::start(Duration::from_millis(1));
+        KeepWarm::start(Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(LIVE_THREADS.load(Ordering::SeqCst), 1);
+
+        assert!(KeepWarm::stop());
+        assert_eq!(LIVE_THREADS.load(Ordering::SeqCst), 0);
+        assert!(!KeepWarm::stop());
+
+        // Clearing the model cache also stops the keep-alive
+        KeepWarm::start(Duration::from_secs(60));
+        InferenceEngine::clear_cache();
+        assert_eq!(LIVE_THREADS.load(Ordering::SeqCst), 0);
+        assert!(!KeepWarm::stop());
+    }
+}
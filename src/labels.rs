@@ -1,77 +1,347 @@
 /// ImageNet labels management and storage
-use crate::constants::FALLBACK_LABELS;
+use crate::constants::{FALLBACK_LABELS, MIN_CLASSIFICATION_CLASSES};
 use crate::errors::{InferenceError, InferenceResult};
+use std::collections::HashMap;
+use std::io::BufRead;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
 /// Static storage for ImageNet labels
 static IMAGENET_LABELS: Mutex<Option<Vec<String>>> = Mutex::new(None);
 
+/// Name to class index lookup over the current labels, built on first use and dropped whenever
+/// the labels (or the fallback labels' shape) change
+static LABEL_INDEX: Mutex<Option<LabelIndex>> = Mutex::new(None);
+
+/// Static storage for the last labels/model class count mismatch warning
+static LABEL_WARNING: Mutex<Option<String>> = Mutex::new(None);
+
+/// Output class count of the active model when it is static, which loaded labels must match to be used
+static MODEL_CLASS_COUNT: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Bumped whenever the loaded labels change, so caches of label-bearing results can tell
+static LABELS_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Strict mode: never fabricate `class_N` labels for indices without a loaded label
+static STRICT_LABELS: AtomicBool = AtomicBool::new(false);
+
+/// Class count fallback labels are padded to and classifier outputs must reach
+static EXPECTED_CLASS_COUNT: AtomicUsize = AtomicUsize::new(MIN_CLASSIFICATION_CLASSES);
+
+/// How class indices of the active model are turned into label strings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelResolution {
+    /// No active model, or its output class count isn't static
+    Unknown { label_count: Option<usize> },
+    /// No labels loaded: the built-in fallback labels are used
+    Fallback { model_classes: usize },
+    /// Loaded labels match the model's classes one-to-one and are used directly
+    Matched { classes: usize },
+    /// Counts differ: loaded labels are not used, and every class gets `class_N`
+    /// (empty in strict mode)
+    Mismatch { label_count: usize, model_classes: usize },
+}
+
+impl LabelResolution {
+    /// Short status name used in JSON
+    pub fn status(&self) -> &'static str {
+        match self {
+            LabelResolution::Unknown { .. } => "unknown",
+            LabelResolution::Fallback { .. } => "fallback",
+            LabelResolution::Matched { .. } => "matched",
+            LabelResolution::Mismatch { .. } => "mismatch",
+        }
+    }
+
+    /// Serialize as `{"status":..,"model_classes":..,"label_count":..}` (null when not known)
+    pub fn to_json(&self) -> String {
+        let (model_classes, label_count) = match *self {
+            LabelResolution::Unknown { label_count } => (None, label_count),
+            LabelResolution::Fallback { model_classes } => (Some(model_classes), None),
+            LabelResolution::Matched { classes } => (Some(classes), Some(classes)),
+            LabelResolution::Mismatch { label_count, model_classes } => (Some(model_classes), Some(label_count)),
+        };
+        let number = |value: Option<usize>| value.map_or_else(|| "null".to_string(), |value| value.to_string());
+        format!(
+            "{{\"status\":\"{}\",\"model_classes\":{},\"label_count\":{}}}",
+            self.status(),
+            number(model_classes),
+            number(label_count)
+        )
+    }
+}
+
+/// First class index of each label, by exact and by lowercased name
+struct LabelIndex {
+    exact: HashMap<String, usize>,
+    folded: HashMap<String, usize>,
+}
+
+impl LabelIndex {
+    fn build(labels: &[String]) -> Self {
+        let mut index = Self {
+            exact: HashMap::with_capacity(labels.len()),
+            folded: HashMap::with_capacity(labels.len()),
+        };
+        for (class_id, label) in labels.iter().enumerate() {
+            index.exact.entry(label.clone()).or_insert(class_id);
+            index.folded.entry(label.to_lowercase()).or_insert(class_id);
+        }
+        index
+    }
+}
+
 /// Labels manager for ImageNet classification
 pub struct LabelsManager;
 
 impl LabelsManager {
-    /// Get ImageNet labels, falling back to hardcoded labels if not loaded
+    /// Get ImageNet labels, falling back to hardcoded labels if not loaded (empty in strict mode).
+    /// Loaded labels whose count differs from the active model's classes are replaced by `class_N`.
     pub fn get_labels() -> Vec<String> {
+        Self::labels_for(Self::model_class_count())
+    }
+
+    /// Labels as `get_labels` resolves them, but for a model with `model_classes` output classes
+    /// rather than the active one (`None` when its class count isn't static)
+    pub fn labels_for(model_classes: Option<usize>) -> Vec<String> {
         // Try to get labels from static storage first
-        if let Ok(labels_guard) = IMAGENET_LABELS.lock() {
-            if let Some(ref labels) = *labels_guard {
-                return labels.clone();
+        if let Ok(labels_guard) = IMAGENET_LABELS.lock()
+            && let Some(ref labels) = *labels_guard
+        {
+            match model_classes {
+                Some(classes) if classes != labels.len() => {
+                    if Self::is_strict() {
+                        return Vec::new();
+                    }
+                    return (0..classes).map(|i| format!("class_{}", i)).collect();
+                }
+                _ => return labels.clone(),
             }
         }
+
+        if Self::is_strict() {
+            return Vec::new();
+        }
         
         // Fallback to hardcoded labels with generated classes for missing ones
         let mut labels = FALLBACK_LABELS.iter().map(|&s| s.to_string()).collect::<Vec<_>>();
         
-        // Generate remaining classes up to 1000
-        for i in labels.len()..1000 {
+        // Generate remaining classes up to the expected class count
+        let expected = Self::expected_class_count();
+        labels.truncate(expected);
+        for i in labels.len()..expected {
             labels.push(format!("class_{}", i));
         }
         
         labels
     }
 
-    /// Load labels from file content
-    pub fn load_labels_from_content(content: &str) -> InferenceResult<usize> {
-        let labels: Vec<String> = content
-            .lines()
-            .filter(|line| !line.trim().is_empty())
-            .map(|line| line.trim().to_string())
-            .collect();
+    /// Split labels file content into trimmed, non-empty labels. A leading UTF-8 BOM is dropped,
+    /// LF and CRLF line endings both work, and lines starting with `#` are comments.
+    fn parse_labels(content: &str) -> InferenceResult<Vec<String>> {
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let labels: Vec<String> = content.lines().filter_map(Self::label_line).map(str::to_string).collect();
+        Self::check_not_empty(labels)
+    }
 
+    /// The label on one line of a labels file (`None` for blank and comment lines)
+    fn label_line(line: &str) -> Option<&str> {
+        let line = line.trim();
+        (!line.is_empty() && !line.starts_with('#')).then_some(line)
+    }
+
+    fn check_not_empty(labels: Vec<String>) -> InferenceResult<Vec<String>> {
         if labels.is_empty() {
             return Err(InferenceError::labels_loading_failed("Labels file is empty"));
         }
+        Ok(labels)
+    }
 
-        let count = labels.len();
+    /// Decode raw labels bytes, which must be UTF-8
+    fn decode_labels(bytes: &[u8]) -> InferenceResult<&str> {
+        std::str::from_utf8(bytes).map_err(|e| {
+            InferenceError::labels_loading_failed(format!("Labels are not valid UTF-8 (at byte {})", e.valid_up_to()))
+        })
+    }
 
-        // Store labels in static variable
+    /// Replace the stored labels, returning how many there are
+    fn store_labels(labels: Vec<String>) -> InferenceResult<usize> {
+        let count = labels.len();
         match IMAGENET_LABELS.lock() {
             Ok(mut labels_guard) => {
                 *labels_guard = Some(labels);
+                LABELS_GENERATION.fetch_add(1, Ordering::SeqCst);
+                // The index lock is taken after the labels lock is released, never while holding it
+                drop(labels_guard);
+                Self::invalidate_index();
                 Ok(count)
             }
             Err(_) => Err(InferenceError::labels_loading_failed("Failed to acquire labels mutex")),
         }
     }
 
-    /// Load labels from file path
+    /// Load labels from file content
+    pub fn load_labels_from_content(content: &str) -> InferenceResult<usize> {
+        Self::store_labels(Self::parse_labels(content)?)
+    }
+
+    /// Load labels from raw UTF-8 bytes (e.g. read from APK assets)
+    pub fn load_labels_from_bytes(bytes: &[u8]) -> InferenceResult<usize> {
+        Self::load_labels_from_content(Self::decode_labels(bytes)?)
+    }
+
+    /// Parse and validate new labels in full before swapping them in, so a bad update
+    /// leaves the current labels untouched. With `expected_count`, the new labels must
+    /// have exactly that many entries.
+    pub fn replace_labels(bytes: &[u8], expected_count: Option<usize>) -> InferenceResult<usize> {
+        let labels = Self::parse_labels(Self::decode_labels(bytes)?)?;
+        if let Some(expected) = expected_count
+            && labels.len() != expected
+        {
+            return Err(InferenceError::labels_loading_failed(format!(
+                "Expected {} labels but got {}",
+                expected,
+                labels.len()
+            )));
+        }
+        Self::store_labels(labels)
+    }
+
+    /// Load labels from file path, streaming it line by line so a large taxonomy is never held in
+    /// memory as one string next to its labels. Lines are handled as in `load_labels_from_content`.
     pub fn load_labels_from_file(path: &str) -> InferenceResult<usize> {
-        let content = std::fs::read_to_string(path)
-            .map_err(|e| InferenceError::labels_loading_failed(format!("Failed to read file '{}': {}", path, e)))?;
-        
-        Self::load_labels_from_content(&content)
+        let read_error = |e: std::io::Error| InferenceError::labels_loading_failed(format!("Failed to read file '{}': {}", path, e));
+        let mut reader = std::io::BufReader::new(std::fs::File::open(path).map_err(read_error)?);
+
+        let mut labels = Vec::new();
+        let mut line = String::new();
+        let mut first_line = true;
+        while reader.read_line(&mut line).map_err(read_error)? > 0 {
+            let text = if first_line { line.strip_prefix('\u{feff}').unwrap_or(&line) } else { &line };
+            if let Some(label) = Self::label_line(text) {
+                labels.push(label.to_string());
+            }
+            line.clear();
+            first_line = false;
+        }
+        labels.shrink_to_fit();
+
+        Self::store_labels(Self::check_not_empty(labels)?)
     }
 
-    /// Get label for specific class index
+    /// Get label for specific class index (empty in strict mode when no label was loaded for it)
     pub fn get_label(index: usize) -> String {
-        let labels = Self::get_labels();
+        Self::label_for(index, Self::model_class_count())
+    }
+
+    /// Label of a class index for a model with `model_classes` output classes (see `labels_for`)
+    pub fn label_for(index: usize, model_classes: Option<usize>) -> String {
+        let labels = Self::labels_for(model_classes);
         if index < labels.len() {
             labels[index].clone()
+        } else if Self::is_strict() {
+            String::new()
         } else {
             format!("class_{}", index)
         }
     }
 
+    /// Class index of the label `name` (`None` if no label matches). An exact match wins; otherwise
+    /// the name is compared case-insensitively. With duplicate labels the lowest index is returned.
+    pub fn index_of(name: &str) -> Option<usize> {
+        let mut index = LABEL_INDEX.lock().ok()?;
+        let index = index.get_or_insert_with(|| LabelIndex::build(&Self::get_labels()));
+        index
+            .exact
+            .get(name)
+            .or_else(|| index.folded.get(&name.to_lowercase()))
+            .copied()
+    }
+
+    /// Drop the name lookup so the next `index_of` rebuilds it from the current labels
+    fn invalidate_index() {
+        if let Ok(mut index) = LABEL_INDEX.lock() {
+            *index = None;
+        }
+    }
+
+    /// Counter that changes every time labels are loaded, replaced or cleared
+    pub fn generation() -> u64 {
+        LABELS_GENERATION.load(Ordering::SeqCst)
+    }
+
+    /// Enable or disable strict labels (lenient `class_N` padding is the default)
+    pub fn set_strict(strict: bool) {
+        STRICT_LABELS.store(strict, Ordering::SeqCst);
+        Self::invalidate_index();
+    }
+
+    /// Check whether strict labels mode is enabled
+    pub fn is_strict() -> bool {
+        STRICT_LABELS.load(Ordering::SeqCst)
+    }
+
+    /// Set the class count fallback labels are padded to and outputs are classified at
+    pub fn set_expected_class_count(count: usize) {
+        EXPECTED_CLASS_COUNT.store(count, Ordering::SeqCst);
+        Self::invalidate_index();
+    }
+
+    /// Get the expected class count (`MIN_CLASSIFICATION_CLASSES` unless configured)
+    pub fn expected_class_count() -> usize {
+        EXPECTED_CLASS_COUNT.load(Ordering::SeqCst)
+    }
+
+    /// Record the active model's output class count (`None` when no model is active or it isn't static)
+    pub fn set_model_class_count(count: Option<usize>) {
+        if let Ok(mut model_classes) = MODEL_CLASS_COUNT.lock() {
+            *model_classes = count;
+        }
+        Self::invalidate_index();
+    }
+
+    /// The active model's output class count, as last recorded with `set_model_class_count`
+    pub fn model_class_count() -> Option<usize> {
+        *MODEL_CLASS_COUNT.lock().ok()?
+    }
+
+    /// Get the number of labels loaded from file/content (None when using fallback labels)
+    pub fn loaded_label_count() -> Option<usize> {
+        IMAGENET_LABELS.lock().ok()?.as_ref().map(|labels| labels.len())
+    }
+
+    /// Compare loaded labels against the model's class count, storing a warning on mismatch.
+    /// This never fails: off-by-one label files (e.g. with a background class) are sometimes intentional.
+    pub fn validate_class_count(num_classes: usize) -> Option<String> {
+        let warning = match Self::loaded_label_count() {
+            Some(count) if count != num_classes => Some(format!(
+                "Label count mismatch: {} labels loaded but model outputs {} classes",
+                count, num_classes
+            )),
+            _ => None,
+        };
+
+        if let Ok(mut last_warning) = LABEL_WARNING.lock() {
+            *last_warning = warning.clone();
+        }
+
+        warning
+    }
+
+    /// Decide how labels resolve for a model with `model_classes` output classes
+    pub fn resolve(model_classes: Option<usize>) -> LabelResolution {
+        match (model_classes, Self::loaded_label_count()) {
+            (None, label_count) => LabelResolution::Unknown { label_count },
+            (Some(model_classes), None) => LabelResolution::Fallback { model_classes },
+            (Some(classes), Some(label_count)) if label_count == classes => LabelResolution::Matched { classes },
+            (Some(model_classes), Some(label_count)) => LabelResolution::Mismatch { label_count, model_classes },
+        }
+    }
+
+    /// Get the last labels/model class count mismatch warning
+    pub fn get_label_warning() -> Option<String> {
+        LABEL_WARNING.lock().ok()?.as_ref().cloned()
+    }
 
     /// Clear loaded labels (mainly for testing)
     #[cfg(test)]
@@ -79,15 +349,19 @@ impl LabelsManager {
         if let Ok(mut labels_guard) = IMAGENET_LABELS.lock() {
             *labels_guard = None;
         }
+        LABELS_GENERATION.fetch_add(1, Ordering::SeqCst);
+        Self::invalidate_index();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{lock_global_state, write_temp_file};
 
     #[test]
     fn test_fallback_labels() {
+        let _guard = lock_global_state();
         LabelsManager::clear_labels();
         let labels = LabelsManager::get_labels();
         assert_eq!(labels.len(), 1000);
@@ -96,8 +370,22 @@ mod tests {
         assert_eq!(labels[999], "class_999");
     }
 
+    #[test]
+    fn test_fallback_padding_stops_at_expected_count() {
+        let _guard = lock_global_state();
+        LabelsManager::clear_labels();
+        LabelsManager::set_expected_class_count(21);
+        let labels = LabelsManager::get_labels();
+        LabelsManager::set_expected_class_count(MIN_CLASSIFICATION_CLASSES);
+
+        assert_eq!(labels.len(), 21);
+        assert_eq!(labels[14], "indigo bunting");
+        assert_eq!(labels[20], "class_20");
+    }
+
     #[test]
     fn test_load_labels_from_content() {
+        let _guard = lock_global_state();
         let content = "dog\ncat\nbird\n";
         let result = LabelsManager::load_labels_from_content(content);
         assert!(result.is_ok());
@@ -109,10 +397,152 @@ mod tests {
         assert_eq!(labels[2], "bird");
     }
 
+    #[test]
+    fn test_bom_crlf_and_comments() {
+        let _guard = lock_global_state();
+        LabelsManager::load_labels_from_bytes(b"\xef\xbb\xbftench\r\ngoldfish\r\n").unwrap();
+        assert_eq!(LabelsManager::get_labels(), vec!["tench", "goldfish"]);
+        assert_eq!(LabelsManager::index_of("tench"), Some(0));
+
+        LabelsManager::load_labels_from_content("# ImageNet subset\ntench\n  # retired: carp\ngoldfish\n").unwrap();
+        assert_eq!(LabelsManager::get_labels(), vec!["tench", "goldfish"]);
+    }
+
+    #[test]
+    fn test_large_label_file_streams() {
+        let _guard = lock_global_state();
+        let mut content = String::from("\u{feff}# product taxonomy\r\n");
+        for i in 0..120_000 {
+            content.push_str(&format!("sku_{}\r\n", i));
+        }
+        let path = write_temp_file("labels_large.txt", content.as_bytes());
+
+        assert_eq!(LabelsManager::load_labels_from_file(&path).unwrap(), 120_000);
+        assert_eq!(LabelsManager::get_label(0), "sku_0");
+        assert_eq!(LabelsManager::get_label(119_999), "sku_119999");
+        assert_eq!(LabelsManager::index_of("sku_65536"), Some(65_536));
+
+        assert!(LabelsManager::load_labels_from_file(&write_temp_file("labels_bad.txt", b"ok\n\xff\n")).is_err());
+        assert!(LabelsManager::load_labels_from_file("/missing/labels.txt").is_err());
+    }
+
+    #[test]
+    fn test_index_of_label_name() {
+        let _guard = lock_global_state();
+        LabelsManager::load_labels_from_content("dog\nCat\ncat\nbird\n").unwrap();
+        assert_eq!(LabelsManager::index_of("bird"), Some(3));
+        assert_eq!(LabelsManager::index_of("cat"), Some(2));
+        assert_eq!(LabelsManager::index_of("DOG"), Some(0));
+        assert_eq!(LabelsManager::index_of("hedgehog"), None);
+
+        LabelsManager::load_labels_from_content("hedgehog\n").unwrap();
+        assert_eq!(LabelsManager::index_of("hedgehog"), Some(0));
+    }
+
+    #[test]
+    fn test_load_labels_from_bytes() {
+        let _guard = lock_global_state();
+        assert_eq!(LabelsManager::load_labels_from_bytes("dog\ncat\nhérisson\n".as_bytes()).unwrap(), 3);
+        assert_eq!(LabelsManager::get_label(2), "hérisson");
+
+        let err = LabelsManager::load_labels_from_bytes(b"dog\n\xff\xfe\n").unwrap_err();
+        assert!(err.to_string().contains("not valid UTF-8 (at byte 4)"));
+    }
+
+    #[test]
+    fn test_failed_replace_keeps_previous_labels() {
+        let _guard = lock_global_state();
+        LabelsManager::load_labels_from_content("dog\ncat\nbird\n").unwrap();
+
+        assert!(LabelsManager::replace_labels(b"\n \n", None).is_err());
+        assert!(LabelsManager::replace_labels(b"fox\n\xff\n", None).is_err());
+        let err = LabelsManager::replace_labels(b"fox\nowl\n", Some(3)).unwrap_err();
+        assert!(err.to_string().contains("Expected 3 labels but got 2"));
+        assert_eq!(LabelsManager::get_labels(), vec!["dog", "cat", "bird"]);
+
+        assert_eq!(LabelsManager::replace_labels(b"fox\nowl\nbat\n", Some(3)).unwrap(), 3);
+        assert_eq!(LabelsManager::get_label(0), "fox");
+    }
+
     #[test]
     fn test_empty_content() {
+        let _guard = lock_global_state();
         let content = "\n\n\n";
         let result = LabelsManager::load_labels_from_content(content);
         assert!(result.is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_label_count_mismatch_warning() {
+        let _guard = lock_global_state();
+        LabelsManager::load_labels_from_content("dog\ncat\nbird\n").unwrap();
+
+        let warning = LabelsManager::validate_class_count(4);
+        assert!(warning.is_some());
+        assert!(warning.unwrap().contains("3 labels loaded but model outputs 4 classes"));
+        assert!(LabelsManager::get_label_warning().is_some());
+
+        // Matching counts clear the previous warning
+        assert!(LabelsManager::validate_class_count(3).is_none());
+        assert!(LabelsManager::get_label_warning().is_none());
+
+        // Fallback labels never warn
+        LabelsManager::clear_labels();
+        assert!(LabelsManager::validate_class_count(1001).is_none());
+    }
+
+    #[test]
+    fn test_label_resolution() {
+        let _guard = lock_global_state();
+        LabelsManager::load_labels_from_content("dog\ncat\nbird\n").unwrap();
+
+        assert_eq!(LabelsManager::resolve(Some(3)), LabelResolution::Matched { classes: 3 });
+        let mismatch = LabelsManager::resolve(Some(4));
+        assert_eq!(mismatch, LabelResolution::Mismatch { label_count: 3, model_classes: 4 });
+        assert_eq!(mismatch.to_json(), r#"{"status":"mismatch","model_classes":4,"label_count":3}"#);
+        assert_eq!(LabelsManager::resolve(None), LabelResolution::Unknown { label_count: Some(3) });
+
+        LabelsManager::clear_labels();
+        assert_eq!(LabelsManager::resolve(Some(1000)), LabelResolution::Fallback { model_classes: 1000 });
+    }
+
+    #[test]
+    fn test_mismatched_labels_are_not_used() {
+        let _guard = lock_global_state();
+        LabelsManager::load_labels_from_content("dog\ncat\nbird\n").unwrap();
+
+        LabelsManager::set_model_class_count(Some(3));
+        assert_eq!(LabelsManager::get_label(1), "cat");
+        assert_eq!(LabelsManager::index_of("bird"), Some(2));
+
+        LabelsManager::set_model_class_count(Some(4));
+        assert_eq!(LabelsManager::get_labels(), vec!["class_0", "class_1", "class_2", "class_3"]);
+        assert_eq!(LabelsManager::get_label(1), "class_1");
+        assert_eq!(LabelsManager::index_of("bird"), None);
+        LabelsManager::set_strict(true);
+        assert!(LabelsManager::get_labels().is_empty());
+        assert_eq!(LabelsManager::get_label(1), "");
+        LabelsManager::set_strict(false);
+
+        LabelsManager::set_model_class_count(None);
+        assert_eq!(LabelsManager::get_label(1), "cat");
+        LabelsManager::clear_labels();
+    }
+
+    #[test]
+    fn test_strict_mode_yields_no_synthetic_labels() {
+        let _guard = lock_global_state();
+        LabelsManager::set_strict(true);
+
+        LabelsManager::clear_labels();
+        assert!(LabelsManager::get_labels().is_empty());
+        assert_eq!(LabelsManager::get_label(0), "");
+
+        LabelsManager::load_labels_from_content("dog\ncat\n").unwrap();
+        assert_eq!(LabelsManager::get_label(1), "cat");
+        assert_eq!(LabelsManager::get_label(2), "");
+
+        LabelsManager::set_strict(false);
+        assert_eq!(LabelsManager::get_label(2), "class_2");
+    }
+}
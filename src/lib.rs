@@ -1,34 +1,89 @@
 use std::ptr;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use jni::JNIEnv;
-use jni::objects::{JClass, JString, JByteArray};
-use jni::sys::{jfloatArray, jstring, jint, jintArray};
+use jni::objects::{JClass, JString, JByteArray, JFloatArray, JIntArray, JObject, JObjectArray};
+use jni::sys::{jboolean, jfloat, jfloatArray, jstring, jint, jintArray, jlong, jobjectArray};
 use ort::session::Session;
 
 // Import our modules
+mod arena;
+mod base64;
+mod config;
+mod config_json;
 mod constants;
 mod errors;
+mod evaluation;
 mod inference;
+mod jobs;
+mod json;
+mod keep_warm;
 mod labels;
+mod listener;
+mod model_proto;
+mod pipeline;
+mod postprocess;
+mod preload;
+mod preprocess;
+mod provider_probe;
+mod runtime_log;
+mod trace;
+mod tta;
 mod types;
 mod error_helper;
+#[cfg(feature = "flatbuffers")]
+mod flatbuffer;
+#[cfg(test)]
+mod test_utils;
 
 // Re-export types for external use
-use crate::inference::InferenceEngine;
-use crate::labels::LabelsManager;
-use crate::types::InferenceResult;
+use crate::config::{ArenaConfig, ArenaExtendStrategy, Calibration, ClassGroups, ClassificationHead, ConfidenceFormat, ConfigManager, DataOrder, ExecutionProviderKind, Normalization, NormalizationPreset, OutputCap, QuantParams, ResizeMode, ReturnedSubset};
+use crate::config_json::ConfigJson;
+use crate::constants::{MIN_CLASSIFICATION_CLASSES, RESULT_SCHEMA_VERSION, TOP_K_PREDICTIONS};
+pub use crate::errors::InferenceError;
+use crate::evaluation::Evaluator;
+pub use crate::inference::InferenceEngine;
+use crate::jobs::{JobQueue, JobState};
+use crate::listener::ResultListener;
+use crate::keep_warm::KeepWarm;
+pub use crate::labels::{LabelResolution, LabelsManager};
+use crate::pipeline::Pipeline;
+use crate::postprocess::Postprocessor;
+use crate::preload::Preloader;
+use crate::preprocess::{ImagePreprocessor, PixelFormat, Roi};
+pub use crate::preprocess::LetterboxInfo;
+use crate::provider_probe::ProviderProbe;
+use crate::runtime_log::RuntimeLog;
+use crate::trace::TimingTrace;
+use crate::tta::TtaMode;
+pub use crate::types::{BenchmarkStats, ClassificationResult, Detection, EvaluationReport, GroupPrediction, HeadPredictions, InferenceResult, LoadedModelInfo, ModelInfo, OutputTensor, PreloadReport, ProviderProbeReport, TensorInfo, TensorStats};
 
 
 
 
 pub fn run_inference_internal(
     image_bytes: &[u8],
+    deadline: Option<Duration>,
 ) -> Result<InferenceResult, Box<dyn std::error::Error>> {
-    match InferenceEngine::run_inference(image_bytes) {
+    let result = match deadline {
+        Some(deadline) => InferenceEngine::run_inference_with_deadline(image_bytes, Some(deadline)),
+        None => InferenceEngine::run_inference(image_bytes),
+    };
+    store_inference_error(result)
+}
+
+// Wrap serialized JSON objects as {"schema_version":N,"<key>":[...]}
+fn versioned_json(key: &str, schema_version: u32, items: &[String]) -> String {
+    format!("{{\"schema_version\":{},\"{}\":[{}]}}", schema_version, key, items.join(","))
+}
+
+// Store a failed inference's message for getLastError before handing it back
+fn store_inference_error<T>(result: Result<T, InferenceError>) -> Result<T, Box<dyn std::error::Error>> {
+    match result {
         Ok(result) => Ok(result),
         Err(e) => {
             let error_msg = e.to_string();
-            InferenceEngine::store_error(&error_msg);
+            InferenceEngine::store_error_detail(&error_msg, &e);
             Err(error_msg.into())
         }
     }
@@ -61,7 +116,76 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_loadModelNative(
         Ok(_) => format!("Model loaded successfully: {}", model_path_str),
         Err(e) => {
             let error_msg = format!("Failed to load model: {}", e);
-            InferenceEngine::store_error(&error_msg);
+            InferenceEngine::store_error_detail(&error_msg, &e);
+            error_msg
+        }
+    };
+
+    match env.new_string(&result) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Load a model trying execution providers in order (0 = CPU, 1 = NNAPI, 2 = XNNPACK), e.g. [1, 2, 0]:
+// each attempt is logged to getRuntimeLogNative and the first provider that registers is used, CPU if none
+// does. Applies to this load only. Returns the provider's name, or null with a stored error
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_loadModelWithProvidersNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    model_path: JString,
+    provider_ids: JIntArray,
+) -> jstring {
+    let model_path: String = match env.get_string(&model_path) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            InferenceEngine::store_error("Failed to get model path from JNI");
+            return ptr::null_mut();
+        }
+    };
+    let len = match env.get_array_length(&provider_ids) {
+        Ok(len) => len as usize,
+        Err(_) => return ptr::null_mut(),
+    };
+    let mut ids = vec![0; len];
+    if env.get_int_array_region(&provider_ids, 0, &mut ids).is_err() {
+        return ptr::null_mut();
+    }
+    let providers = match ids.iter().map(|&id| ExecutionProviderKind::from_code(id).ok_or(id)).collect() {
+        Ok(providers) => providers,
+        Err(id) => {
+            InferenceEngine::store_error(&format!("Invalid execution provider: {}", id));
+            return ptr::null_mut();
+        }
+    };
+
+    match InferenceEngine::load_model_with_providers(&model_path, providers) {
+        Ok(provider) => match env.new_string(provider) {
+            Ok(jstr) => jstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        Err(e) => {
+            InferenceEngine::store_error_detail(&format!("Failed to load model: {}", e), &e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// Load model from a byte range of an open file descriptor (e.g. AssetFileDescriptor); the fd is not closed
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_loadModelFromFdNative(
+    env: JNIEnv,
+    _class: JClass,
+    fd: jint,
+    offset: jlong,
+    length: jlong,
+) -> jstring {
+    let result = match InferenceEngine::load_model_from_fd(fd, offset, length) {
+        Ok(_) => format!("Model loaded successfully from fd {} (offset {}, length {})", fd, offset, length),
+        Err(e) => {
+            let error_msg = format!("Failed to load model: {}", e);
+            InferenceEngine::store_error_detail(&error_msg, &e);
             error_msg
         }
     };
@@ -81,6 +205,62 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_isModelLoadedNativ
     if InferenceEngine::is_model_loaded() { 1 } else { 0 }
 }
 
+// Drop every cached model (and stop the keep-alive); the next run needs loadModelNative again
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_clearCacheNative(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    InferenceEngine::clear_cache();
+}
+
+// Keep the session hot with a background warmup run every `intervalMs` (results aren't stored);
+// `on` = false stops it. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setKeepWarmNative(
+    _env: JNIEnv,
+    _class: JClass,
+    on: jboolean,
+    interval_ms: jint,
+) -> jint {
+    if on == 0 {
+        KeepWarm::stop();
+        return 1;
+    }
+    if interval_ms <= 0 {
+        InferenceEngine::store_error(&format!("Invalid keep-warm interval: {} ms", interval_ms));
+        return 0;
+    }
+
+    KeepWarm::start(Duration::from_millis(interval_ms as u64));
+    1
+}
+
+// List cached models as JSON: {"schema_version":N,"models":[{"path":...,"size_bytes":...,"active":...}]}, oldest first
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLoadedModelsJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let json_parts: Vec<String> = InferenceEngine::loaded_model_info()
+        .iter()
+        .map(|model| {
+            format!(
+                "{{\"path\":\"{}\",\"size_bytes\":{},\"active\":{}}}",
+                json::escape(&model.path),
+                model.size_bytes,
+                model.active
+            )
+        })
+        .collect();
+    let json = versioned_json("models", RESULT_SCHEMA_VERSION, &json_parts);
+
+    match env.new_string(&json) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
 // Get the path of the currently loaded model
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLoadedModelPathNative(
@@ -99,13 +279,80 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLoadedModelPath
     }
 }
 
+// Get the execution provider the active model's session registered (e.g. "NnapiExecutionProvider"),
+// "CPUExecutionProvider" when a requested provider fell back, or "" when no model is loaded
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getActiveProviderNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let provider = InferenceEngine::active_execution_provider().unwrap_or_default();
+    match env.new_string(provider) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Get the active model's inputs, outputs, opset, producer and provider as
+// {"schema_version":N,"path":..,"inputs":[{"name":..,"type":..,"shape":[..]}],"outputs":[..],"opset":..,"producer":..,"provider":..};
+// null if no model is loaded
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getModelInfoJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    match InferenceEngine::active_model_info() {
+        Some(info) => match env.new_string(info.to_json()) {
+            Ok(jstr) => jstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+// Try NNAPI on a tiny bundled model without touching the loaded models:
+// {"schema_version":N,"provider":"NNAPIExecutionProvider","available":..,"error":..|null,"elapsed_ms":..}
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_probeNnapiNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    match env.new_string(ProviderProbe::nnapi().to_json()) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// The last result with timings rounded per setTimingPrecisionNative, for the timing getters
+fn reported_last_result() -> Option<InferenceResult> {
+    let decimals = ConfigManager::postprocess_config().timing_decimals;
+    InferenceEngine::get_last_result().map(|result| result.with_timings_rounded(decimals))
+}
+
+// Share of the last result's total time spent in each stage, with the absolute values:
+// {"schema_version":N,"total_time_ms":..,"stages":{"preprocessing":{"ms":..,"percent":..},..}};
+// null if there is no result. Timings follow setTimingPrecisionNative
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTimingBreakdownJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let Some(result) = reported_last_result() else {
+        return ptr::null_mut();
+    };
+    match env.new_string(result.timing_breakdown_json()) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
 // Get inference time from last run
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getInferenceTimeNative(
     _env: JNIEnv,
     _class: JClass,
 ) -> jni::sys::jfloat {
-    if let Some(result) = InferenceEngine::get_last_result() {
+    if let Some(result) = reported_last_result() {
         result.inference_time_ms
     } else {
         0.0
@@ -118,7 +365,7 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getPreprocessingTi
     _env: JNIEnv,
     _class: JClass,
 ) -> jni::sys::jfloat {
-    if let Some(result) = InferenceEngine::get_last_result() {
+    if let Some(result) = reported_last_result() {
         result.preprocessing_time_ms
     } else {
         0.0
@@ -131,7 +378,7 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getPostprocessingT
     _env: JNIEnv,
     _class: JClass,
 ) -> jni::sys::jfloat {
-    if let Some(result) = InferenceEngine::get_last_result() {
+    if let Some(result) = reported_last_result() {
         result.postprocessing_time_ms
     } else {
         0.0
@@ -144,13 +391,49 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTotalTimeNative
     _env: JNIEnv,
     _class: JClass,
 ) -> jni::sys::jfloat {
-    if let Some(result) = InferenceEngine::get_last_result() {
+    if let Some(result) = reported_last_result() {
         result.total_time_ms
     } else {
         0.0
     }
 }
 
+// Get JNI marshalling time (byte array conversion + float array creation) from last run
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getJniTimeNative(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jni::sys::jfloat {
+    if let Some(result) = reported_last_result() {
+        result.jni_time_ms
+    } else {
+        0.0
+    }
+}
+
+// Entropy (nats) of the last run's class probabilities, or -1 if it had none. High values mean the model
+// is unsure, e.g. a blurry photo worth retaking
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getPredictionEntropyNative(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jfloat {
+    InferenceEngine::get_last_result()
+        .and_then(|result| result.entropy)
+        .unwrap_or(-1.0)
+}
+
+// Top-1 minus top-2 probability of the last run, or -1 if it had no class probabilities
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTopMarginNative(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jfloat {
+    InferenceEngine::get_last_result()
+        .and_then(|result| result.top_margin)
+        .unwrap_or(-1.0)
+}
+
 // Test function to verify JNI is working
 #[unsafe(no_mangle)]
 pub extern "system" fn Java_com_example_onnxapp_OnnxInference_testJNINative(
@@ -313,12 +596,12 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_testOnnxInit(
                     panic_details.push_str("==================================\n\n");
                     
                     // Include panic hook information if available
-                    if let Ok(panic_storage) = PANIC_INFO.lock() {
-                        if let Some(ref hook_info) = *panic_storage {
-                            panic_details.push_str("PANIC HOOK DATA:\n");
-                            panic_details.push_str(hook_info);
-                            panic_details.push_str("\n");
-                        }
+                    if let Ok(panic_storage) = PANIC_INFO.lock()
+                        && let Some(ref hook_info) = *panic_storage
+                    {
+                        panic_details.push_str("PANIC HOOK DATA:\n");
+                        panic_details.push_str(hook_info);
+                        panic_details.push('\n');
                     }
                     
                     // Try to extract panic message from catch_unwind
@@ -380,139 +663,2034 @@ pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferenceNative
     _class: JClass,
     image_bytes: JByteArray,
 ) -> jfloatArray {
-    // Get byte array from Java
-    let image_data = match env.convert_byte_array(image_bytes) {
-        Ok(data) => data,
-        Err(_) => return ptr::null_mut(),
-    };
-    
-    // Use the converted Vec<i8> and convert to &[u8]
-    let image_slice: &[u8] = unsafe {
-        std::slice::from_raw_parts(image_data.as_ptr() as *const u8, image_data.len())
-    };
-
-    // Create a debug log function for Android
-    let log_debug = |msg: &str| {
-        if let Ok(jstr) = env.new_string(&format!("RUST_DEBUG: {}", msg)) {
-            // Try to log via Android's logging system if available
-            // For now, we'll just ignore logging errors
-            let _ = jstr;
-        }
-    };
+    run_inference_jni(env, image_bytes, |image| run_inference_internal(image, None))
+}
 
-    match run_inference_internal(image_slice) {
-        Ok(result) => {
-            log_debug(&format!("Inference successful, data size: {}", result.data.len()));
-            
-            match env.new_float_array(result.data.len() as jint) {
-                Ok(array) => {
-                    if env.set_float_array_region(&array, 0, &result.data).is_ok() {
-                        log_debug("Successfully created and populated float array");
-                        array.into_raw()
-                    } else {
-                        log_debug("Failed to set float array region");
-                        ptr::null_mut()
-                    }
-                }
-                Err(e) => {
-                    log_debug(&format!("Failed to create float array: {:?}", e));
-                    ptr::null_mut()
-                }
-            }
-        }
-        Err(e) => {
-            let error_msg = format!("Inference failed: {}", e);
-            log_debug(&error_msg);
+// Run inference on packed 8-bit pixels without encoding them to PNG/JPEG first. pixelFormat gives the
+// byte order of each 4-byte pixel: 0 = RGBA (Bitmap.copyPixelsToBuffer on ARGB_8888), 1 = BGRA,
+// 2 = ARGB (Bitmap.getPixels ints written big-endian). Alpha follows setAlphaBackgroundNative.
+// Null + stored error if the format is unknown or the buffer isn't width * height * 4 bytes
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferenceOnPixelsNative(
+    env: JNIEnv,
+    _class: JClass,
+    pixels: JByteArray,
+    width: jint,
+    height: jint,
+    pixel_format: jint,
+) -> jfloatArray {
+    run_inference_jni(env, pixels, |pixels| {
+        let format = PixelFormat::from_code(pixel_format)
+            .ok_or_else(|| InferenceError::invalid_image(format!("Invalid pixel format: {}", pixel_format)));
+        let size = match (u32::try_from(width), u32::try_from(height)) {
+            (Ok(width), Ok(height)) => Ok((width, height)),
+            _ => Err(InferenceError::invalid_image(format!("Invalid pixel buffer size {}x{}", width, height))),
+        };
+        let result = format.and_then(|format| {
+            let (width, height) = size?;
+            InferenceEngine::run_inference_pixels(pixels, width, height, format)
+        });
+        store_inference_error(result)
+    })
+}
 
-            // Error is already stored by run_inference_internal
+// Run inference with the cached model at `model_index` (order of getLoadedModelsJsonNative) without changing
+// the active model; null + stored error if the index is out of range
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferenceByIndexNative(
+    env: JNIEnv,
+    _class: JClass,
+    model_index: jint,
+    image_bytes: JByteArray,
+) -> jfloatArray {
+    run_inference_jni(env, image_bytes, |image| {
+        let result = usize::try_from(model_index)
+            .map_err(|_| InferenceError::model_not_found(format!("No cached model at index {}", model_index)))
+            .and_then(|index| InferenceEngine::run_inference_by_index(index, image));
+        store_inference_error(result)
+    })
+}
 
-            ptr::null_mut()
-        }
-    }
+// Run inference unless preprocessing alone exceeds `deadline_ms` (null + "Deadline exceeded" error).
+// Best-effort: the deadline is checked before session.run, which itself can't be interrupted.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferenceWithDeadlineNative(
+    env: JNIEnv,
+    _class: JClass,
+    image_bytes: JByteArray,
+    deadline_ms: jlong,
+) -> jfloatArray {
+    let deadline = Duration::from_millis(deadline_ms.max(0) as u64);
+    run_inference_jni(env, image_bytes, |image| run_inference_internal(image, Some(deadline)))
 }
 
+// Simplest integration: run inference and return only the top-1 label ("" if the model isn't a
+// classifier); null + stored error if inference fails
 #[unsafe(no_mangle)]
-pub extern "system" fn Java_com_example_onnxapp_OnnxInference_isClassificationNative(
-    _env: JNIEnv,
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_classifyNative(
+    env: JNIEnv,
     _class: JClass,
-) -> jint {
-    if let Some(result) = InferenceEngine::get_last_result() {
-        return if result.is_classification { 1 } else { 0 };
+    image_bytes: JByteArray,
+) -> jstring {
+    let Ok(image_data) = env.convert_byte_array(image_bytes) else {
+        return ptr::null_mut();
+    };
+    match store_inference_error(InferenceEngine::classify(&image_data)) {
+        Ok(label) => match env.new_string(label) {
+            Ok(jstr) => jstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        Err(_) => ptr::null_mut(),
     }
-    0
 }
 
+// Run inference without touching the last result, last error or result cache, so several threads
+// can classify at once. Returns the result JSON, or {"schema_version":N,"error":{...}} on failure;
+// null only if the image bytes can't be read from Java
 #[unsafe(no_mangle)]
-pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getOutputShapeNative(
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferencePureNative(
     env: JNIEnv,
     _class: JClass,
-) -> jintArray {
-    if let Some(result) = InferenceEngine::get_last_result() {
-        let shape_i32: Vec<jint> = result.shape.iter().map(|&x| x as jint).collect();
-        match env.new_int_array(shape_i32.len() as jint) {
-            Ok(array) => {
-                if env.set_int_array_region(&array, 0, &shape_i32).is_ok() {
-                    return array.into_raw();
-                }
-            }
-            Err(_) => {}
+    image_bytes: JByteArray,
+) -> jstring {
+    let Ok(image_data) = env.convert_byte_array(image_bytes) else {
+        return ptr::null_mut();
+    };
+    let json = match InferenceEngine::run_inference_pure(&image_data) {
+        Ok(result) => {
+            let config = ConfigManager::postprocess_config();
+            result
+                .with_timings_rounded(config.timing_decimals)
+                .to_json(config.confidence_format)
         }
+        Err(e) => format!("{{\"schema_version\":{},\"error\":{}}}", RESULT_SCHEMA_VERSION, e.to_json()),
+    };
+
+    match env.new_string(&json) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
     }
-    ptr::null_mut()
 }
 
+// Run inference on the (x, y, w, h) region of the decoded image; null + stored error if it isn't inside the image
 #[unsafe(no_mangle)]
-pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTopPredictionsJsonNative(
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferenceOnRoiNative(
     env: JNIEnv,
     _class: JClass,
-) -> jstring {
-    if let Some(result) = InferenceEngine::get_last_result() {
-        if result.is_classification && !result.top_predictions.is_empty() {
-            // Create JSON string with predictions
-            let mut json_parts = Vec::new();
-            for prediction in &result.top_predictions {
-                json_parts.push(format!(
-                    "{{\"class_id\":{},\"class_name\":\"{}\",\"confidence\":{}}}",
-                    prediction.class_id,
-                    prediction.class_name.replace('"', "\\\""),
-                    prediction.confidence
-                ));
-            }
-            let json = format!("[{}]", json_parts.join(","));
+    image_bytes: JByteArray,
+    x: jint,
+    y: jint,
+    width: jint,
+    height: jint,
+) -> jfloatArray {
+    run_inference_jni(env, image_bytes, |image| {
+        let roi = Roi::from_signed(x, y, width, height)
+            .ok_or_else(|| InferenceError::invalid_image(format!("Invalid ROI ({}, {}, {}x{})", x, y, width, height)));
+        store_inference_error(roi.and_then(|roi| InferenceEngine::run_inference_on_roi(image, roi)))
+    })
+}
 
-            match env.new_string(&json) {
-                Ok(jstr) => return jstr.into_raw(),
-                Err(_) => {}
-            }
-        }
+// Test-time augmentation: classify center + 4 corner crops (mode 0) or those plus their mirrors (mode 1).
+// Returns the crop-averaged logits; top predictions/probabilities come from the averaged distribution.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferenceTtaNative(
+    env: JNIEnv,
+    _class: JClass,
+    image_bytes: JByteArray,
+    mode: jint,
+) -> jfloatArray {
+    run_inference_jni(env, image_bytes, |image| {
+        let mode = TtaMode::from_code(mode)
+            .ok_or_else(|| InferenceError::invalid_input(format!("Invalid TTA mode: {}", mode)));
+        store_inference_error(mode.and_then(|mode| InferenceEngine::run_inference_tta(image, mode)))
+    })
+}
+
+// Ensemble: classify with each cached model in `model_indices` (order of getLoadedModelsJsonNative) and
+// return the mean of their probabilities, weighted per setEnsembleWeightsNative; top predictions come from
+// that mean. The active model is unchanged. Null + stored error if an index is out of range, the weights
+// don't match the models or the models' class counts differ
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runEnsembleNative(
+    env: JNIEnv,
+    _class: JClass,
+    model_indices: JIntArray,
+    image_bytes: JByteArray,
+) -> jfloatArray {
+    let len = match env.get_array_length(&model_indices) {
+        Ok(len) => len as usize,
+        Err(_) => return ptr::null_mut(),
+    };
+    let mut ids = vec![0; len];
+    if env.get_int_array_region(&model_indices, 0, &mut ids).is_err() {
+        return ptr::null_mut();
     }
-    ptr::null_mut()
+
+    run_inference_jni(env, image_bytes, |image| {
+        let indices = ids
+            .iter()
+            .map(|&id| {
+                usize::try_from(id).map_err(|_| InferenceError::model_not_found(format!("No cached model at index {}", id)))
+            })
+            .collect::<Result<Vec<_>, _>>();
+        store_inference_error(indices.and_then(|indices| InferenceEngine::run_ensemble(&indices, image)))
+    })
 }
 
+// Run inference and write the output into a caller-owned float[] (reusable across frames, so JNI allocations and
+// GC pressure stay flat). The output is capped as set by setMaxReturnedElementsNative before it is copied.
+// Returns the number of values written, or -1 (with a stored error) on failure or if the buffer is too small.
 #[unsafe(no_mangle)]
-pub extern "system" fn Java_com_example_onnxapp_OnnxInference_loadImageNetLabelsNative(
-    mut env: JNIEnv,
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferenceIntoNative(
+    env: JNIEnv,
     _class: JClass,
-    labels_path: JString,
-) -> jstring {
-    let labels_path_str: String = match env.get_string(&labels_path) {
-        Ok(s) => s.into(),
-        Err(_) => {
-            return match env.new_string("Failed to get labels path from JNI") {
-                Ok(jstr) => jstr.into_raw(),
-                Err(_) => ptr::null_mut(),
-            }
-        }
+    image_bytes: JByteArray,
+    out_buffer: JFloatArray,
+) -> jint {
+    let convert_start = Instant::now();
+    let image_data = match env.convert_byte_array(image_bytes) {
+        Ok(data) => data,
+        Err(_) => return -1,
     };
+    let capacity = match env.get_array_length(&out_buffer) {
+        Ok(len) => len as usize,
+        Err(_) => return -1,
+    };
+    let convert_time_ms = convert_start.elapsed().as_secs_f32() * 1000.0;
 
-    let result = match LabelsManager::load_labels_from_file(&labels_path_str) {
-        Ok(count) => format!("Successfully loaded {} ImageNet labels", count),
-        Err(e) => e.to_string()
+    let result = match run_inference_internal(&image_data, None) {
+        Ok(result) => result,
+        Err(_) => return -1, // Error is already stored by run_inference_internal
     };
+    let output = Postprocessor::capped_output(&result.data, ConfigManager::postprocess_config().output_cap);
+    if let Err(e) = InferenceEngine::check_output_capacity(output.len(), capacity) {
+        InferenceEngine::store_error_detail(&e.to_string(), &e);
+        return -1;
+    }
 
-    match env.new_string(&result) {
-        Ok(jstr) => jstr.into_raw(),
-        Err(_) => ptr::null_mut(),
+    let marshal_start = Instant::now();
+    if let Err(e) = copy_output_to_java(&env, &out_buffer, &output) {
+        InferenceEngine::store_error_detail(&e.to_string(), &e);
+        return -1;
     }
+    let marshal_time_ms = marshal_start.elapsed().as_secs_f32() * 1000.0;
+    InferenceEngine::record_jni_time(convert_time_ms + marshal_time_ms);
+
+    output.len() as jint
 }
 
+// Run inference on an already normalized tensor (no image decoding/preprocessing); `shape` must match the data length.
+// The returned output is capped as set by setMaxReturnedElementsNative
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_runInferenceTensorNative(
+    env: JNIEnv,
+    _class: JClass,
+    tensor: JFloatArray,
+    shape: JIntArray,
+) -> jfloatArray {
+    let tensor_len = env.get_array_length(&tensor).unwrap_or(0) as usize;
+    let shape_len = env.get_array_length(&shape).unwrap_or(0) as usize;
+    let mut data = vec![0.0f32; tensor_len];
+    let mut dims = vec![0; shape_len];
+    if env.get_float_array_region(&tensor, 0, &mut data).is_err()
+        || env.get_int_array_region(&shape, 0, &mut dims).is_err()
+    {
+        return ptr::null_mut();
+    }
+    if dims.iter().any(|&dim| dim < 0) {
+        InferenceEngine::store_error(&format!("Invalid tensor shape {:?}: dims must be non-negative", dims));
+        return ptr::null_mut();
+    }
+    let dims: Vec<usize> = dims.into_iter().map(|dim| dim as usize).collect();
+
+    let result = match store_inference_error(InferenceEngine::run_inference_tensor(data, &dims)) {
+        Ok(result) => result,
+        Err(_) => return ptr::null_mut(),
+    };
+    let output = Postprocessor::capped_output(&result.data, ConfigManager::postprocess_config().output_cap);
+    match output_to_java(&env, &output) {
+        Some(array) => array.into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+// Copy output values into a Java array in OUTPUT_MARSHAL_CHUNK-sized region calls
+fn copy_output_to_java(env: &JNIEnv, array: &JFloatArray, data: &[f32]) -> Result<(), InferenceError> {
+    for chunk in InferenceEngine::output_chunks(data.len()) {
+        env.set_float_array_region(array, chunk.start as jint, &data[chunk.clone()])
+            .map_err(|e| {
+                InferenceError::output_processing_failed(format!(
+                    "failed to marshal output to Java: len={} (values {}..{}: {})",
+                    data.len(),
+                    chunk.start,
+                    chunk.end,
+                    e
+                ))
+            })?;
+    }
+    Ok(())
+}
+
+// Create a Java float array holding the output; stores a specific error for getLastError on failure
+fn output_to_java<'local>(env: &JNIEnv<'local>, data: &[f32]) -> Option<JFloatArray<'local>> {
+    let array = InferenceEngine::java_array_len(data.len()).and_then(|len| {
+        let array = env.new_float_array(len).map_err(|e| {
+            InferenceError::output_processing_failed(format!(
+                "failed to marshal output to Java: len={} (array allocation: {})",
+                data.len(),
+                e
+            ))
+        })?;
+        copy_output_to_java(env, &array, data)?;
+        Ok(array)
+    });
+
+    match array {
+        Ok(array) => Some(array),
+        Err(e) => {
+            InferenceEngine::store_error_detail(&e.to_string(), &e);
+            None
+        }
+    }
+}
+
+// Shared body of the runInference JNI entry points
+fn run_inference_jni(
+    env: JNIEnv,
+    image_bytes: JByteArray,
+    run: impl FnOnce(&[u8]) -> Result<InferenceResult, Box<dyn std::error::Error>>,
+) -> jfloatArray {
+    // Get byte array from Java (timed as JNI marshalling)
+    let convert_start = Instant::now();
+    let image_data = match env.convert_byte_array(image_bytes) {
+        Ok(data) => data,
+        Err(_) => return ptr::null_mut(),
+    };
+    let convert_time_ms = convert_start.elapsed().as_secs_f32() * 1000.0;
+    
+    // The error is already stored by the run closure
+    let Ok(result) = run(&image_data) else {
+        return ptr::null_mut();
+    };
+
+    let marshal_start = Instant::now();
+    let output = Postprocessor::capped_output(&result.data, ConfigManager::postprocess_config().output_cap);
+    // On failure output_to_java has stored the error
+    let Some(array) = output_to_java(&env, &output) else {
+        return ptr::null_mut();
+    };
+    let marshal_time_ms = marshal_start.elapsed().as_secs_f32() * 1000.0;
+    InferenceEngine::record_jni_time(convert_time_ms + marshal_time_ms);
+    array.into_raw()
+}
+
+// Pre-flight check: decode and preprocess the image and match it against the loaded model's input
+// without running the model. {"schema_version":N,"valid":true,"input_shape":[...]} or
+// {"schema_version":N,"valid":false,"error":{"code":..,"variant":..,"message":..}}
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_validateInputNative(
+    env: JNIEnv,
+    _class: JClass,
+    image_bytes: JByteArray,
+) -> jstring {
+    let result = env
+        .convert_byte_array(image_bytes)
+        .map_err(|_| InferenceError::invalid_image("Failed to read image bytes from JNI"))
+        .and_then(|data| InferenceEngine::validate_input(&data));
+    let json = match result {
+        Ok(shape) => {
+            let dims: Vec<String> = shape.iter().map(|dim| dim.to_string()).collect();
+            format!(
+                "{{\"schema_version\":{},\"valid\":true,\"input_shape\":[{}]}}",
+                RESULT_SCHEMA_VERSION,
+                dims.join(",")
+            )
+        }
+        // The error is only reported in the JSON: a dry run leaves get_last_error to real runs
+        Err(e) => format!("{{\"schema_version\":{},\"valid\":false,\"error\":{}}}", RESULT_SCHEMA_VERSION, e.to_json()),
+    };
+
+    match env.new_string(&json) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Run the loaded model `iterations` times on one image (after a warmup) and return inference time stats as JSON:
+// {"schema_version":N,"iterations":N,"min_ms":..,"mean_ms":..,"p50_ms":..,"p95_ms":..,"max_ms":..}. Null on error.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_benchmarkNative(
+    env: JNIEnv,
+    _class: JClass,
+    image_bytes: JByteArray,
+    iterations: jint,
+) -> jstring {
+    let image_data = match env.convert_byte_array(image_bytes) {
+        Ok(data) => data,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    benchmark_json(&env, InferenceEngine::benchmark(&image_data, iterations.max(0) as usize))
+}
+
+// Time only session.run on a zero tensor of the model's input shape (built once, after a warmup),
+// leaving image decode/resize out of model comparisons. Same JSON as benchmarkNative; null on error
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_benchmarkModelOnlyNative(
+    env: JNIEnv,
+    _class: JClass,
+    iterations: jint,
+) -> jstring {
+    benchmark_json(&env, InferenceEngine::benchmark_model_only(iterations.max(0) as usize))
+}
+
+// Serialize benchmark stats for the benchmark JNI entry points (null + stored error on failure)
+fn benchmark_json(env: &JNIEnv, stats: Result<BenchmarkStats, InferenceError>) -> jstring {
+    let stats = match store_inference_error(stats) {
+        Ok(stats) => stats,
+        Err(_) => return ptr::null_mut(),
+    };
+    let json = format!(
+        "{{\"schema_version\":{},\"iterations\":{},\"min_ms\":{},\"mean_ms\":{},\"p50_ms\":{},\"p95_ms\":{},\"max_ms\":{}}}",
+        RESULT_SCHEMA_VERSION, stats.iterations, stats.min_ms, stats.mean_ms, stats.p50_ms, stats.p95_ms, stats.max_ms
+    );
+
+    match env.new_string(&json) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Queue inference on a background thread; returns a job id for pollInferenceNative (0 on failure).
+// Jobs leave the last result and last error alone: their outcome is only reported by polling
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_startInferenceNative(
+    env: JNIEnv,
+    _class: JClass,
+    image_bytes: JByteArray,
+) -> jint {
+    match env.convert_byte_array(image_bytes) {
+        Ok(data) => JobQueue::start(data, None) as jint,
+        Err(_) => 0,
+    }
+}
+
+// Same as startInferenceNative, tagging the job's result with `requestId` (echoed as "request_id" in its JSON)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_startInferenceWithRequestIdNative(
+    env: JNIEnv,
+    _class: JClass,
+    image_bytes: JByteArray,
+    request_id: jlong,
+) -> jint {
+    match env.convert_byte_array(image_bytes) {
+        Ok(data) => JobQueue::start(data, Some(request_id)) as jint,
+        Err(_) => 0,
+    }
+}
+
+// Poll a job: {"schema_version":N,"job_id":N,"status":"pending"|"done"|"error"|"unknown"}, plus
+// "result" (same object as the inference result JSON) when done or "error" when it failed. The result
+// carries "request_id" only when the job was submitted with one
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_pollInferenceNative(
+    env: JNIEnv,
+    _class: JClass,
+    job_id: jint,
+) -> jstring {
+    let (status, detail) = match JobQueue::poll(job_id as u32) {
+        Some(JobState::Pending) => ("pending", String::new()),
+        Some(JobState::Done(result)) => (
+            "done",
+            {
+                let config = ConfigManager::postprocess_config();
+                let result = result.with_timings_rounded(config.timing_decimals);
+                format!(",\"result\":{}", result.to_json(config.confidence_format))
+            },
+        ),
+        Some(JobState::Failed(error)) => (
+            "error",
+            format!(",\"error\":\"{}\"", json::escape(&error)),
+        ),
+        None => ("unknown", String::new()),
+    };
+    let json = format!(
+        "{{\"schema_version\":{},\"job_id\":{},\"status\":\"{}\"{}}}",
+        RESULT_SCHEMA_VERSION, job_id, status, detail
+    );
+
+    match env.new_string(&json) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Free a job's stored state/result; returns 1 if the job existed
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_releaseJobNative(
+    _env: JNIEnv,
+    _class: JClass,
+    job_id: jint,
+) -> jint {
+    if JobQueue::release(job_id as u32) { 1 } else { 0 }
+}
+
+// Call listener.onInference(String json) with the result JSON after every completed stateful inference
+// (runInference*, TTA, cached results and background jobs; not runInferencePureNative), on the thread
+// that ran it. Threads not yet attached to the JVM are attached for the call. The listener is held as
+// a global ref until unregistered or replaced by another registration. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_registerResultListenerNative(
+    env: JNIEnv,
+    _class: JClass,
+    listener: JObject,
+) -> jint {
+    if listener.is_null() {
+        InferenceEngine::store_error("Result listener is null");
+        return 0;
+    }
+    let (vm, listener) = match (env.get_java_vm(), env.new_global_ref(&listener)) {
+        (Ok(vm), Ok(listener)) => (vm, listener),
+        _ => {
+            InferenceEngine::store_error("Failed to keep a reference to the result listener");
+            return 0;
+        }
+    };
+
+    ResultListener::register(Arc::new(move |result: &InferenceResult| {
+        // Already-attached threads get a guard that leaves them attached
+        let Ok(mut env) = vm.attach_current_thread() else {
+            return;
+        };
+        let config = ConfigManager::postprocess_config();
+        let json = result.clone().with_timings_rounded(config.timing_decimals).to_json(config.confidence_format);
+        let Ok(jstr) = env.new_string(json) else {
+            return;
+        };
+        let called = env.call_method(listener.as_obj(), "onInference", "(Ljava/lang/String;)V", &[(&jstr).into()]);
+        // A throwing listener must not leave a pending exception behind on an inference thread
+        if called.is_err() && env.exception_check().unwrap_or(false) {
+            let _ = env.exception_clear();
+        }
+        let _ = env.delete_local_ref(jstr);
+    }));
+    1
+}
+
+// Stop notifying the registered result listener and release its global ref; returns 1 if one was registered
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_unregisterResultListenerNative(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    if ResultListener::unregister() { 1 } else { 0 }
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_isClassificationNative(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    if let Some(result) = InferenceEngine::get_last_result() {
+        return if result.is_classification { 1 } else { 0 };
+    }
+    0
+}
+
+// Check whether the loaded model looks like a classifier before running it (from its declared output shape)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_modelLooksLikeClassifierNative(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    if InferenceEngine::model_looks_like_classifier() { 1 } else { 0 }
+}
+
+// Number of classes in the active model's classification output, from its declared shape;
+// -1 for non-classification models or when no model is loaded
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getNumClassesNative(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    InferenceEngine::num_classes()
+        .and_then(|classes| jint::try_from(classes).ok())
+        .unwrap_or(-1)
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getOutputShapeNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jintArray {
+    if let Some(result) = InferenceEngine::get_last_result() {
+        let shape_i32: Vec<jint> = result.shape.iter().map(|&x| x as jint).collect();
+        if let Ok(array) = env.new_int_array(shape_i32.len() as jint)
+            && env.set_int_array_region(&array, 0, &shape_i32).is_ok()
+        {
+            return array.into_raw();
+        }
+    }
+    ptr::null_mut()
+}
+
+// Shapes of every output of the last run: {"schema_version":N,"shapes":{"<name>":[dims...],...}};
+// null before the first inference
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getOutputShapesJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    match InferenceEngine::get_last_result() {
+        Some(result) => match env.new_string(result.output_shapes_json()) {
+            Ok(jstr) => jstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTopPredictionsJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    if let Some(result) = InferenceEngine::get_last_result()
+        && result.is_classification
+        && !result.top_predictions.is_empty()
+    {
+        // Create JSON string with predictions
+        let confidence_format = ConfigManager::postprocess_config().confidence_format;
+        let json_parts: Vec<String> = result
+            .top_predictions
+            .iter()
+            .map(|prediction| prediction.to_json(confidence_format))
+            .collect();
+        let json = versioned_json("predictions", result.schema_version, &json_parts);
+
+        if let Ok(jstr) = env.new_string(&json) {
+            return jstr.into_raw();
+        }
+    }
+    ptr::null_mut()
+}
+
+// Top-k class groups of the last classification, confidences summed over each group's classes:
+// {"schema_version":N,"groups":[{"group_id":..,"group_name":..,"confidence":..}]}; null (see getLastError)
+// without groups or a classification result
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTopGroupsNative(
+    env: JNIEnv,
+    _class: JClass,
+    k: jint,
+) -> jstring {
+    match InferenceEngine::top_groups(k.max(0) as usize) {
+        Ok(groups) => {
+            let confidence_format = ConfigManager::postprocess_config().confidence_format;
+            let items: Vec<String> = groups.iter().map(|group| group.to_json(confidence_format)).collect();
+            match env.new_string(versioned_json("groups", RESULT_SCHEMA_VERSION, &items)) {
+                Ok(jstr) => jstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+        Err(e) => {
+            InferenceEngine::store_error_detail(&e.to_string(), &e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// Top-k predictions per head of a multi-head classifier (heads set with setClassificationHeadsNative), each
+// head softmaxed on its own: {"schema_version":N,"heads":[{"head":..,"predictions":[{"class_id":..,
+// "class_name":..,"confidence":..}]}]}. Class ids are relative to the head. Null (see getLastError) without
+// heads or a result
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTopPredictionsPerHeadJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    match InferenceEngine::top_predictions_per_head(TOP_K_PREDICTIONS) {
+        Ok(heads) => {
+            let confidence_format = ConfigManager::postprocess_config().confidence_format;
+            let items: Vec<String> = heads.iter().map(|head| head.to_json(confidence_format)).collect();
+            match env.new_string(versioned_json("heads", RESULT_SCHEMA_VERSION, &items)) {
+                Ok(jstr) => jstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+        Err(e) => {
+            InferenceEngine::store_error_detail(&e.to_string(), &e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// Get the last run's top predictions as display strings ("Class 207 (golden retriever): 83.21%").
+// Null if the last run was not classification
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTopPredictionsFormattedNative(
+    mut env: JNIEnv,
+    _class: JClass,
+) -> jobjectArray {
+    let Some(result) = InferenceEngine::get_last_result().filter(|result| result.is_classification) else {
+        return ptr::null_mut();
+    };
+
+    let formatted = result.formatted_predictions();
+    let array = match env.new_object_array(formatted.len() as jint, "java/lang/String", JObject::null()) {
+        Ok(array) => array,
+        Err(_) => return ptr::null_mut(),
+    };
+    for (index, line) in formatted.iter().enumerate() {
+        let Ok(jstr) = env.new_string(line) else {
+            return ptr::null_mut();
+        };
+        if env.set_object_array_element(&array, index as jint, jstr).is_err() {
+            return ptr::null_mut();
+        }
+    }
+    array.into_raw()
+}
+
+// Get boxes from the last run of an [N, 6] detection output ([x1, y1, x2, y2, score, class_id] rows) as
+// {"schema_version":N,"detections":[{"x1":..,"y1":..,"x2":..,"y2":..,"score":..,"class_id":..}]}.
+// Boxes below the detection threshold or outside the class filter are dropped before per-class NMS, and the
+// rest are mapped back to original image pixels (including the ROI offset) when letterbox preprocessing was used.
+// `score` is the model's own value, unaffected by setConfidenceFormatNative: detectors don't all emit probabilities,
+// and it stays on the same scale as the detection threshold. Null if not a detection output.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getDetectionsJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    if let Some(result) = InferenceEngine::get_last_result()
+        && let Some(detections) = Postprocessor::decode_detections(&result.data, &result.shape)
+    {
+        let mut detections =
+            Postprocessor::filter_detections(&detections, &ConfigManager::postprocess_config().detection_filter);
+        if let Some(info) = result.letterbox {
+            detections = Postprocessor::map_letterboxed_boxes(&detections, &info);
+        }
+
+        let json_parts: Vec<String> = detections
+            .iter()
+            .map(|d| {
+                format!(
+                    "{{\"x1\":{},\"y1\":{},\"x2\":{},\"y2\":{},\"score\":{},\"class_id\":{}}}",
+                    d.x1, d.y1, d.x2, d.y2, d.score, d.class_id
+                )
+            })
+            .collect();
+        let json = versioned_json("detections", result.schema_version, &json_parts);
+
+        if let Ok(jstr) = env.new_string(&json) {
+            return jstr.into_raw();
+        }
+    }
+    ptr::null_mut()
+}
+
+// Get the last run's raw output as {"schema_version":N,"dtype":"float32","byte_order":"little","shape":[..],"data":"<Base64>"}
+// for transport as text; null if there is no result. To decode on the JVM:
+//   val bytes = java.util.Base64.getDecoder().decode(data)
+//   val floats = FloatArray(bytes.size / 4)
+//   ByteBuffer.wrap(bytes).order(ByteOrder.LITTLE_ENDIAN).asFloatBuffer().get(floats)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getRawOutputBase64Native(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    if let Some(result) = InferenceEngine::get_last_result()
+        && let Ok(jstr) = env.new_string(result.raw_output_base64_json())
+    {
+        return jstr.into_raw();
+    }
+    ptr::null_mut()
+}
+
+// Get the raw model output (logits for classifiers) from the last run; null (see getLastError) if it can't be copied
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLogitsNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jfloatArray {
+    InferenceEngine::get_last_result()
+        .and_then(|result| output_to_java(&env, &result.data))
+        .map_or(ptr::null_mut(), JFloatArray::into_raw)
+}
+
+// Get the softmax probabilities from the last run (null if the output wasn't classified, see getLastError if it can't be copied)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getProbabilitiesNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jfloatArray {
+    InferenceEngine::get_last_result()
+        .and_then(|result| result.probabilities)
+        .and_then(|probabilities| output_to_java(&env, &probabilities))
+        .map_or(ptr::null_mut(), JFloatArray::into_raw)
+}
+
+// Get indices of the top-k raw output values from the last run (no labels involved)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTopIndicesNative(
+    env: JNIEnv,
+    _class: JClass,
+    k: jint,
+) -> jintArray {
+    if let Some(result) = InferenceEngine::get_last_result() {
+        let indices: Vec<jint> = Postprocessor::top_k(&result.data, k.max(0) as usize)
+            .iter()
+            .map(|&(idx, _)| idx as jint)
+            .collect();
+        if let Ok(array) = env.new_int_array(indices.len() as jint)
+            && env.set_int_array_region(&array, 0, &indices).is_ok()
+        {
+            return array.into_raw();
+        }
+    }
+    ptr::null_mut()
+}
+
+// Get the raw output values matching getTopIndicesNative(k), in the same order
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getTopScoresNative(
+    env: JNIEnv,
+    _class: JClass,
+    k: jint,
+) -> jfloatArray {
+    if let Some(result) = InferenceEngine::get_last_result() {
+        let scores: Vec<f32> = Postprocessor::top_k(&result.data, k.max(0) as usize)
+            .iter()
+            .map(|&(_, score)| score)
+            .collect();
+        if let Ok(array) = env.new_float_array(scores.len() as jint)
+            && env.set_float_array_region(&array, 0, &scores).is_ok()
+        {
+            return array.into_raw();
+        }
+    }
+    ptr::null_mut()
+}
+
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_loadImageNetLabelsNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    labels_path: JString,
+) -> jstring {
+    let labels_path_str: String = match env.get_string(&labels_path) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            return match env.new_string("Failed to get labels path from JNI") {
+                Ok(jstr) => jstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+    };
+
+    let result = match LabelsManager::load_labels_from_file(&labels_path_str) {
+        Ok(count) => format!("Successfully loaded {} ImageNet labels", count),
+        Err(e) => e.to_string()
+    };
+
+    match env.new_string(&result) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Load labels from the raw bytes of a labels file (e.g. an APK asset), one label per line
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_loadLabelsFromBytesNative(
+    env: JNIEnv,
+    _class: JClass,
+    content: JByteArray,
+) -> jstring {
+    let result = match env.convert_byte_array(content) {
+        Ok(bytes) => match LabelsManager::load_labels_from_bytes(&bytes) {
+            Ok(count) => format!("Successfully loaded {} ImageNet labels", count),
+            Err(e) => e.to_string(),
+        },
+        Err(_) => "Failed to get labels bytes from JNI".to_string(),
+    };
+
+    match env.new_string(&result) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Replace labels from raw UTF-8 bytes, validating them in full first (non-empty, and matching the
+// active model's class count when it is known). Returns the new label count, or -1 with a stored
+// error, in which case the previous labels stay in place
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_replaceLabelsAtomicNative(
+    env: JNIEnv,
+    _class: JClass,
+    content: JByteArray,
+) -> jint {
+    let bytes = match env.convert_byte_array(content) {
+        Ok(bytes) => bytes,
+        Err(_) => return -1,
+    };
+    match InferenceEngine::replace_labels(&bytes) {
+        Ok(count) => count as jint,
+        Err(e) => {
+            InferenceEngine::store_error_detail(&e.to_string(), &e);
+            -1
+        }
+    }
+}
+
+// Set the model's class count (default 1000): fallback labels are padded to it and outputs with
+// at least this many classes are treated as classification. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setExpectedClassCountNative(
+    _env: JNIEnv,
+    _class: JClass,
+    count: jint,
+) -> jint {
+    if count <= 0 {
+        InferenceEngine::store_error(&format!("Invalid expected class count: {}", count));
+        return 0;
+    }
+    LabelsManager::set_expected_class_count(count as usize);
+    1
+}
+
+// Toggle strict labels: when on, indices without a loaded label get an empty name instead of `class_N`
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setStrictLabelsNative(
+    _env: JNIEnv,
+    _class: JClass,
+    strict: jboolean,
+) {
+    LabelsManager::set_strict(strict != 0);
+}
+
+// Class index of the label `name`: exact match first, then case-insensitive; -1 if no label matches
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getClassIndexByNameNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    name: JString,
+) -> jint {
+    let Ok(name) = env.get_string(&name) else {
+        return -1;
+    };
+    let name: String = name.into();
+    LabelsManager::index_of(&name)
+        .and_then(|index| jint::try_from(index).ok())
+        .unwrap_or(-1)
+}
+
+// Postprocessing branch of the last result: "topk" (softmax + top-k), "argmax" (softmax skipped
+// for outputs over setMaxSoftmaxClassesNative) or "raw" (not a classification output); null if no result
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getPostprocessModeNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let Some(result) = InferenceEngine::get_last_result() else {
+        return ptr::null_mut();
+    };
+    match env.new_string(result.postprocess_mode.as_str()) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Treat [N, C] (or batched [1, N, C]) outputs as N region proposals: each row is softmaxed on its own and
+// its top-1 kept
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setRegionPredictionsNative(
+    _env: JNIEnv,
+    _class: JClass,
+    on: jboolean,
+) {
+    ConfigManager::set_region_predictions(on != 0);
+}
+
+// Top-1 prediction of each region of the last [N, C] result as {"schema_version":N,"regions":[...]},
+// in region order; null if region predictions are off or the last output wasn't two-dimensional
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getRegionPredictionsJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let Some(regions) = InferenceEngine::get_last_result().and_then(|result| result.region_predictions) else {
+        return ptr::null_mut();
+    };
+    let confidence_format = ConfigManager::postprocess_config().confidence_format;
+    let items: Vec<String> = regions.iter().map(|region| region.to_json(confidence_format)).collect();
+
+    match env.new_string(versioned_json("regions", RESULT_SCHEMA_VERSION, &items)) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Get the warning from the last labels/model class count check (empty if none)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLabelWarningNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let warning = LabelsManager::get_label_warning().unwrap_or_default();
+
+    match env.new_string(&warning) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Warnings attached to the last result, one per line (empty if there were none)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLastWarningsNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let warnings = InferenceEngine::get_last_result()
+        .map(|result| result.warnings.join("\n"))
+        .unwrap_or_default();
+
+    match env.new_string(&warnings) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// How labels resolve for the active model: {"status":"matched"|"mismatch"|"fallback"|"unknown",
+// "model_classes":..,"label_count":..}. On "mismatch" the loaded labels are ignored and classes
+// are named class_N (empty in strict mode)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLabelResolutionStatusNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    match env.new_string(InferenceEngine::label_resolution().to_json()) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Select the resize strategy (0 = stretch, 1 = resize shorter side to `size`, 2 = letterbox,
+// 3 = follow the model's declared H/W, sizing dynamic axes by aspect ratio snapped to a multiple of `size`,
+// 4 = pad to a square with the 0xRRGGBB fill color in `size`, then stretch); returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setResizeModeNative(
+    _env: JNIEnv,
+    _class: JClass,
+    mode: jint,
+    size: jint,
+) -> jint {
+    match ResizeMode::from_code(mode, size) {
+        Some(resize_mode) => {
+            ConfigManager::set_resize_mode(resize_mode);
+            1
+        }
+        None => {
+            InferenceEngine::store_error(&format!("Invalid resize mode: {} (size {})", mode, size));
+            0
+        }
+    }
+}
+
+// Restrict top predictions to the given class ids; an empty array restores all classes
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setClassAllowlistNative(
+    env: JNIEnv,
+    _class: JClass,
+    class_ids: JIntArray,
+) -> jint {
+    let len = match env.get_array_length(&class_ids) {
+        Ok(len) => len as usize,
+        Err(_) => return 0,
+    };
+    let mut ids = vec![0; len];
+    if env.get_int_array_region(&class_ids, 0, &mut ids).is_err() {
+        return 0;
+    }
+    if ids.iter().any(|&id| id < 0) {
+        InferenceEngine::store_error("Invalid class allowlist: class ids must be non-negative");
+        return 0;
+    }
+
+    ConfigManager::set_class_allowlist(ids.into_iter().map(|id| id as usize).collect());
+    1
+}
+
+// Compute and extract only the named model outputs; the first is the primary output (getOutputShapeNative,
+// predictions, ...). An empty array restores the default (first output only). Returns 1 on success;
+// unknown names fail at the next run
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setRequestedOutputsNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    names: JObjectArray,
+) -> jint {
+    let len = match env.get_array_length(&names) {
+        Ok(len) => len,
+        Err(_) => return 0,
+    };
+    let mut output_names = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        let name = match env.get_object_array_element(&names, i) {
+            Ok(name) => JString::from(name),
+            Err(_) => return 0,
+        };
+        match env.get_string(&name) {
+            Ok(name) => output_names.push(String::from(name)),
+            Err(_) => {
+                InferenceEngine::store_error("Invalid requested outputs: names must be non-null strings");
+                return 0;
+            }
+        }
+    }
+
+    ConfigManager::set_requested_outputs(output_names);
+    1
+}
+
+// Skip softmax when the classification output has more than n values (e.g. a large LM head):
+// such results carry no probabilities and only the argmax, with its probability as "confidence" and
+// its logit as "raw_score".
+// n <= 0 always applies softmax. Returns 1
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setMaxSoftmaxClassesNative(
+    _env: JNIEnv,
+    _class: JClass,
+    n: jint,
+) -> jint {
+    ConfigManager::set_max_softmax_classes((n > 0).then_some(n as usize));
+    1
+}
+
+// Return at most n output values from runInferenceNative and the other run methods that return a float[]
+// or fill runInferenceIntoNative's buffer (including runInferenceTensorNative): subset 0 = the first n in
+// output order, 1 = the n largest, highest first. The largest values come without class ids; when the
+// output has more than n values, getTopIndicesNative(n) returns their ids in the same order. Stored
+// results and getters keep the full output. n < 0 returns the whole output again. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setMaxReturnedElementsNative(
+    _env: JNIEnv,
+    _class: JClass,
+    n: jint,
+    subset: jint,
+) -> jint {
+    let Some(subset) = ReturnedSubset::from_code(subset) else {
+        InferenceEngine::store_error(&format!("Invalid returned subset: {}", subset));
+        return 0;
+    };
+    ConfigManager::set_output_cap(usize::try_from(n).ok().map(|max_elements| OutputCap { max_elements, subset }));
+    1
+}
+
+// Calibrate classifier confidences: logits become logit / temperature + classBias[i] before softmax.
+// An empty classBias applies the temperature alone; (1, []) turns calibration off. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setCalibrationNative(
+    env: JNIEnv,
+    _class: JClass,
+    temperature: jfloat,
+    class_bias: JFloatArray,
+) -> jint {
+    let len = match env.get_array_length(&class_bias) {
+        Ok(len) => len as usize,
+        Err(_) => return 0,
+    };
+    let mut bias = vec![0.0f32; len];
+    if env.get_float_array_region(&class_bias, 0, &mut bias).is_err() {
+        return 0;
+    }
+
+    if temperature == 1.0 && bias.is_empty() {
+        ConfigManager::set_calibration(None);
+        return 1;
+    }
+    match Calibration::new(temperature, bias) {
+        Some(calibration) => {
+            ConfigManager::set_calibration(Some(calibration));
+            1
+        }
+        None => {
+            InferenceEngine::store_error("Invalid calibration: temperature must be positive and every value finite");
+            0
+        }
+    }
+}
+
+// Set the detection score threshold and the IoU above which same-class boxes are suppressed
+// (in [0, 1]). Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setDetectionThresholdsNative(
+    _env: JNIEnv,
+    _class: JClass,
+    conf_threshold: jfloat,
+    iou_threshold: jfloat,
+) -> jint {
+    if !conf_threshold.is_finite() || !(0.0..=1.0).contains(&iou_threshold) {
+        InferenceEngine::store_error(&format!(
+            "Invalid detection thresholds: confidence {}, IoU {}",
+            conf_threshold, iou_threshold
+        ));
+        return 0;
+    }
+    ConfigManager::set_detection_thresholds(conf_threshold, iou_threshold);
+    1
+}
+
+// Keep only detections of the given class ids; an empty array keeps every class
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setDetectionClassFilterNative(
+    env: JNIEnv,
+    _class: JClass,
+    class_ids: JIntArray,
+) -> jint {
+    let len = match env.get_array_length(&class_ids) {
+        Ok(len) => len as usize,
+        Err(_) => return 0,
+    };
+    let mut ids = vec![0; len];
+    if env.get_int_array_region(&class_ids, 0, &mut ids).is_err() {
+        return 0;
+    }
+    if let Some(&invalid) = ids.iter().find(|&&id| id < 0) {
+        InferenceEngine::store_error(&format!("Invalid class id in detection filter: {}", invalid));
+        return 0;
+    }
+
+    ConfigManager::set_detection_class_filter(ids.into_iter().map(|id| id as usize).collect());
+    1
+}
+
+// Map classes to named groups for getTopGroupsNative: classToGroup[classId] indexes groupNames (-1 = no group).
+// Empty arrays clear the mapping. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setClassGroupsNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    class_to_group: JIntArray,
+    group_names: JObjectArray,
+) -> jint {
+    let len = match env.get_array_length(&class_to_group) {
+        Ok(len) => len as usize,
+        Err(_) => return 0,
+    };
+    let mut mapping = vec![0; len];
+    if env.get_int_array_region(&class_to_group, 0, &mut mapping).is_err() {
+        return 0;
+    }
+    let names_len = match env.get_array_length(&group_names) {
+        Ok(len) => len,
+        Err(_) => return 0,
+    };
+    let mut names = Vec::with_capacity(names_len as usize);
+    for i in 0..names_len {
+        let name = match env.get_object_array_element(&group_names, i) {
+            Ok(name) => JString::from(name),
+            Err(_) => return 0,
+        };
+        match env.get_string(&name) {
+            Ok(name) => names.push(String::from(name)),
+            Err(_) => {
+                InferenceEngine::store_error("Invalid class groups: group names must be non-null strings");
+                return 0;
+            }
+        }
+    }
+
+    if mapping.is_empty() && names.is_empty() {
+        ConfigManager::set_class_groups(None);
+        return 1;
+    }
+    match ClassGroups::new(&mapping, names) {
+        Some(groups) => {
+            ConfigManager::set_class_groups(Some(groups));
+            1
+        }
+        None => {
+            InferenceEngine::store_error("Invalid class groups: every entry must be -1 or index groupNames");
+            0
+        }
+    }
+}
+
+// Split the output into classification heads for getTopPredictionsPerHeadJsonNative: head i covers
+// headSizes[i] logits starting at headOffsets[i]. Empty arrays clear the heads. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setClassificationHeadsNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    head_offsets: JIntArray,
+    head_sizes: JIntArray,
+    head_names: JObjectArray,
+) -> jint {
+    let read_ints = |array: &JIntArray| {
+        let len = env.get_array_length(array).ok()? as usize;
+        let mut values = vec![0; len];
+        env.get_int_array_region(array, 0, &mut values).ok()?;
+        Some(values)
+    };
+    let (Some(offsets), Some(sizes)) = (read_ints(&head_offsets), read_ints(&head_sizes)) else {
+        return 0;
+    };
+    let names_len = match env.get_array_length(&head_names) {
+        Ok(len) => len,
+        Err(_) => return 0,
+    };
+    if offsets.len() != sizes.len() || offsets.len() != names_len as usize {
+        InferenceEngine::store_error("Invalid classification heads: offsets, sizes and names must have the same length");
+        return 0;
+    }
+
+    let mut heads = Vec::with_capacity(offsets.len());
+    for (i, (&offset, &size)) in offsets.iter().zip(&sizes).enumerate() {
+        let name = match env.get_object_array_element(&head_names, i as jint) {
+            Ok(name) => JString::from(name),
+            Err(_) => return 0,
+        };
+        let name = match env.get_string(&name) {
+            Ok(name) => String::from(name),
+            Err(_) => {
+                InferenceEngine::store_error("Invalid classification heads: names must be non-null strings");
+                return 0;
+            }
+        };
+        match ClassificationHead::new(offset, size, name) {
+            Some(head) => heads.push(head),
+            None => {
+                InferenceEngine::store_error(&format!(
+                    "Invalid classification head {}: offset must be >= 0 and size > 0",
+                    i
+                ));
+                return 0;
+            }
+        }
+    }
+    ConfigManager::set_classification_heads(heads);
+    1
+}
+
+// Choose how classification confidences are written in JSON outputs: 0 = fraction in [0, 1] (default),
+// 1 = percent in [0, 100]. Detection scores are left as the model produced them. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setConfidenceFormatNative(
+    _env: JNIEnv,
+    _class: JClass,
+    format: jint,
+) -> jint {
+    match ConfidenceFormat::from_code(format) {
+        Some(format) => {
+            ConfigManager::set_confidence_format(format);
+            1
+        }
+        None => {
+            InferenceEngine::store_error(&format!("Invalid confidence format: {}", format));
+            0
+        }
+    }
+}
+
+// Round timings returned by the timing getters and in result JSON to `decimals` places (0..=6); negative
+// restores full precision. Stored results keep full precision. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setTimingPrecisionNative(
+    _env: JNIEnv,
+    _class: JClass,
+    decimals: jint,
+) -> jint {
+    match decimals {
+        ..0 => ConfigManager::set_timing_decimals(None),
+        0..=6 => ConfigManager::set_timing_decimals(Some(decimals as u32)),
+        _ => {
+            InferenceEngine::store_error(&format!("Invalid timing precision: {} (max 6 decimals)", decimals));
+            return 0;
+        }
+    }
+    1
+}
+
+// Set input quantization for int8/uint8 models (q = round(value / scale) + zeroPoint), enabling the fused
+// preprocess+quantize path; scale 0 disables it. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setQuantParamsNative(
+    _env: JNIEnv,
+    _class: JClass,
+    scale: jfloat,
+    zero_point: jint,
+) -> jint {
+    if scale == 0.0 {
+        ConfigManager::set_quant_params(None);
+        return 1;
+    }
+
+    match QuantParams::new(scale, zero_point) {
+        Some(params) => {
+            ConfigManager::set_quant_params(Some(params));
+            1
+        }
+        None => {
+            InferenceEngine::store_error(&format!("Invalid quantization parameters: scale {}, zero point {}", scale, zero_point));
+            0
+        }
+    }
+}
+
+// Set the largest image (width * height) that will be decoded; bigger images fail with
+// InvalidImageData before any pixel buffer is allocated. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setMaxImageSizeNative(
+    _env: JNIEnv,
+    _class: JClass,
+    max_pixels: jint,
+) -> jint {
+    if max_pixels <= 0 {
+        InferenceEngine::store_error(&format!("Invalid maximum image size: {}", max_pixels));
+        return 0;
+    }
+    ConfigManager::set_max_image_pixels(max_pixels as u64);
+    1
+}
+
+// Warn when an image's shorter side is under `min_dim` pixels, which upscaling can't make up for: inference
+// still runs and the warning goes on the result ("warnings" in its JSON, see getLastWarningsNative).
+// 0 turns the check off (the default). Returns 1 on success, 0 for a negative size
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setMinImageQualityNative(
+    _env: JNIEnv,
+    _class: JClass,
+    min_dim: jint,
+) -> jint {
+    let Ok(min_dim) = u32::try_from(min_dim) else {
+        InferenceEngine::store_error(&format!("Invalid minimum image dimension: {}", min_dim));
+        return 0;
+    };
+    ConfigManager::set_min_image_dimension((min_dim > 0).then_some(min_dim));
+    1
+}
+
+// Composite images with transparency onto the 0xRRGGBB `background` color before conversion to RGB;
+// a negative value restores the default of dropping alpha. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setAlphaBackgroundNative(
+    _env: JNIEnv,
+    _class: JClass,
+    background: jint,
+) -> jint {
+    if background > 0xFF_FFFF {
+        InferenceEngine::store_error(&format!("Invalid alpha background color: {:#x}", background));
+        return 0;
+    }
+    let color = (background >= 0).then(|| {
+        let [_, r, g, b] = background.to_be_bytes();
+        [r, g, b]
+    });
+    ConfigManager::set_alpha_background(color);
+    1
+}
+
+// Lay out color input values planar (0 = NCHW planes, default) or interleaved (1 = RGBRGB.. behind the
+// declared NCHW shape), for models exported with a mislabeled input layout. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setDataOrderNative(
+    _env: JNIEnv,
+    _class: JClass,
+    order: jint,
+) -> jint {
+    match DataOrder::from_code(order) {
+        Some(order) => {
+            ConfigManager::set_data_order(order);
+            1
+        }
+        None => {
+            InferenceEngine::store_error(&format!("Invalid data order: {}", order));
+            0
+        }
+    }
+}
+
+// Normalize each image by the mean and std of its own resized pixels instead of the configured
+// normalization (as tf.image.per_image_standardization does); a flat image becomes all zeros
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setPerImageStandardizationNative(
+    _env: JNIEnv,
+    _class: JClass,
+    on: jboolean,
+) {
+    ConfigManager::set_per_image_standardization(on != 0);
+}
+
+// Replace the resize mode and normalization with an ordered JSON pipeline of resize/crop/flip/scale/normalize
+// steps, e.g. [{"op":"resize","shortest_side":256},{"op":"crop","width":224,"height":224}]; an empty string
+// goes back to the individual settings. At least one resize or crop step is required. Returns 0 + stored
+// error (naming the bad step) if the JSON is invalid
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setPreprocessPipelineNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    json: JString,
+) -> jint {
+    let json: String = match env.get_string(&json) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            InferenceEngine::store_error("Failed to get pipeline JSON from JNI");
+            return 0;
+        }
+    };
+    if json.trim().is_empty() {
+        ConfigManager::set_preprocess_pipeline(None);
+        return 1;
+    }
+    match Pipeline::parse(&json) {
+        Ok(pipeline) => {
+            ConfigManager::set_preprocess_pipeline(Some(pipeline));
+            1
+        }
+        Err(e) => {
+            InferenceEngine::store_error(&format!("Invalid preprocessing pipeline: {}", e));
+            0
+        }
+    }
+}
+
+// Weight each runEnsembleNative member's probabilities, in the order its indices are passed; an empty
+// array restores equal weights. Returns 0 + stored error if a weight is negative or non-finite, or all are zero
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setEnsembleWeightsNative(
+    env: JNIEnv,
+    _class: JClass,
+    weights: JFloatArray,
+) -> jint {
+    let len = match env.get_array_length(&weights) {
+        Ok(len) => len as usize,
+        Err(_) => return 0,
+    };
+    let mut values = vec![0.0f32; len];
+    if env.get_float_array_region(&weights, 0, &mut values).is_err() {
+        return 0;
+    }
+
+    if values.is_empty() {
+        ConfigManager::set_ensemble_weights(None);
+        return 1;
+    }
+    if values.iter().any(|weight| !weight.is_finite() || *weight < 0.0) || values.iter().all(|weight| *weight == 0.0) {
+        InferenceEngine::store_error("Invalid ensemble weights: every weight must be finite and non-negative, and one positive");
+        return 0;
+    }
+    ConfigManager::set_ensemble_weights(Some(values));
+    1
+}
+
+// Select a normalization preset (0 = ImageNet, 1 = Inception [-1, 1], 2 = CLIP, 3 = None [0, 1]); returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setNormalizationPresetNative(
+    _env: JNIEnv,
+    _class: JClass,
+    preset: jint,
+) -> jint {
+    match NormalizationPreset::from_code(preset) {
+        Some(preset) => {
+            ConfigManager::set_normalization(preset.normalization());
+            1
+        }
+        None => {
+            InferenceEngine::store_error(&format!("Invalid normalization preset: {}", preset));
+            0
+        }
+    }
+}
+
+// Set custom normalization: (pixel * scale - mean[c]) / std[c] with 3-element mean/std; returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setNormalizationNative(
+    env: JNIEnv,
+    _class: JClass,
+    mean: JFloatArray,
+    std: JFloatArray,
+    scale: jfloat,
+) -> jint {
+    let mut mean_values = [0.0f32; 3];
+    let mut std_values = [0.0f32; 3];
+    let lengths_ok = env.get_array_length(&mean).is_ok_and(|len| len == 3)
+        && env.get_array_length(&std).is_ok_and(|len| len == 3);
+
+    if lengths_ok
+        && env.get_float_array_region(&mean, 0, &mut mean_values).is_ok()
+        && env.get_float_array_region(&std, 0, &mut std_values).is_ok()
+        && let Some(normalization) = Normalization::new(mean_values, std_values, scale)
+    {
+        ConfigManager::set_normalization(normalization);
+        return 1;
+    }
+
+    InferenceEngine::store_error("Invalid normalization: mean and std need 3 finite values, std non-zero");
+    0
+}
+
+// Get the last result's input image size as [width, height], before any crop or resize, so boxes and
+// overlays can be scaled without decoding the image again. Null if there is no result or it came from a tensor
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getOriginalImageSizeNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jintArray {
+    if let Some(result) = InferenceEngine::get_last_result()
+        && result.orig_width > 0
+    {
+        let size = [result.orig_width as jint, result.orig_height as jint];
+        if let Ok(array) = env.new_int_array(size.len() as jint)
+            && env.set_int_array_region(&array, 0, &size).is_ok()
+        {
+            return array.into_raw();
+        }
+    }
+    ptr::null_mut()
+}
+
+// Get the letterbox transform of the last result as [scale, padX, padY, origWidth, origHeight, offsetX, offsetY],
+// where origWidth/origHeight is the letterboxed region and offsetX/offsetY its ROI origin (null if none)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLetterboxInfoNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jfloatArray {
+    if let Some(info) = InferenceEngine::get_last_result().and_then(|result| result.letterbox) {
+        let values = [
+            info.scale,
+            info.pad_x as f32,
+            info.pad_y as f32,
+            info.orig_width as f32,
+            info.orig_height as f32,
+            info.offset_x as f32,
+            info.offset_y as f32,
+        ];
+        if let Ok(array) = env.new_float_array(values.len() as jint)
+            && env.set_float_array_region(&array, 0, &values).is_ok()
+        {
+            return array.into_raw();
+        }
+    }
+    ptr::null_mut()
+}
+
+// Get min/max/mean/std of the last preprocessed input tensor as
+// {"schema_version":N,"min":..,"max":..,"mean":..,"std":..}; ImageNet-normalized images land near mean 0, std 1.
+// Null before any image has been preprocessed
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLastInputStatsNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    match ImagePreprocessor::last_input_stats() {
+        Some(stats) => match env.new_string(stats.to_json()) {
+            Ok(jstr) => jstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+// Toggle deterministic sessions (1 thread, no memory arena) for subsequently loaded models.
+// Trades throughput for reproducible outputs and stable inference_time_ms.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setDeterministicNative(
+    _env: JNIEnv,
+    _class: JClass,
+    on: jboolean,
+) {
+    ConfigManager::set_deterministic(on != 0);
+}
+
+// Get the last result (shape, timings, predictions) serialized as a FlatBuffer
+#[cfg(feature = "flatbuffers")]
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getLastResultFlatbufferNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jni::sys::jbyteArray {
+    if let Some(result) = reported_last_result() {
+        let buf = flatbuffer::serialize_result(&result);
+        if let Ok(array) = env.byte_array_from_slice(&buf) {
+            return array.into_raw();
+        }
+    }
+    ptr::null_mut()
+}
+
+// Create the ONNX Runtime environment with log capture (call before loading a model)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_initRuntimeNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let result = match RuntimeLog::init() {
+        Ok(true) => "ONNX Runtime initialized with log capture".to_string(),
+        Ok(false) => "ONNX Runtime already initialized".to_string(),
+        Err(e) => {
+            let error_msg = e.to_string();
+            InferenceEngine::store_error_detail(&error_msg, &e);
+            error_msg
+        }
+    };
+
+    match env.new_string(&result) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Set the minimum ORT log severity (0 = verbose, 1 = info, 2 = warning, 3 = error, 4 = fatal); returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setLogLevelNative(
+    _env: JNIEnv,
+    _class: JClass,
+    level: jint,
+) -> jint {
+    match RuntimeLog::level_from_code(level) {
+        Some(log_level) => {
+            RuntimeLog::set_level(log_level);
+            1
+        }
+        None => {
+            InferenceEngine::store_error(&format!("Invalid log level: {}", level));
+            0
+        }
+    }
+}
+
+// Get recent ONNX Runtime log entries, one per line (oldest first)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getRuntimeLogNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let log = RuntimeLog::entries().join("\n");
+
+    match env.new_string(&log) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Keep a copy of each preprocessed input for saveDebugInputNative (off by default; off also drops the copy)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setInputCaptureNative(
+    _env: JNIEnv,
+    _class: JClass,
+    on: jboolean,
+) {
+    InferenceEngine::set_input_capture(on != 0);
+}
+
+// Save the last captured input (denormalized to RGB with the config it was made with) as a PNG for
+// debugging normalization. Needs setInputCaptureNative(true) before the run
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_saveDebugInputNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    out_path: JString,
+) -> jstring {
+    let out_path_str: String = match env.get_string(&out_path) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            return match env.new_string("Failed to get output path from JNI") {
+                Ok(jstr) => jstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+    };
+
+    let result = match InferenceEngine::save_debug_input(&out_path_str) {
+        Ok(_) => format!("Debug input saved: {}", out_path_str),
+        Err(e) => {
+            let error_msg = e.to_string();
+            InferenceEngine::store_error_detail(&error_msg, &e);
+            error_msg
+        }
+    };
+
+    match env.new_string(&result) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Load every model listed in a manifest of `modelPath[,providers]` lines (providers e.g. "nnapi|cpu") into the
+// model cache, one at a time; a failing entry doesn't stop the rest. Returns
+// {"schema_version":N,"loaded":[{"path":..,"provider":..}],"failed":[{"entry":..,"error":..}]}, or null (with a
+// stored error) if the manifest can't be read. The last loaded model becomes active
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_preloadModelsNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    manifest_path: JString,
+) -> jstring {
+    let manifest_path: String = match env.get_string(&manifest_path) {
+        Ok(s) => s.into(),
+        Err(_) => return ptr::null_mut(),
+    };
+
+    match Preloader::preload_manifest(&manifest_path) {
+        Ok(report) => match env.new_string(report.to_json()) {
+            Ok(jstr) => jstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        Err(e) => {
+            InferenceEngine::store_error_detail(&e.to_string(), &e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// Evaluate the active model on a manifest of `imagePath,trueClassId` lines. Returns
+// {"schema_version":N,"evaluated":N,"top1_accuracy":..,"top5_accuracy":..,"skipped":[{"entry":..,"reason":..}]},
+// or null (with a stored error) if the manifest can't be read
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_evaluateNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    manifest_path: JString,
+) -> jstring {
+    let manifest_path: String = match env.get_string(&manifest_path) {
+        Ok(s) => s.into(),
+        Err(_) => return ptr::null_mut(),
+    };
+
+    match Evaluator::evaluate_manifest(&manifest_path) {
+        Ok(report) => match env.new_string(report.to_json()) {
+            Ok(jstr) => jstr.into_raw(),
+            Err(_) => ptr::null_mut(),
+        },
+        Err(e) => {
+            InferenceEngine::store_error_detail(&e.to_string(), &e);
+            ptr::null_mut()
+        }
+    }
+}
+
+// Write the last result's full class distribution to a CSV file (class_id,class_name,confidence; most confident first)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_exportDistributionCsvNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    out_path: JString,
+) -> jstring {
+    let out_path_str: String = match env.get_string(&out_path) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            return match env.new_string("Failed to get output path from JNI") {
+                Ok(jstr) => jstr.into_raw(),
+                Err(_) => ptr::null_mut(),
+            }
+        }
+    };
+
+    let result = match InferenceEngine::export_distribution_csv(&out_path_str) {
+        Ok(rows) => format!("Exported {} classes to {}", rows, out_path_str),
+        Err(e) => {
+            let error_msg = e.to_string();
+            InferenceEngine::store_error_detail(&error_msg, &e);
+            error_msg
+        }
+    };
+
+    match env.new_string(&result) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Append timestamp,prep_ms,infer_ms,post_ms,total_ms for every completed inference to a CSV file
+// (truncated; writes are buffered until stopTraceNative). Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_startTraceNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    path: JString,
+) -> jint {
+    let path: String = match env.get_string(&path) {
+        Ok(s) => s.into(),
+        Err(_) => return 0,
+    };
+
+    match TimingTrace::start(&path) {
+        Ok(()) => 1,
+        Err(e) => {
+            InferenceEngine::store_error_detail(&e.to_string(), &e);
+            0
+        }
+    }
+}
+
+// Flush and close the timing trace; returns 1 if a trace was running and its rows were written
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_stopTraceNative(
+    _env: JNIEnv,
+    _class: JClass,
+) -> jint {
+    match TimingTrace::stop() {
+        Ok(stopped) => stopped as jint,
+        Err(e) => {
+            InferenceEngine::store_error_detail(&e.to_string(), &e);
+            0
+        }
+    }
+}
+
+// Set the name of the input that receives the image for subsequently loaded models (empty = auto-detect rank-4 input)
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setInputNameNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    input_name: JString,
+) {
+    let name: String = match env.get_string(&input_name) {
+        Ok(s) => s.into(),
+        Err(_) => return,
+    };
+    ConfigManager::set_input_name((!name.is_empty()).then_some(name));
+}
+
+// Restore every preprocessing, postprocessing and session option (and the expected class count) to its default
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_resetConfigNative(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    ConfigManager::reset();
+    LabelsManager::set_expected_class_count(MIN_CLASSIFICATION_CLASSES);
+}
+
+// Reuse the previous result when runInferenceNative gets byte-identical image bytes (the JSON then has
+// "cached":true). Exact match only: any changed byte, model or option runs the model again. Off by default
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setResultCacheNative(
+    _env: JNIEnv,
+    _class: JClass,
+    on: jboolean,
+) {
+    InferenceEngine::set_result_cache(on != 0);
+}
+
+// Image formats this build can decode, e.g. {"schema_version":N,"formats":["jpeg","png","gif","bmp","pnm"]}
+// ("webp" is added when built with the webp feature), for rejecting unsupported uploads up front
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_getSupportedImageFormatsNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    let items: Vec<String> = ImagePreprocessor::supported_formats()
+        .iter()
+        .map(|format| format!("\"{}\"", format))
+        .collect();
+    match env.new_string(versioned_json("formats", RESULT_SCHEMA_VERSION, &items)) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Dump every preprocessing, postprocessing and session option as
+// {"schema_version":N,"preprocess":{..},"postprocess":{..},"session":{..}} for bug reports or per-model configs
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_exportConfigJsonNative(
+    env: JNIEnv,
+    _class: JClass,
+) -> jstring {
+    match env.new_string(ConfigJson::export()) {
+        Ok(jstr) => jstr.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+// Restore options from exportConfigJsonNative's JSON. Missing keys take their defaults; an invalid document
+// changes nothing. Session options apply to the next model load. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_importConfigJsonNative(
+    mut env: JNIEnv,
+    _class: JClass,
+    json: JString,
+) -> jint {
+    let json: String = match env.get_string(&json) {
+        Ok(s) => s.into(),
+        Err(_) => {
+            InferenceEngine::store_error("Failed to get config JSON from JNI");
+            return 0;
+        }
+    };
+    match ConfigJson::import(&json) {
+        Ok(()) => 1,
+        Err(e) => {
+            InferenceEngine::store_error(&format!("Failed to import config: {}", e));
+            0
+        }
+    }
+}
+
+// Set the execution provider for subsequently loaded models (0 = CPU, 1 = NNAPI, 2 = XNNPACK).
+// Check getActiveProviderNative() after loading to see whether it registered.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setExecutionProviderNative(
+    _env: JNIEnv,
+    _class: JClass,
+    provider: jint,
+) -> jint {
+    match ExecutionProviderKind::from_code(provider) {
+        Some(kind) => {
+            ConfigManager::set_execution_provider(kind);
+            1
+        }
+        None => {
+            InferenceEngine::store_error(&format!("Invalid execution provider: {}", provider));
+            0
+        }
+    }
+}
+
+// Use a shared CPU arena for subsequently loaded models: strategy 0 = next power of two (ORT default),
+// 1 = same as requested (smallest footprint, slower runs while the arena grows); initBytes 0 = default
+// first chunk. strategy -1 restores ORT's per-session default arena. Returns 1 on success
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setArenaConfigNative(
+    _env: JNIEnv,
+    _class: JClass,
+    strategy: jint,
+    init_bytes: jlong,
+) -> jint {
+    if strategy == -1 {
+        ConfigManager::set_arena_config(None);
+        return 1;
+    }
+
+    match (ArenaExtendStrategy::from_code(strategy), usize::try_from(init_bytes)) {
+        (Some(extend_strategy), Ok(initial_chunk_bytes)) => {
+            ConfigManager::set_arena_config(Some(ArenaConfig {
+                extend_strategy,
+                initial_chunk_bytes,
+            }));
+            1
+        }
+        _ => {
+            InferenceEngine::store_error(&format!("Invalid arena config: strategy {}, initial bytes {}", strategy, init_bytes));
+            0
+        }
+    }
+}
+
+// Set inter-op threads for subsequently loaded models (0 = ORT default).
+// Only used for parallel graph branches; setDeterministicNative(true) overrides it to 1.
+#[unsafe(no_mangle)]
+pub extern "system" fn Java_com_example_onnxapp_OnnxInference_setInterOpThreadsNative(
+    _env: JNIEnv,
+    _class: JClass,
+    threads: jint,
+) -> jint {
+    if threads < 0 {
+        InferenceEngine::store_error(&format!("Invalid inter-op thread count: {}", threads));
+        return 0;
+    }
+    ConfigManager::set_inter_op_threads(threads as usize);
+    1
+}
@@ -0,0 +1,33 @@
+/// Notification of every completed inference to one registered observer (e.g. the app's Java listener)
+use crate::types::InferenceResult as InferenceOutput;
+use std::sync::{Arc, Mutex};
+
+/// Callback run with each completed result
+pub type ResultHook = Arc<dyn Fn(&InferenceOutput) + Send + Sync>;
+
+/// Static storage for the registered hook
+static LISTENER: Mutex<Option<ResultHook>> = Mutex::new(None);
+
+/// Result listener registry
+pub struct ResultListener;
+
+impl ResultListener {
+    /// Register `hook`, replacing (and dropping) any previous one
+    pub fn register(hook: ResultHook) {
+        *LISTENER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(hook);
+    }
+
+    /// Remove the registered hook; returns false if none was registered
+    pub fn unregister() -> bool {
+        LISTENER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take().is_some()
+    }
+
+    /// Run the registered hook on `result`, on the calling thread. The registry lock is released
+    /// first, so the hook may run inference or (un)register listeners itself.
+    pub fn notify(result: &InferenceOutput) {
+        let hook = LISTENER.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        if let Some(hook) = hook {
+            hook(result);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Minimal ONNX protobuf reader for model fields ONNX Runtime doesn't expose (e.g. the opset)
+
+/// `ModelProto.opset_import` field number
+const OPSET_IMPORT_FIELD: u64 = 8;
+
+/// Cursor over protobuf wire-format bytes
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes.get(self.pos)?;
+            self.pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(len)?;
+        let slice = self.bytes.get(self.pos..end)?;
+        self.pos = end;
+        Some(slice)
+    }
+
+    /// Next field number and value (`None` at the end or on malformed input)
+    fn field(&mut self) -> Option<(u64, Field<'a>)> {
+        if self.pos >= self.bytes.len() {
+            return None;
+        }
+        let key = self.varint()?;
+        let field = match key & 7 {
+            0 => Field::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Field::Skipped
+            }
+            2 => {
+                let len = usize::try_from(self.varint()?).ok()?;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Field::Skipped
+            }
+            _ => return None,
+        };
+        Some((key >> 3, field))
+    }
+}
+
+/// Field value by wire type
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    /// Fixed-width fields, which no field read here uses
+    Skipped,
+}
+
+/// Version of the default (`ai.onnx`) operator set the model imports, if it can be read
+pub fn default_opset(model_bytes: &[u8]) -> Option<i64> {
+    let mut model = Reader::new(model_bytes);
+    while let Some((number, field)) = model.field() {
+        let (OPSET_IMPORT_FIELD, Field::Bytes(opset)) = (number, field) else {
+            continue;
+        };
+
+        let (mut domain, mut version): (&[u8], Option<i64>) = (b"", None);
+        let mut entry = Reader::new(opset);
+        while let Some((number, field)) = entry.field() {
+            match (number, field) {
+                (1, Field::Bytes(value)) => domain = value,
+                (2, Field::Varint(value)) => version = Some(value as i64),
+                _ => {}
+            }
+        }
+        if domain.is_empty() || domain == b"ai.onnx" {
+            return version;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::onnx;
+
+    #[test]
+    fn test_default_opset() {
+        assert_eq!(default_opset(&onnx::tiny_classifier(1000)), Some(13));
+        assert_eq!(default_opset(b"not a model"), None);
+    }
+}
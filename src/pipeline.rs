@@ -0,0 +1,246 @@
+//! Preprocessing described as an ordered JSON list of steps, e.g.
+//! `[{"op":"resize","shortest_side":256},{"op":"crop","width":224,"height":224},{"op":"scale","factor":0.00392}]`
+use crate::config::{Normalization, ResizeMode};
+use crate::constants::MAX_IMAGE_DIMENSION;
+use crate::json::{self, Json};
+use crate::preprocess::ImagePreprocessor;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbImage};
+
+/// One preprocessing step
+#[derive(Debug, Clone, PartialEq)]
+pub enum PipelineStep {
+    /// Resize to exactly `width` x `height`
+    Resize { width: u32, height: u32 },
+    /// Resize so the shorter side is this long, keeping the aspect ratio
+    ResizeShortestSide(u32),
+    /// Crop a `width` x `height` region (clamped to the image) centered on `anchor`, a fractional
+    /// `[x, y]` image position: `[0.5, 0.5]` is the geometric center, `[0.5, 0.3]` biases toward the
+    /// top. The window is shifted as needed to stay inside the image.
+    CenterCrop { width: u32, height: u32, anchor: [f32; 2] },
+    /// Mirror horizontally (`true`) or vertically
+    Flip { horizontal: bool },
+    /// Multiply every value by this factor
+    Scale(f32),
+    /// Subtract the per-channel mean, then divide by the per-channel std
+    Normalize { mean: [f32; 3], std: [f32; 3] },
+}
+
+/// An ordered preprocessing pipeline.
+///
+/// Resize, crop and flip run on the image in order. Scale and normalize are folded, in order, into
+/// a single per-channel multiply-add applied to the final pixels, so where they sit relative to
+/// the geometric steps doesn't change the result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pipeline {
+    pub steps: Vec<PipelineStep>,
+}
+
+impl Pipeline {
+    /// Parse a JSON array of steps; the error names the first step that is invalid
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::from_json(&json::parse(text)?)
+    }
+
+    /// Build a pipeline from an already parsed JSON array of steps. It must contain a resize or
+    /// crop step, since without one the tensor would take the size of whatever image comes in.
+    pub fn from_json(value: &Json) -> Result<Self, String> {
+        let items = value.as_array().ok_or("Pipeline must be a JSON array of steps")?;
+        let steps = items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| Self::parse_step(item).map_err(|e| format!("Pipeline step {}: {}", index, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sizes_image = |step: &PipelineStep| {
+            matches!(step, PipelineStep::Resize { .. } | PipelineStep::ResizeShortestSide(_) | PipelineStep::CenterCrop { .. })
+        };
+        if !steps.iter().any(sizes_image) {
+            return Err("Pipeline needs a resize or crop step to fix the input size".to_string());
+        }
+        Ok(Self { steps })
+    }
+
+    fn parse_step(item: &Json) -> Result<PipelineStep, String> {
+        let size = |key: &str| {
+            item.get(key)
+                .and_then(Json::as_usize)
+                .and_then(|size| u32::try_from(size).ok())
+                .filter(|size| (1..=MAX_IMAGE_DIMENSION).contains(size))
+                .ok_or_else(|| format!("\"{}\" must be an integer from 1 to {}", key, MAX_IMAGE_DIMENSION))
+        };
+        let triple = |key: &str| match item.get(key).and_then(Json::as_array) {
+            Some([a, b, c]) => [a, b, c]
+                .map(|value| value.as_f64().map(|value| value as f32).filter(|value| value.is_finite()))
+                .into_iter()
+                .collect::<Option<Vec<_>>>()
+                .map(|values| [values[0], values[1], values[2]]),
+            _ => None,
+        }
+        .ok_or_else(|| format!("\"{}\" must be an array of three numbers", key));
+
+        match item.get("op").and_then(Json::as_str) {
+            Some("resize") if item.get("shortest_side").is_some() => Ok(PipelineStep::ResizeShortestSide(size("shortest_side")?)),
+            Some("resize") => Ok(PipelineStep::Resize { width: size("width")?, height: size("height")? }),
+            Some("crop") => {
+                let anchor = match item.get("anchor").map(Json::as_array) {
+                    None => [0.5, 0.5],
+                    Some(Some([x, y])) => [x, y]
+                        .map(|value| value.as_f64().filter(|value| (0.0..=1.0).contains(value)).map(|value| value as f32))
+                        .into_iter()
+                        .collect::<Option<Vec<_>>>()
+                        .map(|anchor| [anchor[0], anchor[1]])
+                        .ok_or("\"anchor\" values must be between 0 and 1")?,
+                    Some(_) => return Err("\"anchor\" must be an [x, y] array".to_string()),
+                };
+                Ok(PipelineStep::CenterCrop { width: size("width")?, height: size("height")?, anchor })
+            }
+            Some("flip") => match item.get("direction").and_then(Json::as_str).unwrap_or("horizontal") {
+                "horizontal" => Ok(PipelineStep::Flip { horizontal: true }),
+                "vertical" => Ok(PipelineStep::Flip { horizontal: false }),
+                other => Err(format!("unknown flip direction '{}'", other)),
+            },
+            Some("scale") => item
+                .get("factor")
+                .and_then(Json::as_f64)
+                .map(|factor| factor as f32)
+                .filter(|factor| factor.is_finite() && *factor > 0.0)
+                .map(PipelineStep::Scale)
+                .ok_or_else(|| "\"factor\" must be a positive number".to_string()),
+            Some("normalize") => {
+                let (mean, std) = (triple("mean")?, triple("std")?);
+                if std.iter().any(|std| *std <= 0.0) {
+                    return Err("\"std\" values must be positive".to_string());
+                }
+                Ok(PipelineStep::Normalize { mean, std })
+            }
+            Some(other) => Err(format!("unknown op '{}'", other)),
+            None => Err("missing \"op\"".to_string()),
+        }
+    }
+
+    /// Serialize back to the JSON array `parse` accepts
+    pub fn to_json(&self) -> String {
+        let list = |values: &[f32; 3]| format!("[{},{},{}]", values[0], values[1], values[2]);
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|step| match step {
+                PipelineStep::Resize { width, height } => format!("{{\"op\":\"resize\",\"width\":{},\"height\":{}}}", width, height),
+                PipelineStep::ResizeShortestSide(side) => format!("{{\"op\":\"resize\",\"shortest_side\":{}}}", side),
+                PipelineStep::CenterCrop { width, height, anchor } => format!(
+                    "{{\"op\":\"crop\",\"width\":{},\"height\":{},\"anchor\":[{},{}]}}",
+                    width, height, anchor[0], anchor[1]
+                ),
+                PipelineStep::Flip { horizontal } => format!(
+                    "{{\"op\":\"flip\",\"direction\":\"{}\"}}",
+                    if *horizontal { "horizontal" } else { "vertical" }
+                ),
+                PipelineStep::Scale(factor) => format!("{{\"op\":\"scale\",\"factor\":{}}}", factor),
+                PipelineStep::Normalize { mean, std } => {
+                    format!("{{\"op\":\"normalize\",\"mean\":{},\"std\":{}}}", list(mean), list(std))
+                }
+            })
+            .collect();
+        format!("[{}]", steps.join(","))
+    }
+
+    /// Run the geometric steps on `img` and return the resulting pixels together with the
+    /// normalization equivalent to the value steps (identity if there are none)
+    pub fn apply(&self, img: &DynamicImage) -> (RgbImage, Normalization) {
+        let mut pixels = img.to_rgb8();
+        // value * gain + offset, per channel
+        let (mut gain, mut offset) = ([1.0f32; 3], [0.0f32; 3]);
+        for step in &self.steps {
+            match *step {
+                PipelineStep::Resize { width, height } => {
+                    pixels = imageops::resize(&pixels, width, height, FilterType::Lanczos3);
+                }
+                PipelineStep::ResizeShortestSide(side) => {
+                    let (width, height) =
+                        ImagePreprocessor::target_size(pixels.width(), pixels.height(), ResizeMode::ResizeShortestSide(side));
+                    pixels = imageops::resize(&pixels, width, height, FilterType::Lanczos3);
+                }
+                PipelineStep::CenterCrop { width, height, anchor } => {
+                    let (width, height) = (width.min(pixels.width()), height.min(pixels.height()));
+                    let x = Self::crop_origin(pixels.width(), width, anchor[0]);
+                    let y = Self::crop_origin(pixels.height(), height, anchor[1]);
+                    pixels = imageops::crop_imm(&pixels, x, y, width, height).to_image();
+                }
+                PipelineStep::Flip { horizontal: true } => imageops::flip_horizontal_in_place(&mut pixels),
+                PipelineStep::Flip { horizontal: false } => imageops::flip_vertical_in_place(&mut pixels),
+                PipelineStep::Scale(factor) => {
+                    for c in 0..3 {
+                        gain[c] *= factor;
+                        offset[c] *= factor;
+                    }
+                }
+                PipelineStep::Normalize { mean, std } => {
+                    for c in 0..3 {
+                        gain[c] /= std[c];
+                        offset[c] = (offset[c] - mean[c]) / std[c];
+                    }
+                }
+            }
+        }
+
+        // (value - mean) / std == value * gain + offset with std = 1 / gain, mean = -offset / gain
+        let normalization = Normalization {
+            mean: [0, 1, 2].map(|c| -offset[c] / gain[c]),
+            std: gain.map(|gain| 1.0 / gain),
+            scale: 1.0,
+        };
+        (pixels, normalization)
+    }
+
+    /// Start of a `window`-long crop of a `len`-long axis centered at `anchor * len`, clamped so
+    /// the window stays inside the axis
+    fn crop_origin(len: u32, window: u32, anchor: f32) -> u32 {
+        let start = (anchor * len as f32 - window as f32 / 2.0).round();
+        start.clamp(0.0, (len - window) as f32) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_apply_two_step_pipeline() {
+        let pipeline = Pipeline::parse(r#"[{"op":"crop","width":2,"height":1},{"op":"normalize","mean":[10,20,30],"std":[2,4,5]}]"#).unwrap();
+        assert_eq!(pipeline.steps.len(), 2);
+        assert_eq!(Pipeline::parse(&pipeline.to_json()).unwrap(), pipeline);
+
+        let img = RgbImage::from_fn(4, 3, |x, y| image::Rgb([(x * 10 + y) as u8, 50, 100]));
+        let (pixels, normalization) = pipeline.apply(&DynamicImage::ImageRgb8(img));
+        assert_eq!(pixels.dimensions(), (2, 1));
+        assert_eq!(pixels.get_pixel(0, 0).0, [11, 50, 100]);
+        for (c, expected) in [0.5, 7.5, 14.0].into_iter().enumerate() {
+            let value = pixels.get_pixel(0, 0).0[c] as f32 * normalization.scale;
+            assert!(((value - normalization.mean[c]) / normalization.std[c] - expected).abs() < 1e-4);
+        }
+
+        let err = Pipeline::parse(r#"[{"op":"flip"},{"op":"blur"}]"#).unwrap_err();
+        assert_eq!(err, "Pipeline step 1: unknown op 'blur'");
+        assert!(Pipeline::parse(r#"[{"op":"scale","factor":0}]"#).is_err());
+        let err = Pipeline::parse(r#"[{"op":"flip"},{"op":"scale","factor":0.5}]"#).unwrap_err();
+        assert!(err.contains("resize or crop"), "{}", err);
+    }
+
+    #[test]
+    fn test_crop_anchor_shifts_window() {
+        // Each pixel encodes its own position, so the crop's first pixel gives the window origin
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(100, 80, |x, y| image::Rgb([x as u8, y as u8, 0])));
+        let origin = |json: &str| {
+            let (pixels, _) = Pipeline::parse(json).unwrap().apply(&img);
+            assert_eq!(pixels.dimensions(), (40, 40));
+            let [x, y, _] = pixels.get_pixel(0, 0).0;
+            (x, y)
+        };
+
+        assert_eq!(origin(r#"[{"op":"crop","width":40,"height":40}]"#), (30, 20));
+        assert_eq!(origin(r#"[{"op":"crop","width":40,"height":40,"anchor":[0.5,0.3]}]"#), (30, 4));
+        // Anchors near an edge are clamped so the window stays inside the image
+        assert_eq!(origin(r#"[{"op":"crop","width":40,"height":40,"anchor":[1.0,0.0]}]"#), (60, 0));
+        assert!(Pipeline::parse(r#"[{"op":"crop","width":40,"height":40,"anchor":[1.5,0]}]"#).is_err());
+    }
+}
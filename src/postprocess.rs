@@ -0,0 +1,494 @@
+/// Output tensor postprocessing helpers
+use crate::config::{Calibration, ClassGroups, DetectionFilter, OutputCap, ReturnedSubset};
+use crate::errors::{InferenceError, InferenceResult};
+use crate::labels::LabelsManager;
+use crate::preprocess::LetterboxInfo;
+use crate::types::Detection;
+use std::borrow::Cow;
+
+/// Postprocessing utilities for model outputs
+pub struct Postprocessor;
+
+impl Postprocessor {
+    /// Get the `k` largest values as `(index, value)` pairs, highest first.
+    /// Ties keep the lower index first, so results are deterministic.
+    pub fn top_k(values: &[f32], k: usize) -> Vec<(usize, f32)> {
+        Self::select_top_k(values.iter().copied().enumerate().collect(), k)
+    }
+
+    /// Like `top_k`, but only ranks the listed indices (duplicates and out-of-range ids are ignored)
+    pub fn top_k_among(values: &[f32], k: usize, allowed: &[usize]) -> Vec<(usize, f32)> {
+        let mut indices: Vec<usize> = allowed.iter().copied().filter(|&i| i < values.len()).collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        Self::select_top_k(indices.into_iter().map(|i| (i, values[i])).collect(), k)
+    }
+
+    /// Rank `(index, value)` pairs by value (descending), then index (ascending), keeping the first `k`.
+    /// NaN ranks below every number, so a NaN output never becomes the top class.
+    /// Partitions around the k-th entry before sorting, so only the kept entries are sorted —
+    /// e.g. top-5 of 1000 classes sorts 5 entries instead of 1000.
+    fn select_top_k(mut indexed: Vec<(usize, f32)>, k: usize) -> Vec<(usize, f32)> {
+        let order = |a: &(usize, f32), b: &(usize, f32)| {
+            a.1.is_nan().cmp(&b.1.is_nan()).then(b.1.total_cmp(&a.1)).then(a.0.cmp(&b.0))
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+        if k < indexed.len() {
+            indexed.select_nth_unstable_by(k - 1, order);
+            indexed.truncate(k);
+        }
+        indexed.sort_unstable_by(order);
+        indexed
+    }
+
+    /// The part of `data` returned to Java under `cap`: the first `max_elements` values, or the
+    /// largest ones highest first. Outputs within the cap are returned whole, in output order.
+    /// The largest values carry no class ids: they are ranked exactly like `top_k`, so
+    /// `top_k(data, max_elements)` gives their ids in the same order.
+    pub fn capped_output(data: &[f32], cap: Option<OutputCap>) -> Cow<'_, [f32]> {
+        match cap {
+            Some(cap) if data.len() > cap.max_elements => match cap.subset {
+                ReturnedSubset::First => Cow::Borrowed(&data[..cap.max_elements]),
+                ReturnedSubset::Largest => {
+                    Cow::Owned(Self::top_k(data, cap.max_elements).into_iter().map(|(_, value)| value).collect())
+                }
+            },
+            _ => Cow::Borrowed(data),
+        }
+    }
+
+    /// Shannon entropy of a probability distribution in nats (0 = certain, ln(C) = uniform over C classes)
+    pub fn entropy(probabilities: &[f32]) -> f32 {
+        -probabilities
+            .iter()
+            .filter(|&&p| p > 0.0)
+            .map(|&p| p * p.ln())
+            .sum::<f32>()
+    }
+
+    /// Gap between the two highest probabilities (the top probability itself for a single class)
+    pub fn top_margin(probabilities: &[f32]) -> f32 {
+        match Self::top_k(probabilities, 2)[..] {
+            [(_, first), (_, second)] => first - second,
+            [(_, first)] => first,
+            _ => 0.0,
+        }
+    }
+
+    /// Apply a calibration to logits: `logit / temperature + class_bias[i]`
+    pub fn calibrate(logits: &[f32], calibration: &Calibration) -> InferenceResult<Vec<f32>> {
+        let Calibration { temperature, ref class_bias } = *calibration;
+        if class_bias.is_empty() {
+            return Ok(logits.iter().map(|&logit| logit / temperature).collect());
+        }
+        if class_bias.len() != logits.len() {
+            return Err(InferenceError::output_processing_failed(format!(
+                "Calibration has {} class biases but the model outputs {} classes",
+                class_bias.len(),
+                logits.len()
+            )));
+        }
+        Ok(logits.iter().zip(class_bias).map(|(&logit, &bias)| logit / temperature + bias).collect())
+    }
+
+    /// Sum class probabilities per group. Classes the mapping doesn't cover (or leaves
+    /// ungrouped) are ignored, so group totals can add up to less than 1.
+    pub fn group_probabilities(probabilities: &[f32], groups: &ClassGroups) -> Vec<f32> {
+        let mut totals = vec![0.0f32; groups.names.len()];
+        for (&probability, group) in probabilities.iter().zip(&groups.class_to_group) {
+            if let Some(group) = *group {
+                totals[group] += probability;
+            }
+        }
+        totals
+    }
+
+    /// Decode an `[N, 6]` (or `[1, N, 6]`) output of `[x1, y1, x2, y2, score, class_id]` rows,
+    /// the layout of detectors exported with NMS included. Returns `None` for other shapes.
+    pub fn decode_detections(data: &[f32], shape: &[usize]) -> Option<Vec<Detection>> {
+        let rows = match shape {
+            [rows, 6] | [1, rows, 6] => *rows,
+            _ => return None,
+        };
+        if data.len() != rows * 6 {
+            return None;
+        }
+
+        Some(
+            data.chunks_exact(6)
+                .map(|row| Detection {
+                    x1: row[0],
+                    y1: row[1],
+                    x2: row[2],
+                    y2: row[3],
+                    score: row[4],
+                    class_id: row[5].max(0.0) as usize,
+                })
+                .collect(),
+        )
+    }
+
+    /// Drop boxes below the score threshold or outside the class filter, then run greedy
+    /// per-class NMS. The survivors are returned highest score first.
+    pub fn filter_detections(boxes: &[Detection], filter: &DetectionFilter) -> Vec<Detection> {
+        let mut candidates: Vec<Detection> = boxes
+            .iter()
+            .filter(|detection| detection.score >= filter.conf_threshold)
+            .filter(|detection| {
+                filter
+                    .classes
+                    .as_ref()
+                    .is_none_or(|classes| classes.contains(&detection.class_id))
+            })
+            .copied()
+            .collect();
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        let mut kept: Vec<Detection> = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            let suppressed = kept
+                .iter()
+                .any(|kept| kept.class_id == candidate.class_id && Self::iou(kept, &candidate) > filter.iou_threshold);
+            if !suppressed {
+                kept.push(candidate);
+            }
+        }
+        kept
+    }
+
+    /// Intersection over union of two boxes (0 when either has no area)
+    fn iou(a: &Detection, b: &Detection) -> f32 {
+        let width = (a.x2.min(b.x2) - a.x1.max(b.x1)).max(0.0);
+        let height = (a.y2.min(b.y2) - a.y1.max(b.y1)).max(0.0);
+        let intersection = width * height;
+        let area = |d: &Detection| (d.x2 - d.x1).max(0.0) * (d.y2 - d.y1).max(0.0);
+        let union = area(a) + area(b) - intersection;
+        if union > 0.0 { intersection / union } else { 0.0 }
+    }
+
+    /// Undo a letterbox transform: remove the padding, divide by the resize scale and clamp
+    /// each box to the original image bounds
+    pub fn map_boxes_to_original(
+        boxes: &[Detection],
+        scale: f32,
+        pad: (f32, f32),
+        orig_w: u32,
+        orig_h: u32,
+    ) -> Vec<Detection> {
+        let (pad_x, pad_y) = pad;
+        let map_x = |x: f32| ((x - pad_x) / scale).clamp(0.0, orig_w as f32);
+        let map_y = |y: f32| ((y - pad_y) / scale).clamp(0.0, orig_h as f32);
+
+        boxes
+            .iter()
+            .map(|detection| Detection {
+                x1: map_x(detection.x1),
+                y1: map_y(detection.y1),
+                x2: map_x(detection.x2),
+                y2: map_y(detection.y2),
+                ..*detection
+            })
+            .collect()
+    }
+
+    /// Map boxes back through a recorded letterbox transform into decoded-image pixels, shifting
+    /// them by the ROI origin when the letterboxed region was an ROI crop
+    pub fn map_letterboxed_boxes(boxes: &[Detection], info: &LetterboxInfo) -> Vec<Detection> {
+        let (offset_x, offset_y) = (info.offset_x as f32, info.offset_y as f32);
+        Self::map_boxes_to_original(
+            boxes,
+            info.scale,
+            (info.pad_x as f32, info.pad_y as f32),
+            info.orig_width,
+            info.orig_height,
+        )
+        .into_iter()
+        .map(|detection| Detection {
+            x1: detection.x1 + offset_x,
+            y1: detection.y1 + offset_y,
+            x2: detection.x2 + offset_x,
+            y2: detection.y2 + offset_y,
+            ..detection
+        })
+        .collect()
+    }
+
+    /// Class axis length of a declared output shape (see `class_axis_len`).
+    /// A dynamic batch dim is treated as 1; any other dynamic dim makes the count unknown.
+    pub fn declared_class_count(declared_shape: &[i64]) -> Option<usize> {
+        let mut shape = Vec::with_capacity(declared_shape.len());
+        for (axis, &dim) in declared_shape.iter().enumerate() {
+            match dim {
+                d if d >= 0 => shape.push(d as usize),
+                _ if axis == 0 => shape.push(1),
+                _ => return None,
+            }
+        }
+
+        Self::class_axis_len(&shape).ok()
+    }
+
+    /// Decide from a declared output shape whether a model looks like a classifier
+    pub fn looks_like_classifier(declared_shape: &[i64]) -> bool {
+        Self::declared_class_count(declared_shape).is_some_and(|classes| classes >= LabelsManager::expected_class_count())
+    }
+
+    /// Squeeze size-1 dims from an output shape and return the class axis length.
+    /// `[1, C]` and `[1, C, 1, 1]` yield `C`; anything that isn't 1D after squeezing
+    /// (e.g. a `[1, C, 7, 7]` feature map) is rejected.
+    pub fn class_axis_len(shape: &[usize]) -> InferenceResult<usize> {
+        let squeezed: Vec<usize> = shape.iter().copied().filter(|&dim| dim != 1).collect();
+
+        match squeezed.as_slice() {
+            [classes] => Ok(*classes),
+            // Every dim is 1: a single-logit output
+            [] if !shape.is_empty() => Ok(1),
+            _ => Err(InferenceError::invalid_output(format!(
+                "Output shape {:?} is not 1D after squeezing size-1 dims",
+                shape
+            ))),
+        }
+    }
+
+    /// Class count of an output treated as classification logits: `Some(C)` when the output has a
+    /// single class axis of at least `min_classes`, `None` when it isn't a classifier output. A
+    /// `[1, C, H, W]` map with a class-sized channel axis is an error rather than `None`: it is a
+    /// classifier missing its pooling, and its values aren't `C` class logits.
+    pub fn classification_class_count(shape: &[usize], min_classes: usize) -> InferenceResult<Option<usize>> {
+        match (Self::class_axis_len(shape), shape) {
+            (Ok(classes), _) => Ok(Some(classes).filter(|&classes| classes >= min_classes)),
+            (Err(err), &[1, channels, _, _]) if channels >= min_classes => Err(err),
+            (Err(_), _) => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_k_indices() {
+        let scores = [0.2, 0.9, -1.0, 0.5, 0.9, 0.1];
+        let top = Postprocessor::top_k(&scores, 3);
+        assert_eq!(top.iter().map(|&(i, _)| i).collect::<Vec<_>>(), vec![1, 4, 3]);
+        assert_eq!(top[2].1, 0.5);
+
+        // k larger than the vector returns everything
+        assert_eq!(Postprocessor::top_k(&scores, 10).len(), scores.len());
+    }
+
+    #[test]
+    fn test_partial_top_k_matches_full_sort() {
+        // Coarse values so many entries tie
+        let scores: Vec<f32> = (0..1000).map(|i| ((i * 7919) % 97) as f32 / 97.0).collect();
+        let mut full: Vec<(usize, f32)> = scores.iter().copied().enumerate().collect();
+        full.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+        for k in [0, 1, 5, 10, 97, 999, 1000, 1500] {
+            assert_eq!(Postprocessor::top_k(&scores, k), full[..k.min(full.len())], "k = {}", k);
+        }
+    }
+
+    #[test]
+    fn test_top_k_ranks_nan_last() {
+        let scores = [0.1, f32::NAN, 0.7, -f32::NAN, f32::NEG_INFINITY];
+        let top = Postprocessor::top_k(&scores, 5);
+        assert_eq!(top.iter().map(|&(i, _)| i).collect::<Vec<_>>(), vec![2, 0, 4, 1, 3]);
+        assert_eq!(Postprocessor::top_k(&scores, 1), vec![(2, 0.7)]);
+        assert_eq!(Postprocessor::top_k_among(&scores, 1, &[1, 4]), vec![(4, f32::NEG_INFINITY)]);
+    }
+
+    #[test]
+    #[ignore = "benchmark: run with --ignored --nocapture"]
+    fn bench_partial_top_k_against_full_sort() {
+        use std::time::Instant;
+        const ITERATIONS: u32 = 2000;
+        let scores: Vec<f32> = (0..1000).map(|i| ((i * 7919) % 1009) as f32 / 1009.0).collect();
+
+        for k in [1, 5] {
+            let started = Instant::now();
+            for _ in 0..ITERATIONS {
+                let mut full: Vec<(usize, f32)> = scores.iter().copied().enumerate().collect();
+                full.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+                full.truncate(k);
+                std::hint::black_box(full);
+            }
+            let full_us = started.elapsed().as_secs_f64() * 1e6 / ITERATIONS as f64;
+
+            let started = Instant::now();
+            for _ in 0..ITERATIONS {
+                std::hint::black_box(Postprocessor::top_k(std::hint::black_box(&scores), k));
+            }
+            let partial_us = started.elapsed().as_secs_f64() * 1e6 / ITERATIONS as f64;
+
+            println!("top-{} of 1000: full sort {:.1} us, partial selection {:.1} us", k, full_us, partial_us);
+        }
+    }
+
+    #[test]
+    fn test_capped_output_length() {
+        let data = [0.5, 3.0, -1.0, 2.0, 0.0];
+        let first = OutputCap { max_elements: 2, subset: ReturnedSubset::First };
+        let largest = OutputCap { max_elements: 3, subset: ReturnedSubset::Largest };
+
+        assert_eq!(&*Postprocessor::capped_output(&data, Some(first)), &[0.5, 3.0]);
+        assert_eq!(&*Postprocessor::capped_output(&data, Some(largest)), &[3.0, 2.0, 0.5]);
+        assert_eq!(Postprocessor::capped_output(&data, None).len(), 5);
+        let roomy = OutputCap { max_elements: 10, ..first };
+        assert_eq!(&*Postprocessor::capped_output(&data, Some(roomy)), &data);
+    }
+
+    #[test]
+    fn test_largest_subset_pairs_with_top_indices() {
+        // Ties included: both sides must break them the same way
+        let data = [0.5, 3.0, -1.0, 2.0, 3.0, 0.0];
+        let cap = OutputCap { max_elements: 4, subset: ReturnedSubset::Largest };
+
+        let values = Postprocessor::capped_output(&data, Some(cap));
+        let indices: Vec<usize> = Postprocessor::top_k(&data, 4).into_iter().map(|(index, _)| index).collect();
+        assert_eq!(indices, vec![1, 4, 3, 0]);
+        assert_eq!(indices.iter().map(|&index| data[index]).collect::<Vec<_>>(), values.to_vec());
+    }
+
+    #[test]
+    fn test_entropy_and_margin_of_known_distribution() {
+        let probabilities = [0.25, 0.5, 0.25];
+        // -(0.5 ln 0.5 + 2 * 0.25 ln 0.25) = 1.5 ln 2
+        assert!((Postprocessor::entropy(&probabilities) - 1.5 * 2f32.ln()).abs() < 1e-6);
+        assert_eq!(Postprocessor::top_margin(&probabilities), 0.25);
+
+        assert_eq!(Postprocessor::entropy(&[1.0, 0.0]), 0.0);
+        assert_eq!(Postprocessor::top_margin(&[1.0, 0.0]), 1.0);
+        assert_eq!(Postprocessor::top_margin(&[0.7]), 0.7);
+    }
+
+    #[test]
+    fn test_group_probabilities_sum_members() {
+        let groups = ClassGroups::new(&[0, 1, 0, -1, 2, 1], vec!["animal".into(), "vehicle".into(), "food".into()]).unwrap();
+        let probabilities = [0.1, 0.2, 0.3, 0.15, 0.05, 0.2];
+
+        let totals = Postprocessor::group_probabilities(&probabilities, &groups);
+        let expected = [0.4, 0.4, 0.05];
+        assert!(totals.iter().zip(expected).all(|(total, want)| (total - want).abs() < 1e-6));
+
+        let top = Postprocessor::top_k(&totals, 2);
+        assert_eq!(top.iter().map(|&(group, _)| group).collect::<Vec<_>>(), vec![0, 1]);
+
+        assert!(ClassGroups::new(&[0, 3], vec!["a".into()]).is_none());
+        assert!(ClassGroups::new(&[-2], vec!["a".into()]).is_none());
+    }
+
+    #[test]
+    fn test_class_axis_squeezes_spatial_dims() {
+        assert_eq!(Postprocessor::class_axis_len(&[1, 3, 1, 1]).unwrap(), 3);
+        assert_eq!(Postprocessor::class_axis_len(&[1, 1000]).unwrap(), 1000);
+        assert_eq!(Postprocessor::class_axis_len(&[1000]).unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_looks_like_classifier() {
+        assert!(Postprocessor::looks_like_classifier(&[1, 1000]));
+        assert!(Postprocessor::looks_like_classifier(&[-1, 1000]));
+        assert!(Postprocessor::looks_like_classifier(&[1, 1000, 1, 1]));
+
+        // Detector heads and feature maps
+        assert!(!Postprocessor::looks_like_classifier(&[1, 25200, 85]));
+        assert!(!Postprocessor::looks_like_classifier(&[1, 84, -1]));
+        // Too few classes
+        assert!(!Postprocessor::looks_like_classifier(&[1, 10]));
+    }
+
+    #[test]
+    fn test_class_axis_rejects_feature_maps() {
+        assert!(Postprocessor::class_axis_len(&[1, 3, 7, 7]).is_err());
+        assert!(Postprocessor::class_axis_len(&[]).is_err());
+    }
+
+    #[test]
+    fn test_classification_class_count() {
+        assert_eq!(Postprocessor::classification_class_count(&[1, 1000, 1, 1], 1000).unwrap(), Some(1000));
+        assert_eq!(Postprocessor::classification_class_count(&[1, 10], 1000).unwrap(), None);
+        // Detector heads and small feature maps aren't classifier outputs
+        assert_eq!(Postprocessor::classification_class_count(&[1, 25200, 85], 1000).unwrap(), None);
+        assert_eq!(Postprocessor::classification_class_count(&[1, 3, 7, 7], 1000).unwrap(), None);
+
+        // An unpooled classifier has several class-sized values per class
+        let err = Postprocessor::classification_class_count(&[1, 1000, 7, 7], 1000).unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidOutput(_)));
+    }
+
+    #[test]
+    fn test_map_boxes_inverts_letterbox() {
+        // 448x224 image letterboxed into 224x224: scale 0.5, 56px bars top and bottom
+        let boxes = [
+            Detection { x1: 10.0, y1: 66.0, x2: 110.0, y2: 156.0, score: 0.9, class_id: 3 },
+            // Extends into the padding and past the right edge
+            Detection { x1: 200.0, y1: 20.0, x2: 230.0, y2: 100.0, score: 0.4, class_id: 1 },
+        ];
+
+        let mapped = Postprocessor::map_boxes_to_original(&boxes, 0.5, (0.0, 56.0), 448, 224);
+        assert_eq!((mapped[0].x1, mapped[0].y1, mapped[0].x2, mapped[0].y2), (20.0, 20.0, 220.0, 200.0));
+        assert_eq!((mapped[0].score, mapped[0].class_id), (0.9, 3));
+        assert_eq!((mapped[1].x1, mapped[1].y1, mapped[1].x2, mapped[1].y2), (400.0, 0.0, 448.0, 88.0));
+    }
+
+    #[test]
+    fn test_map_letterboxed_boxes_adds_roi_offset() {
+        // 448x224 ROI at (100, 40) letterboxed into 224x224
+        let info = LetterboxInfo {
+            scale: 0.5,
+            pad_x: 0,
+            pad_y: 56,
+            orig_width: 448,
+            orig_height: 224,
+            offset_x: 100,
+            offset_y: 40,
+        };
+        let boxes = [Detection { x1: 10.0, y1: 66.0, x2: 110.0, y2: 156.0, score: 0.9, class_id: 3 }];
+
+        let mapped = Postprocessor::map_letterboxed_boxes(&boxes, &info);
+        assert_eq!((mapped[0].x1, mapped[0].y1, mapped[0].x2, mapped[0].y2), (120.0, 60.0, 320.0, 240.0));
+        assert_eq!((mapped[0].score, mapped[0].class_id), (0.9, 3));
+    }
+
+    fn boxes() -> Vec<Detection> {
+        vec![
+            Detection { x1: 0.0, y1: 0.0, x2: 10.0, y2: 10.0, score: 0.9, class_id: 0 },
+            // IoU 81/119 (~0.68) with the first box
+            Detection { x1: 1.0, y1: 1.0, x2: 11.0, y2: 11.0, score: 0.8, class_id: 0 },
+            // Same overlap, other class
+            Detection { x1: 1.0, y1: 1.0, x2: 11.0, y2: 11.0, score: 0.7, class_id: 2 },
+            Detection { x1: 50.0, y1: 50.0, x2: 60.0, y2: 60.0, score: 0.2, class_id: 0 },
+        ]
+    }
+
+    #[test]
+    fn test_detection_threshold_and_class_filter() {
+        let keep_all = Postprocessor::filter_detections(&boxes(), &DetectionFilter::default());
+        assert_eq!(keep_all.len(), 4);
+
+        let thresholded = DetectionFilter { conf_threshold: 0.5, ..Default::default() };
+        let scores: Vec<f32> = Postprocessor::filter_detections(&boxes(), &thresholded).iter().map(|d| d.score).collect();
+        assert_eq!(scores, vec![0.9, 0.8, 0.7]);
+
+        let class_two = DetectionFilter { classes: Some(vec![2]), ..Default::default() };
+        let kept = Postprocessor::filter_detections(&boxes(), &class_two);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].class_id, 2);
+    }
+
+    #[test]
+    fn test_detection_nms_suppresses_same_class_overlaps() {
+        let nms = DetectionFilter { iou_threshold: 0.5, ..Default::default() };
+        let scores: Vec<f32> = Postprocessor::filter_detections(&boxes(), &nms).iter().map(|d| d.score).collect();
+        assert_eq!(scores, vec![0.9, 0.7, 0.2]);
+
+        // Above the overlap, nothing is suppressed
+        let loose = DetectionFilter { iou_threshold: 0.7, ..Default::default() };
+        assert_eq!(Postprocessor::filter_detections(&boxes(), &loose).len(), 4);
+    }
+}
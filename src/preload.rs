@@ -0,0 +1,105 @@
+/// Up-front loading of the models listed in a manifest into the multi-model cache
+use crate::config::ExecutionProviderKind;
+use crate::errors::{InferenceError, InferenceResult};
+use crate::inference::InferenceEngine;
+use crate::types::PreloadReport;
+use std::path::Path;
+
+/// Manifest-driven model preloader
+pub struct Preloader;
+
+impl Preloader {
+    /// Load every `modelPath[,providers]` line of the manifest in order, where `providers` is an
+    /// optional fallback chain such as `nnapi|xnnpack|cpu` (without one, the configured providers are
+    /// used). Relative paths are resolved against the manifest's directory; blank lines and lines
+    /// starting with `#` are ignored. A failed entry is reported and doesn't stop the others.
+    ///
+    /// The last model loaded becomes the active one, and the cache keeps only the newest
+    /// `MAX_CACHED_MODELS`, so longer manifests evict their first entries.
+    pub fn preload_manifest(manifest_path: &str) -> InferenceResult<PreloadReport> {
+        let manifest = std::fs::read_to_string(manifest_path).map_err(|e| {
+            InferenceError::model_loading_failed(format!("Failed to read manifest '{}': {}", manifest_path, e))
+        })?;
+        let base_dir = Path::new(manifest_path).parent().unwrap_or(Path::new(""));
+
+        let mut report = PreloadReport::default();
+        for line in manifest.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (model_path, providers) = match Self::parse_line(line) {
+                Ok(entry) => entry,
+                Err(reason) => {
+                    report.failed.push((line.to_string(), reason));
+                    continue;
+                }
+            };
+
+            let model_path = base_dir.join(model_path).to_string_lossy().into_owned();
+            let loaded = match providers {
+                Some(providers) => InferenceEngine::load_model_with_providers(&model_path, providers),
+                None => InferenceEngine::load_model(&model_path)
+                    .map(|()| InferenceEngine::active_execution_provider().unwrap_or_default()),
+            };
+            match loaded {
+                Ok(provider) => report.loaded.push((model_path, provider)),
+                Err(e) => report.failed.push((line.to_string(), e.to_string())),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Split `modelPath[,providers]` on the last comma; a path containing commas needs a provider chain
+    fn parse_line(line: &str) -> Result<(&str, Option<Vec<ExecutionProviderKind>>), String> {
+        let Some((model_path, chain)) = line.rsplit_once(',') else {
+            return Ok((line, None));
+        };
+        let providers = chain
+            .split('|')
+            .map(|name| match name.trim().to_ascii_lowercase().as_str() {
+                "cpu" => Ok(ExecutionProviderKind::Cpu),
+                "nnapi" => Ok(ExecutionProviderKind::Nnapi),
+                "xnnpack" => Ok(ExecutionProviderKind::Xnnpack),
+                other => Err(format!("unknown execution provider '{}'", other)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((model_path.trim(), Some(providers)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{lock_global_state, onnx, require_ort, write_temp_file};
+
+    #[test]
+    fn test_bad_entries_are_isolated() {
+        let _guard = lock_global_state();
+        let manifest = write_temp_file("preload_missing.txt", b"# models\nmissing_a.onnx\n\nmissing_b.onnx,tpu\n");
+
+        let report = Preloader::preload_manifest(&manifest).unwrap();
+        assert!(report.loaded.is_empty());
+        assert_eq!(report.failed.len(), 2);
+        assert!(report.failed[0].1.contains("Model file not found"));
+        assert_eq!(report.failed[1].1, "unknown execution provider 'tpu'");
+        assert!(Preloader::preload_manifest("/missing/manifest.txt").is_err());
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_one_good_and_one_missing_model() {
+        require_ort!();
+        let _guard = lock_global_state();
+        InferenceEngine::clear_cache();
+
+        let model_path = write_temp_file("preload_good.onnx", &onnx::tiny_classifier(1000));
+        let manifest = write_temp_file(
+            "preload_manifest.txt",
+            format!("{},nnapi|cpu\nmissing.onnx\n", model_path).as_bytes(),
+        );
+
+        let report = Preloader::preload_manifest(&manifest).unwrap();
+        assert_eq!(report.loaded.len(), 1);
+        assert_eq!(report.loaded[0].0, model_path);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(InferenceEngine::loaded_models(), vec![model_path]);
+    }
+}
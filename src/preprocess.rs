@@ -0,0 +1,1005 @@
+/// Image preprocessing: decoding, resizing and normalization into NCHW tensors
+use crate::config::{DataOrder, Normalization, PreprocessConfig, QuantParams, ResizeMode};
+use crate::constants::{IMAGE_HEIGHT, IMAGE_WIDTH, LETTERBOX_FILL, LUMA_ONE, LUMA_WEIGHTS, MAX_IMAGE_DIMENSION};
+use crate::errors::{InferenceError, InferenceResult};
+use crate::tta::{Tta, TtaMode};
+use crate::types::TensorStats;
+use image::{DynamicImage, RgbImage};
+use image::imageops::FilterType;
+use image::io::{Limits, Reader};
+use ndarray::Array4;
+use std::io::Cursor;
+use std::sync::Mutex;
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Scale and padding applied by a letterbox resize (for mapping outputs back)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LetterboxInfo {
+    pub scale: f32,
+    pub pad_x: u32,
+    pub pad_y: u32,
+    /// Size of the letterboxed region: the ROI when one was cropped first, else the whole image
+    pub orig_width: u32,
+    pub orig_height: u32,
+    /// Top-left of that region within the decoded image (0, 0 without an ROI)
+    pub offset_x: u32,
+    pub offset_y: u32,
+}
+
+/// Byte order of packed 8-bit, 4-channel pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PixelFormat {
+    /// `R, G, B, A` per pixel (`Bitmap.copyPixelsToBuffer` on an `ARGB_8888` bitmap)
+    Rgba,
+    /// `B, G, R, A` per pixel (e.g. BGRA8888 surfaces)
+    Bgra,
+    /// `A, R, G, B` per pixel (`Bitmap.getPixels` ints written big-endian)
+    Argb,
+}
+
+impl PixelFormat {
+    /// Map a JNI format code to a pixel format
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(PixelFormat::Rgba),
+            1 => Some(PixelFormat::Bgra),
+            2 => Some(PixelFormat::Argb),
+            _ => None,
+        }
+    }
+
+    /// Reorder one pixel into `[R, G, B, A]`
+    fn to_rgba(self, [p0, p1, p2, p3]: [u8; 4]) -> [u8; 4] {
+        match self {
+            PixelFormat::Rgba => [p0, p1, p2, p3],
+            PixelFormat::Bgra => [p2, p1, p0, p3],
+            PixelFormat::Argb => [p1, p2, p3, p0],
+        }
+    }
+}
+
+/// Image handed to preprocessing: an encoded file, or packed pixels in a known format
+#[derive(Debug, Clone, Copy)]
+pub enum ImageInput<'a> {
+    Encoded(&'a [u8]),
+    Pixels {
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+    },
+}
+
+/// Region of interest in decoded image pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Roi {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Roi {
+    /// Build a ROI from JNI ints; rejects negative coordinates and empty sizes
+    pub fn from_signed(x: i32, y: i32, width: i32, height: i32) -> Option<Self> {
+        if x < 0 || y < 0 || width <= 0 || height <= 0 {
+            return None;
+        }
+        Some(Self {
+            x: x as u32,
+            y: y as u32,
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+}
+
+/// Quantized tensor values, matching the model's integer input type
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuantizedData {
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+}
+
+/// A quantized `[1, 3, H, W]` input tensor
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedTensor {
+    pub shape: [usize; 4],
+    pub data: QuantizedData,
+}
+
+/// A preprocessed tensor together with what preprocessing learned about the image, returned by
+/// value so concurrent callers never read each other's metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prepared<T> {
+    pub tensor: T,
+    /// Width and height of the image as loaded, before any ROI crop or resize
+    pub source_size: (u32, u32),
+    /// Scale and padding, when the image was letterboxed
+    pub letterbox: Option<LetterboxInfo>,
+}
+
+/// Persistent RGB canvas reused across letterbox calls to avoid per-frame allocation
+static LETTERBOX_CANVAS: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// Statistics of the last recorded float input tensor
+static LAST_INPUT_STATS: Mutex<Option<TensorStats>> = Mutex::new(None);
+
+/// Number of times the letterbox canvas had to be (re)allocated
+#[cfg(test)]
+static CANVAS_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Image preprocessor for ImageNet-style models
+pub struct ImagePreprocessor;
+
+impl ImagePreprocessor {
+    /// Decode image bytes and convert them into a normalized `[1, 3, H, W]` tensor (for tests; runs
+    /// go through `InferenceEngine::prepare_input`, which follows the model's input spec)
+    #[cfg(test)]
+    pub fn preprocess_image(image_bytes: &[u8], config: &PreprocessConfig) -> InferenceResult<Array4<f32>> {
+        Self::preprocess_input(ImageInput::Encoded(image_bytes), None, config).map(|prepared| prepared.tensor)
+    }
+
+    /// Load an encoded or raw-pixel image, optionally crop it to `roi` before resizing, and normalize it.
+    /// Nothing is recorded for the `last_*` accessors; see `record`.
+    pub fn preprocess_input(input: ImageInput, roi: Option<Roi>, config: &PreprocessConfig) -> InferenceResult<Prepared<Array4<f32>>> {
+        let mut img = Self::load(input, config)?;
+        let source_size = (img.width(), img.height());
+        if let Some(roi) = roi {
+            img = Self::crop(&img, roi)?;
+        }
+        let (tensor, letterbox) = Self::preprocess_decoded(&img, config);
+        Ok(Prepared { tensor, source_size, letterbox: Self::at_roi(letterbox, roi) })
+    }
+
+    /// Decode once and produce one normalized tensor per test-time augmentation view
+    pub fn preprocess_tta(
+        image_bytes: &[u8],
+        mode: TtaMode,
+        config: &PreprocessConfig,
+    ) -> InferenceResult<Prepared<Vec<Array4<f32>>>> {
+        Self::tta_views(image_bytes, mode, config, |view| Self::preprocess_decoded(view, config).0)
+    }
+
+    /// Decode once and quantize one tensor per test-time augmentation view, as
+    /// `preprocess_image_quantized` does for a single image
+    pub fn preprocess_tta_quantized(
+        image_bytes: &[u8],
+        mode: TtaMode,
+        config: &PreprocessConfig,
+        quant: QuantParams,
+        signed: bool,
+    ) -> InferenceResult<Prepared<Vec<QuantizedTensor>>> {
+        Self::tta_views(image_bytes, mode, config, |view| {
+            Self::with_prepared_rgb(view, config, |pixels, width, height, normalization| {
+                Self::quantize_rgb(pixels, width, height, normalization, config.data_order, quant, signed)
+            })
+            .0
+        })
+    }
+
+    /// Decode `image_bytes` once, then crop (and flip) each augmentation view and hand it to `f`
+    fn tta_views<T>(
+        image_bytes: &[u8],
+        mode: TtaMode,
+        config: &PreprocessConfig,
+        f: impl Fn(&DynamicImage) -> T,
+    ) -> InferenceResult<Prepared<Vec<T>>> {
+        let img = Self::load(ImageInput::Encoded(image_bytes), config)?;
+        let tensor = Tta::views(img.width(), img.height(), mode)
+            .into_iter()
+            .map(|view| {
+                let crop = Self::crop(&img, view.roi)?;
+                let crop = if view.flip { crop.fliph() } else { crop };
+                Ok(f(&crop))
+            })
+            .collect::<InferenceResult<_>>()?;
+        Ok(Prepared { tensor, source_size: (img.width(), img.height()), letterbox: None })
+    }
+
+    /// Decode, resize and quantize straight to integers in one pass (no intermediate f32 tensor).
+    /// `signed` selects int8 output, otherwise uint8.
+    pub fn preprocess_image_quantized(
+        input: ImageInput,
+        roi: Option<Roi>,
+        config: &PreprocessConfig,
+        quant: QuantParams,
+        signed: bool,
+    ) -> InferenceResult<Prepared<QuantizedTensor>> {
+        let mut img = Self::load(input, config)?;
+        let source_size = (img.width(), img.height());
+        if let Some(roi) = roi {
+            img = Self::crop(&img, roi)?;
+        }
+
+        let (tensor, letterbox) = Self::with_prepared_rgb(&img, config, |pixels, width, height, normalization| {
+            Self::quantize_rgb(pixels, width, height, normalization, config.data_order, quant, signed)
+        });
+        Ok(Prepared { tensor, source_size, letterbox: Self::at_roi(letterbox, roi) })
+    }
+
+    /// Place a letterbox transform of an ROI crop at the ROI's origin in the decoded image
+    fn at_roi(letterbox: Option<LetterboxInfo>, roi: Option<Roi>) -> Option<LetterboxInfo> {
+        let (offset_x, offset_y) = roi.map_or((0, 0), |roi| (roi.x, roi.y));
+        letterbox.map(|info| LetterboxInfo { offset_x, offset_y, ..info })
+    }
+
+    /// Keep a recorded input's statistics for `last_input_stats`. Only recorded runs call this, so
+    /// pure runs and dry runs leave them untouched; `stats` is `None` for a quantized input.
+    pub fn record(stats: Option<TensorStats>) {
+        if let Ok(mut last) = LAST_INPUT_STATS.lock() {
+            *last = stats;
+        }
+    }
+
+    /// Names of the image formats this build can decode. The always-on decoders match the `image`
+    /// features in Cargo.toml; optional ones follow the crate's feature flags.
+    pub fn supported_formats() -> Vec<&'static str> {
+        let mut formats = vec![
+            "jpeg", "png", "gif", "bmp", "pnm", "ico", "tga", "tiff", "dds", "hdr", "openexr", "qoi", "farbfeld",
+        ];
+        if cfg!(feature = "webp") {
+            formats.push("webp");
+        }
+        formats
+    }
+
+    /// Decode or unpack an image within the size limits. Transparent images are flattened onto
+    /// `config.alpha_background` when one is set.
+    fn load(input: ImageInput, config: &PreprocessConfig) -> InferenceResult<DynamicImage> {
+        let img = match input {
+            ImageInput::Encoded(image_bytes) => Self::decode(image_bytes, config.max_image_pixels)?,
+            ImageInput::Pixels { data, width, height, format } => {
+                Self::check_image_size(width, height, config.max_image_pixels)?;
+                Self::unpack_pixels(data, width, height, format)?
+            }
+        };
+
+        Ok(match config.alpha_background {
+            Some(background) if img.color().has_alpha() => DynamicImage::ImageRgb8(Self::flatten_alpha(&img, background)),
+            _ => img,
+        })
+    }
+
+    /// Reorder packed 4-byte pixels into an RGBA image
+    fn unpack_pixels(data: &[u8], width: u32, height: u32, format: PixelFormat) -> InferenceResult<DynamicImage> {
+        let expected = width as usize * height as usize * 4;
+        if data.len() != expected {
+            return Err(InferenceError::invalid_image(format!(
+                "Pixel buffer has {} bytes, {}x{} {:?} needs {}",
+                data.len(),
+                width,
+                height,
+                format,
+                expected
+            )));
+        }
+
+        let rgba: Vec<u8> = data
+            .chunks_exact(4)
+            .flat_map(|pixel| format.to_rgba([pixel[0], pixel[1], pixel[2], pixel[3]]))
+            .collect();
+        image::RgbaImage::from_raw(width, height, rgba)
+            .map(DynamicImage::ImageRgba8)
+            .ok_or_else(|| InferenceError::invalid_image("Pixel buffer does not match its dimensions"))
+    }
+
+    /// Load image from bytes, rejecting images over the size limits before decoding the pixels
+    fn decode(image_bytes: &[u8], max_pixels: u64) -> InferenceResult<DynamicImage> {
+        let reader = || {
+            Reader::new(Cursor::new(image_bytes))
+                .with_guessed_format()
+                .map_err(|e| InferenceError::invalid_image(format!("Failed to read image header: {}", e)))
+        };
+
+        // Only the header is parsed here, so a forged size costs nothing
+        let (width, height) = reader()?
+            .into_dimensions()
+            .map_err(|e| InferenceError::invalid_image(format!("Failed to read image dimensions: {}", e)))?;
+        Self::check_image_size(width, height, max_pixels)?;
+
+        // Enforce the same bounds inside the decoder in case the header understated the size
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_IMAGE_DIMENSION);
+        limits.max_image_height = Some(MAX_IMAGE_DIMENSION);
+        let mut reader = reader()?;
+        reader.limits(limits);
+        reader
+            .decode()
+            .map_err(|e| InferenceError::invalid_image(format!("Failed to load image from bytes: {}", e)))
+    }
+
+    /// Composite an image onto a solid background using its alpha channel
+    fn flatten_alpha(img: &DynamicImage, background: [u8; 3]) -> RgbImage {
+        let rgba = img.to_rgba8();
+        RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let [r, g, b, a] = rgba.get_pixel(x, y).0;
+            let blend = |color: u8, back: u8| {
+                ((u32::from(color) * u32::from(a) + u32::from(back) * (255 - u32::from(a)) + 127) / 255) as u8
+            };
+            image::Rgb([blend(r, background[0]), blend(g, background[1]), blend(b, background[2])])
+        })
+    }
+
+    /// Reject images whose declared dimensions exceed the decode limits
+    fn check_image_size(width: u32, height: u32, max_pixels: u64) -> InferenceResult<()> {
+        if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+            return Err(InferenceError::invalid_image(format!(
+                "Image dimensions {}x{} exceed the maximum of {} per side",
+                width, height, MAX_IMAGE_DIMENSION
+            )));
+        }
+
+        let pixels = u64::from(width) * u64::from(height);
+        if pixels > max_pixels {
+            return Err(InferenceError::invalid_image(format!(
+                "Image has {} pixels ({}x{}), limit is {}",
+                pixels, width, height, max_pixels
+            )));
+        }
+        Ok(())
+    }
+
+    /// Resize and normalize an already decoded image; also returns the letterbox transform, if any
+    fn preprocess_decoded(img: &DynamicImage, config: &PreprocessConfig) -> (Array4<f32>, Option<LetterboxInfo>) {
+        Self::with_prepared_rgb(img, config, |pixels, width, height, normalization| {
+            if config.grayscale {
+                Self::normalize_luma(pixels, width, height, normalization)
+            } else {
+                Self::normalize_rgb(pixels, width, height, normalization, config.data_order)
+            }
+        })
+    }
+
+    /// Resize the image (through the preprocessing pipeline, if one is set, or else per the resize
+    /// mode) and hand the packed RGB8 pixels, their size and the normalization to apply to `f`.
+    /// Returns `f`'s result with the letterbox transform, when the resize letterboxed.
+    fn with_prepared_rgb<R>(
+        img: &DynamicImage,
+        config: &PreprocessConfig,
+        f: impl FnOnce(&[u8], u32, u32, &Normalization) -> R,
+    ) -> (R, Option<LetterboxInfo>) {
+        if let Some(pipeline) = &config.pipeline {
+            let (resized, normalization) = pipeline.apply(img);
+            let normalization = Self::effective_normalization(resized.as_raw(), config, normalization);
+            return (f(resized.as_raw(), resized.width(), resized.height(), &normalization), None);
+        }
+        Self::with_resized_rgb(img, config.resize_mode, |pixels, width, height| {
+            let normalization = Self::effective_normalization(pixels, config, config.normalization);
+            f(pixels, width, height, &normalization)
+        })
+    }
+
+    /// `normalization`, or with per-image standardization one built from the resized pixels' own
+    /// mean and std (over every channel, or over luma for grayscale input). As in
+    /// `tf.image.per_image_standardization`, std is floored at `1 / sqrt(N)` so a flat image maps
+    /// to zeros rather than dividing by zero.
+    fn effective_normalization(pixels: &[u8], config: &PreprocessConfig, normalization: Normalization) -> Normalization {
+        if !config.per_image_standardization {
+            return normalization;
+        }
+
+        let (mut count, mut sum, mut sum_sq) = (0usize, 0f64, 0f64);
+        let mut add = |value: f64| {
+            count += 1;
+            sum += value;
+            sum_sq += value * value;
+        };
+        for pixel in pixels.chunks_exact(3) {
+            if config.grayscale {
+                let luma: u32 = pixel.iter().zip(LUMA_WEIGHTS).map(|(&p, w)| u32::from(p) * w).sum();
+                add(f64::from(luma) / f64::from(LUMA_ONE));
+            } else {
+                pixel.iter().for_each(|&p| add(f64::from(p)));
+            }
+        }
+
+        let count = count.max(1) as f64;
+        let mean = sum / count;
+        let std = (sum_sq / count - mean * mean).max(0.0).sqrt().max(1.0 / count.sqrt());
+        Normalization {
+            mean: [mean as f32; 3],
+            std: [std as f32; 3],
+            scale: 1.0,
+        }
+    }
+
+    /// Get min/max/mean/std of the last recorded float input tensor
+    pub fn last_input_stats() -> Option<TensorStats> {
+        *LAST_INPUT_STATS.lock().ok()?
+    }
+
+    /// Resize according to `mode` and hand the packed RGB8 pixels and their size to `f`; the
+    /// letterbox transform is returned alongside `f`'s result in letterbox mode
+    fn with_resized_rgb<R>(
+        img: &DynamicImage,
+        mode: ResizeMode,
+        f: impl FnOnce(&[u8], u32, u32) -> R,
+    ) -> (R, Option<LetterboxInfo>) {
+        if mode == ResizeMode::Letterbox {
+            let (result, info) = Self::letterbox(img, f);
+            return (result, Some(info));
+        }
+
+        if let ResizeMode::PadToSquare(fill) = mode {
+            let squared = DynamicImage::ImageRgb8(Self::pad_to_square(img, fill));
+            let resized = Self::resize(&squared, mode).to_rgb8();
+            return (f(resized.as_raw(), IMAGE_WIDTH, IMAGE_HEIGHT), None);
+        }
+
+        let resized = Self::resize(img, mode).to_rgb8();
+        let (width, height) = resized.dimensions();
+        (f(resized.as_raw(), width, height), None)
+    }
+
+    /// Crop to a region that must lie entirely within the image
+    fn crop(img: &DynamicImage, roi: Roi) -> InferenceResult<DynamicImage> {
+        let fits = roi.x.checked_add(roi.width).is_some_and(|right| right <= img.width())
+            && roi.y.checked_add(roi.height).is_some_and(|bottom| bottom <= img.height());
+        if !fits {
+            return Err(InferenceError::invalid_image(format!(
+                "ROI ({}, {}, {}x{}) is outside the {}x{} image",
+                roi.x,
+                roi.y,
+                roi.width,
+                roi.height,
+                img.width(),
+                img.height()
+            )));
+        }
+        Ok(img.crop_imm(roi.x, roi.y, roi.width, roi.height))
+    }
+
+    /// Warning for an image whose shorter side is below `min_dimension`: upscaling can't restore
+    /// detail that was never captured, so predictions on it are less reliable
+    pub fn resolution_warning(width: u32, height: u32, min_dimension: u32) -> Option<String> {
+        (width.min(height) < min_dimension).then(|| {
+            format!(
+                "Low-resolution input: {}x{} image is below the {}px minimum side; upscaling can't recover the lost detail, so predictions may be unreliable",
+                width, height, min_dimension
+            )
+        })
+    }
+
+    /// Resize preserving aspect ratio and center the result on a padded canvas.
+    /// Rows are copied straight into a reused buffer instead of compositing a new image per frame.
+    fn letterbox<R>(img: &DynamicImage, f: impl FnOnce(&[u8], u32, u32) -> R) -> (R, LetterboxInfo) {
+        let (orig_width, orig_height) = (img.width(), img.height());
+        let (width, height) = Self::target_size(orig_width, orig_height, ResizeMode::Letterbox);
+        let resized = img.resize_exact(width, height, FilterType::Lanczos3).to_rgb8();
+
+        let pad_x = (IMAGE_WIDTH - width) / 2;
+        let pad_y = (IMAGE_HEIGHT - height) / 2;
+
+        let mut canvas = LETTERBOX_CANVAS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let canvas_len = (IMAGE_WIDTH * IMAGE_HEIGHT * 3) as usize;
+        if canvas.len() != canvas_len {
+            *canvas = vec![LETTERBOX_FILL; canvas_len];
+            #[cfg(test)]
+            CANVAS_ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        } else {
+            canvas.fill(LETTERBOX_FILL);
+        }
+
+        let row_len = (width * 3) as usize;
+        for (y, row) in resized.as_raw().chunks_exact(row_len).enumerate() {
+            let start = (((y as u32 + pad_y) * IMAGE_WIDTH + pad_x) * 3) as usize;
+            canvas[start..start + row_len].copy_from_slice(row);
+        }
+
+        let info = LetterboxInfo {
+            scale: width as f32 / orig_width as f32,
+            pad_x,
+            pad_y,
+            orig_width,
+            orig_height,
+            offset_x: 0,
+            offset_y: 0,
+        };
+        (f(&canvas, IMAGE_WIDTH, IMAGE_HEIGHT), info)
+    }
+
+    /// Center the image on a `fill`-colored square canvas whose side is its longer side
+    fn pad_to_square(img: &DynamicImage, fill: [u8; 3]) -> RgbImage {
+        let (width, height) = (img.width(), img.height());
+        let side = width.max(height);
+        let mut canvas = RgbImage::from_pixel(side, side, image::Rgb(fill));
+        image::imageops::replace(
+            &mut canvas,
+            &img.to_rgb8(),
+            i64::from((side - width) / 2),
+            i64::from((side - height) / 2),
+        );
+        canvas
+    }
+
+    /// Compute the output size for a resize mode given the source dimensions
+    pub fn target_size(width: u32, height: u32, mode: ResizeMode) -> (u32, u32) {
+        match mode {
+            ResizeMode::Stretch | ResizeMode::PadToSquare(_) => (IMAGE_WIDTH, IMAGE_HEIGHT),
+            ResizeMode::ResizeShortestSide(side) => {
+                let scale = side as f32 / width.min(height).max(1) as f32;
+                if width <= height {
+                    (side, ((height as f32 * scale).round() as u32).max(1))
+                } else {
+                    (((width as f32 * scale).round() as u32).max(1), side)
+                }
+            }
+            ResizeMode::ModelInput { height: fixed_h, width: fixed_w, stride } => {
+                let snap = |len: f32| ((len / stride as f32).round() as u32).max(1) * stride;
+                let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+                match (fixed_w, fixed_h) {
+                    (Some(w), Some(h)) => (w, h),
+                    (None, Some(h)) => (snap(width * h as f32 / height), h),
+                    (Some(w), None) => (w, snap(height * w as f32 / width)),
+                    // Both dynamic: shorter side to the standard input size, keeping aspect ratio
+                    (None, None) => {
+                        let scale = IMAGE_HEIGHT as f32 / width.min(height);
+                        (snap(width * scale), snap(height * scale))
+                    }
+                }
+            }
+            ResizeMode::Letterbox => {
+                let scale = (IMAGE_WIDTH as f32 / width.max(1) as f32).min(IMAGE_HEIGHT as f32 / height.max(1) as f32);
+                (
+                    ((width as f32 * scale).round() as u32).clamp(1, IMAGE_WIDTH),
+                    ((height as f32 * scale).round() as u32).clamp(1, IMAGE_HEIGHT),
+                )
+            }
+        }
+    }
+
+    /// Resize the decoded image according to the configured mode
+    fn resize(img: &DynamicImage, mode: ResizeMode) -> DynamicImage {
+        let (width, height) = Self::target_size(img.width(), img.height(), mode);
+        img.resize_exact(width, height, FilterType::Lanczos3)
+    }
+
+    /// Reverse normalization of a `[1, 3, H, W]` (or `[1, 1, H, W]`) tensor laid out in `order` back
+    /// into a viewable RGB image
+    pub fn denormalize_to_rgb(tensor: &Array4<f32>, normalization: &Normalization, order: DataOrder) -> RgbImage {
+        let (height, width) = (tensor.shape()[2], tensor.shape()[3]);
+        let Normalization { mean, std, scale } = *normalization;
+
+        // Single-channel (luma) tensors are shown as gray
+        let last_channel = tensor.shape()[1] - 1;
+        let order = if last_channel == 0 { DataOrder::Planar } else { order };
+        let values: Vec<f32> = tensor.iter().copied().collect();
+
+        RgbImage::from_fn(width as u32, height as u32, |x, y| {
+            let pixel = y as usize * width + x as usize;
+            let channel = |c: usize| {
+                let c = c.min(last_channel);
+                let value = (values[order.index(pixel, c, width * height)] * std[c] + mean[c]) / scale;
+                value.round().clamp(0.0, 255.0) as u8
+            };
+            image::Rgb([channel(0), channel(1), channel(2)])
+        })
+    }
+
+    /// Normalize packed RGB8 pixels into a `[1, 3, H, W]` tensor whose values are laid out in `order`
+    fn normalize_rgb(pixels: &[u8], width: u32, height: u32, normalization: &Normalization, order: DataOrder) -> Array4<f32> {
+        let Normalization { mean, std, scale } = *normalization;
+        let plane = (width * height) as usize;
+        let mut data = vec![0.0f32; plane * 3];
+
+        for (i, pixel) in pixels.chunks_exact(3).enumerate() {
+            for c in 0..3 {
+                data[order.index(i, c, plane)] = (pixel[c] as f32 * scale - mean[c]) / std[c];
+            }
+        }
+
+        Array4::from_shape_vec((1, 3, height as usize, width as usize), data).expect("three values per pixel")
+    }
+
+    /// Convert packed RGB8 pixels to BT.601 luma and normalize into a `[1, 1, H, W]` tensor in one pass.
+    /// Luma is a 16-bit fixed-point weighted sum, so scaling and normalization fold into one multiply-add.
+    fn normalize_luma(pixels: &[u8], width: u32, height: u32, normalization: &Normalization) -> Array4<f32> {
+        let Normalization { mean, std, scale } = *normalization;
+        let gain = scale / (LUMA_ONE as f32 * std[0]);
+        let offset = -mean[0] / std[0];
+
+        let data: Vec<f32> = pixels
+            .chunks_exact(3)
+            .map(|pixel| {
+                let luma = LUMA_WEIGHTS[0] * u32::from(pixel[0])
+                    + LUMA_WEIGHTS[1] * u32::from(pixel[1])
+                    + LUMA_WEIGHTS[2] * u32::from(pixel[2]);
+                luma as f32 * gain + offset
+            })
+            .collect();
+        Array4::from_shape_vec((1, 1, height as usize, width as usize), data)
+            .expect("one luma value per pixel")
+    }
+
+    /// Normalize and quantize packed RGB8 pixels into `[1, 3, H, W]` integers laid out in `order`,
+    /// via a per-channel lookup table
+    fn quantize_rgb(
+        pixels: &[u8],
+        width: u32,
+        height: u32,
+        normalization: &Normalization,
+        order: DataOrder,
+        quant: QuantParams,
+        signed: bool,
+    ) -> QuantizedTensor {
+        let Normalization { mean, std, scale } = *normalization;
+        let (min, max) = if signed { (-128, 127) } else { (0, 255) };
+        let mut lut = [[0i32; 256]; 3];
+        for (c, table) in lut.iter_mut().enumerate() {
+            for (value, entry) in table.iter_mut().enumerate() {
+                let normalized = (value as f32 * scale - mean[c]) / std[c];
+                *entry = ((normalized / quant.scale).round() as i32 + quant.zero_point).clamp(min, max);
+            }
+        }
+
+        // Values are already clamped to the target type's range, so narrowing the tables is exact
+        let plane = (width * height) as usize;
+        let data = if signed {
+            QuantizedData::I8(Self::scatter_lut(pixels, &lut.map(|table| table.map(|q| q as i8)), order, plane))
+        } else {
+            QuantizedData::U8(Self::scatter_lut(pixels, &lut.map(|table| table.map(|q| q as u8)), order, plane))
+        };
+        QuantizedTensor {
+            shape: [1, 3, height as usize, width as usize],
+            data,
+        }
+    }
+
+    /// Look every RGB pixel's channels up in `lut` and write them straight into a `plane * 3`
+    /// buffer laid out in `order`
+    fn scatter_lut<T: Copy + Default>(pixels: &[u8], lut: &[[T; 256]; 3], order: DataOrder, plane: usize) -> Vec<T> {
+        let mut data = vec![T::default(); plane * 3];
+        for (i, pixel) in pixels.chunks_exact(3).enumerate() {
+            for c in 0..3 {
+                data[order.index(i, c, plane)] = lut[c][pixel[c] as usize];
+            }
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NormalizationPreset;
+    use crate::constants::{IMAGENET_MEAN, IMAGENET_STD};
+    use crate::test_utils::{encode_png, lock_global_state};
+
+    #[test]
+    fn test_jpeg_and_png_always_supported() {
+        let formats = ImagePreprocessor::supported_formats();
+        assert!(formats.contains(&"jpeg"));
+        assert!(formats.contains(&"png"));
+        assert_eq!(formats.contains(&"webp"), cfg!(feature = "webp"));
+    }
+
+    #[test]
+    fn test_tiff_input_decodes() {
+        let img = image::DynamicImage::ImageRgb8(image::RgbImage::from_pixel(8, 8, image::Rgb([10, 20, 30])));
+        let mut bytes = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Tiff).unwrap();
+        let tensor = ImagePreprocessor::preprocess_image(&bytes, &PreprocessConfig::default()).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, IMAGE_HEIGHT as usize, IMAGE_WIDTH as usize]);
+        assert!(ImagePreprocessor::supported_formats().contains(&"tiff"));
+    }
+
+    #[test]
+    fn test_stretch_produces_square_tensor() {
+        let bytes = encode_png(320, 160, [10, 20, 30]);
+        let tensor = ImagePreprocessor::preprocess_image(&bytes, &PreprocessConfig::default()).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, IMAGE_HEIGHT as usize, IMAGE_WIDTH as usize]);
+    }
+
+    #[test]
+    fn test_per_image_standardization() {
+        let config = PreprocessConfig {
+            per_image_standardization: true,
+            ..PreprocessConfig::default()
+        };
+
+        let flat = ImagePreprocessor::preprocess_image(&encode_png(16, 16, [90, 90, 90]), &config).unwrap();
+        assert!(flat.iter().all(|&value| value == 0.0));
+
+        let gradient: Vec<u8> = (0..16 * 16).flat_map(|i| [(i % 16 * 16) as u8, 128, 255 - (i / 16 * 16) as u8, 255]).collect();
+        let input = ImageInput::Pixels { data: &gradient, width: 16, height: 16, format: PixelFormat::Rgba };
+        let tensor = ImagePreprocessor::preprocess_input(input, None, &config).unwrap().tensor;
+        let stats = TensorStats::from_values(tensor.as_slice().unwrap()).unwrap();
+        assert!(stats.mean.abs() < 1e-3, "mean {}", stats.mean);
+        assert!((stats.std - 1.0).abs() < 1e-3, "std {}", stats.std);
+    }
+
+    #[test]
+    fn test_interleaved_order_reorders_flat_buffer() {
+        let bytes = encode_png(8, 4, [10, 120, 250]);
+        let planar = ImagePreprocessor::preprocess_image(&bytes, &PreprocessConfig::default()).unwrap();
+        let config = PreprocessConfig {
+            data_order: DataOrder::Interleaved,
+            ..PreprocessConfig::default()
+        };
+        let interleaved = ImagePreprocessor::preprocess_image(&bytes, &config).unwrap();
+
+        assert_eq!(interleaved.shape(), planar.shape());
+        let (planar, interleaved) = (planar.into_raw_vec(), interleaved.into_raw_vec());
+        assert_ne!(planar, interleaved);
+        let plane = planar.len() / 3;
+        assert_eq!(interleaved[..3], [planar[0], planar[plane], planar[2 * plane]]);
+        assert_eq!(interleaved[3..6], interleaved[..3]);
+    }
+
+    #[test]
+    fn test_oversized_header_rejected_before_decode() {
+        // PPM header claiming 100000x100000 pixels with no pixel data behind it
+        let bytes = b"P6\n100000 100000\n255\n".to_vec();
+        let err = ImagePreprocessor::preprocess_image(&bytes, &PreprocessConfig::default()).unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidImageData(ref msg) if msg.contains("100000x100000")));
+
+        // Within the per-side limit but over a lowered pixel budget
+        let config = PreprocessConfig {
+            max_image_pixels: 100,
+            ..PreprocessConfig::default()
+        };
+        let err = ImagePreprocessor::preprocess_image(&encode_png(20, 10, [0, 0, 0]), &config).unwrap_err();
+        assert!(matches!(err, InferenceError::InvalidImageData(ref msg) if msg.contains("200 pixels")));
+    }
+
+    #[cfg(feature = "webp")]
+    #[test]
+    fn test_webp_input_decodes() {
+        // 1x1 lossless WebP
+        const WEBP: &[u8] = &[
+            82, 73, 70, 70, 26, 0, 0, 0, 87, 69, 66, 80, 86, 80, 56, 76, 13, 0, 0, 0, 47, 0, 0, 0, 16, 7, 16, 17, 17,
+            136, 136, 254, 7, 0,
+        ];
+        let tensor = ImagePreprocessor::preprocess_image(WEBP, &PreprocessConfig::default()).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, IMAGE_HEIGHT as usize, IMAGE_WIDTH as usize]);
+    }
+
+    #[test]
+    fn test_source_size_returned_before_resize() {
+        let _guard = lock_global_state();
+        let roi = Roi { x: 2, y: 3, width: 10, height: 5 };
+        let config = PreprocessConfig {
+            resize_mode: ResizeMode::Letterbox,
+            ..Default::default()
+        };
+        let prepared = ImagePreprocessor::preprocess_input(ImageInput::Encoded(&encode_png(37, 21, [1, 2, 3])), Some(roi), &config).unwrap();
+        assert_eq!(prepared.source_size, (37, 21));
+        let info = prepared.letterbox.unwrap();
+        assert_eq!((info.orig_width, info.orig_height), (10, 5));
+        assert_eq!((info.offset_x, info.offset_y), (2, 3));
+    }
+
+    #[test]
+    fn test_every_preprocessing_path_returns_source_size() {
+        let bytes = encode_png(300, 250, [1, 2, 3]);
+        let config = PreprocessConfig::default();
+
+        let tta = ImagePreprocessor::preprocess_tta(&bytes, TtaMode::TenCrop, &config).unwrap();
+        assert_eq!((tta.tensor.len(), tta.source_size), (10, (300, 250)));
+
+        let quant = QuantParams::new(1.0 / 255.0, 0).unwrap();
+        let tta = ImagePreprocessor::preprocess_tta_quantized(&bytes, TtaMode::TenCrop, &config, quant, true).unwrap();
+        assert_eq!((tta.tensor.len(), tta.source_size), (10, (300, 250)));
+        assert!(tta.tensor.iter().all(|view| matches!(view.data, QuantizedData::I8(_))));
+
+        let roi = Some(Roi { x: 0, y: 0, width: 40, height: 30 });
+        let quantized = ImagePreprocessor::preprocess_image_quantized(ImageInput::Encoded(&bytes), roi, &config, quant, false).unwrap();
+        assert_eq!(quantized.source_size, (300, 250));
+    }
+
+    #[test]
+    fn test_resolution_warning_checks_shorter_side() {
+        assert!(ImagePreprocessor::resolution_warning(64, 64, 32).is_none());
+        assert!(ImagePreprocessor::resolution_warning(32, 500, 32).is_none());
+        let warning = ImagePreprocessor::resolution_warning(500, 16, 32).unwrap();
+        assert!(warning.contains("500x16 image is below the 32px minimum side"));
+    }
+
+    #[test]
+    fn test_resize_shortest_side_preserves_aspect_ratio() {
+        let config = PreprocessConfig {
+            resize_mode: ResizeMode::ResizeShortestSide(100),
+            ..Default::default()
+        };
+
+        // Landscape: height is the shorter side
+        let bytes = encode_png(400, 200, [200, 100, 50]);
+        let tensor = ImagePreprocessor::preprocess_image(&bytes, &config).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, 100, 200]);
+
+        // Portrait: width is the shorter side
+        let bytes = encode_png(150, 300, [200, 100, 50]);
+        let tensor = ImagePreprocessor::preprocess_image(&bytes, &config).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, 200, 100]);
+    }
+
+    #[test]
+    fn test_letterbox_pads_and_returns_transform() {
+        let _guard = lock_global_state();
+        let config = PreprocessConfig {
+            resize_mode: ResizeMode::Letterbox,
+            ..Default::default()
+        };
+
+        let bytes = encode_png(448, 224, [255, 255, 255]);
+        let Prepared { tensor, letterbox, .. } = ImagePreprocessor::preprocess_input(ImageInput::Encoded(&bytes), None, &config).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, IMAGE_HEIGHT as usize, IMAGE_WIDTH as usize]);
+
+        let info = letterbox.unwrap();
+        assert_eq!(info.scale, 0.5);
+        assert_eq!((info.pad_x, info.pad_y), (0, 56));
+
+        // Top padding row holds the fill color, the image area holds white
+        let fill = (LETTERBOX_FILL as f32 / 255.0 - IMAGENET_MEAN[0]) / IMAGENET_STD[0];
+        let white = (1.0 - IMAGENET_MEAN[0]) / IMAGENET_STD[0];
+        assert!((tensor[[0, 0, 0, 100]] - fill).abs() < 1e-5);
+        assert!((tensor[[0, 0, 112, 100]] - white).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_pad_to_square_pads_symmetrically() {
+        let fill = [0, 255, 0];
+        let wide = image::load_from_memory(&encode_png(40, 20, [255, 0, 0])).unwrap();
+
+        let squared = ImagePreprocessor::pad_to_square(&wide, fill);
+        assert_eq!(squared.dimensions(), (40, 40));
+        assert_eq!(squared.get_pixel(20, 9).0, fill);
+        assert_eq!(squared.get_pixel(20, 10).0, [255, 0, 0]);
+        assert_eq!(squared.get_pixel(20, 29).0, [255, 0, 0]);
+        assert_eq!(squared.get_pixel(20, 30).0, fill);
+
+        let config = PreprocessConfig {
+            resize_mode: ResizeMode::from_code(4, 0x00FF00).unwrap(),
+            ..Default::default()
+        };
+        let tensor = ImagePreprocessor::preprocess_image(&encode_png(40, 20, [255, 0, 0]), &config).unwrap();
+        assert_eq!(tensor.shape(), &[1, 3, IMAGE_HEIGHT as usize, IMAGE_WIDTH as usize]);
+
+        // Green bands above and below a red center
+        let green = (1.0 - IMAGENET_MEAN[1]) / IMAGENET_STD[1];
+        let red = (1.0 - IMAGENET_MEAN[0]) / IMAGENET_STD[0];
+        assert!((tensor[[0, 1, 10, 112]] - green).abs() < 1e-5);
+        assert!((tensor[[0, 0, 112, 112]] - red).abs() < 1e-5);
+        assert!((tensor[[0, 1, 213, 112]] - green).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_alpha_composited_onto_background() {
+        let pixel = image::RgbaImage::from_pixel(1, 1, image::Rgba([200, 100, 0, 128]));
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgba8(pixel)
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+            .unwrap();
+        let normalization = Normalization::new([0.0; 3], [1.0; 3], 1.0).unwrap();
+
+        // Default: alpha is dropped
+        let config = PreprocessConfig { normalization, ..Default::default() };
+        let tensor = ImagePreprocessor::preprocess_image(&bytes, &config).unwrap();
+        assert_eq!([tensor[[0, 0, 0, 0]], tensor[[0, 1, 0, 0]], tensor[[0, 2, 0, 0]]], [200.0, 100.0, 0.0]);
+
+        // Onto white: 128/255 of the pixel plus 127/255 of white, rounded
+        let config = PreprocessConfig {
+            normalization,
+            alpha_background: Some([255, 255, 255]),
+            ..Default::default()
+        };
+        let tensor = ImagePreprocessor::preprocess_image(&bytes, &config).unwrap();
+        assert_eq!([tensor[[0, 0, 0, 0]], tensor[[0, 1, 0, 0]], tensor[[0, 2, 0, 0]]], [227.0, 177.0, 127.0]);
+    }
+
+    #[test]
+    fn test_pixel_formats_yield_identical_tensors() {
+        let (r, g, b, a) = (200, 120, 40, 255);
+        let layouts = [
+            (PixelFormat::Rgba, [r, g, b, a]),
+            (PixelFormat::Bgra, [b, g, r, a]),
+            (PixelFormat::Argb, [a, r, g, b]),
+        ];
+        let config = PreprocessConfig::default();
+
+        let tensors: Vec<Array4<f32>> = layouts
+            .iter()
+            .map(|&(format, pixel)| {
+                let data = pixel.repeat(6 * 4);
+                let input = ImageInput::Pixels { data: &data, width: 6, height: 4, format };
+                ImagePreprocessor::preprocess_input(input, None, &config).unwrap().tensor
+            })
+            .collect();
+        assert_eq!(tensors[0], tensors[1]);
+        assert_eq!(tensors[0], tensors[2]);
+        assert_eq!(tensors[0], ImagePreprocessor::preprocess_image(&encode_png(6, 4, [r, g, b]), &config).unwrap());
+
+        let short = ImageInput::Pixels { data: &[0; 8], width: 6, height: 4, format: PixelFormat::Rgba };
+        assert!(ImagePreprocessor::preprocess_input(short, None, &config).is_err());
+    }
+
+    #[test]
+    fn test_letterbox_reuses_canvas_buffer() {
+        let _guard = lock_global_state();
+        let config = PreprocessConfig {
+            resize_mode: ResizeMode::Letterbox,
+            ..Default::default()
+        };
+        let bytes = encode_png(300, 120, [30, 60, 90]);
+
+        // Warm up so the canvas exists regardless of test order
+        ImagePreprocessor::preprocess_image(&bytes, &config).unwrap();
+        let allocations = CANVAS_ALLOCATIONS.load(Ordering::SeqCst);
+
+        for _ in 0..10 {
+            ImagePreprocessor::preprocess_image(&bytes, &config).unwrap();
+        }
+        assert_eq!(CANVAS_ALLOCATIONS.load(Ordering::SeqCst), allocations);
+        assert!(allocations <= 1);
+    }
+
+    #[test]
+    fn test_grayscale_matches_reference_luma() {
+        let config = PreprocessConfig {
+            grayscale: true,
+            ..Default::default()
+        };
+        let Normalization { mean, std, scale } = config.normalization;
+
+        for [r, g, b] in [[0, 0, 0], [255, 255, 255], [10, 128, 240], [200, 40, 90]] {
+            let tensor = ImagePreprocessor::preprocess_image(&encode_png(64, 48, [r, g, b]), &config).unwrap();
+            assert_eq!(tensor.shape(), &[1, 1, 224, 224]);
+
+            let luma = r as f32 * 0.299 + g as f32 * 0.587 + b as f32 * 0.114;
+            let expected = (luma * scale - mean[0]) / std[0];
+            assert!(tensor.iter().all(|&v| (v - expected).abs() < 1e-4), "rgb {:?}", [r, g, b]);
+        }
+    }
+
+    #[test]
+    fn test_inception_preset_maps_to_unit_range() {
+        let config = PreprocessConfig {
+            normalization: NormalizationPreset::Inception.normalization(),
+            ..Default::default()
+        };
+
+        let black = ImagePreprocessor::preprocess_image(&encode_png(32, 32, [0, 0, 0]), &config).unwrap();
+        let white = ImagePreprocessor::preprocess_image(&encode_png(32, 32, [255, 255, 255]), &config).unwrap();
+        assert!(black.iter().all(|&v| (v + 1.0).abs() < 1e-5));
+        assert!(white.iter().all(|&v| (v - 1.0).abs() < 1e-5));
+
+        let mixed = ImagePreprocessor::preprocess_image(&encode_png(32, 32, [10, 128, 240]), &config).unwrap();
+        assert!(mixed.iter().all(|&v| (-1.0..=1.0).contains(&v)));
+    }
+
+    #[test]
+    fn test_model_input_fixed_height_dynamic_width() {
+        // OCR-style model input [1, 3, 32, -1]
+        let mode = ResizeMode::ModelInput { height: None, width: None, stride: 8 }.with_model_dims(&[1, 3, 32, -1]);
+        assert_eq!(mode, ResizeMode::ModelInput { height: Some(32), width: None, stride: 8 });
+
+        let config = PreprocessConfig {
+            resize_mode: mode,
+            ..Default::default()
+        };
+        let tensor = ImagePreprocessor::preprocess_image(&encode_png(500, 60, [0, 0, 0]), &config).unwrap();
+        // 500 * 32 / 60 = 266.7, snapped to the nearest multiple of 8
+        assert_eq!(tensor.shape(), &[1, 3, 32, 264]);
+    }
+
+    #[test]
+    fn test_quantized_matches_rounded_normalized_values() {
+        let bytes = encode_png(20, 10, [12, 130, 250]);
+        let config = PreprocessConfig::default();
+        let quant = QuantParams::new(0.02, 10).unwrap();
+
+        let reference = ImagePreprocessor::preprocess_image(&bytes, &config).unwrap();
+        let expected: Vec<i32> = reference.iter().map(|&v| ((v / 0.02).round() as i32 + 10).clamp(-128, 127)).collect();
+
+        let tensor = ImagePreprocessor::preprocess_image_quantized(ImageInput::Encoded(&bytes), None, &config, quant, true)
+            .unwrap()
+            .tensor;
+        assert_eq!(tensor.shape, [1, 3, IMAGE_HEIGHT as usize, IMAGE_WIDTH as usize]);
+        let QuantizedData::I8(values) = tensor.data else {
+            panic!("expected int8 data");
+        };
+        assert_eq!(values.iter().map(|&q| q as i32).collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,90 @@
+//! Checks whether an execution provider actually works here, without loading a user model
+use crate::types::ProviderProbeReport;
+use ort::execution_providers::{ExecutionProvider, NNAPIExecutionProvider};
+use ort::session::Session;
+use ort::value::Value;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Instant;
+
+/// `[1, 4]` float input through a single `Relu`, small enough to compile on any accelerator
+const PROBE_MODEL: &[u8] = include_bytes!("../assets/nnapi_probe.onnx");
+
+const PROBE_INPUT: [f32; 4] = [-1.0, 0.0, 0.5, 2.0];
+const PROBE_EXPECTED: [f32; 4] = [0.0, 0.0, 0.5, 2.0];
+
+/// Execution provider probes
+pub struct ProviderProbe;
+
+impl ProviderProbe {
+    /// Build and run the probe model on NNAPI. Panics (e.g. a broken driver or a missing ONNX Runtime
+    /// library) are caught and reported as failures.
+    pub fn nnapi() -> ProviderProbeReport {
+        let nnapi = NNAPIExecutionProvider::default();
+        let start = Instant::now();
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| Self::run(&nnapi)))
+            .unwrap_or_else(|payload| Err(format!("Probe panicked: {}", panic_message(payload.as_ref()))));
+
+        ProviderProbeReport {
+            provider: nnapi.name().to_string(),
+            available: outcome.is_ok(),
+            error: outcome.err(),
+            elapsed_ms: start.elapsed().as_secs_f32() * 1000.0,
+        }
+    }
+
+    fn run(provider: &impl ExecutionProvider) -> Result<(), String> {
+        let mut builder = Session::builder().map_err(|e| format!("Failed to create session builder: {}", e))?;
+        provider
+            .register(&mut builder)
+            .map_err(|e| format!("Failed to register {}: {}", provider.name(), e))?;
+        let mut session = builder
+            .commit_from_memory(PROBE_MODEL)
+            .map_err(|e| format!("Failed to build probe session: {}", e))?;
+
+        let input = Value::from_array(([1usize, 4], PROBE_INPUT.to_vec()))
+            .map_err(|e| format!("Failed to create probe input: {}", e))?;
+        let outputs = session
+            .run(ort::inputs!["input" => input])
+            .map_err(|e| format!("Probe run failed: {}", e))?;
+        let (_, output) = outputs["output"]
+            .try_extract_tensor::<f32>()
+            .map_err(|e| format!("Failed to read probe output: {}", e))?;
+
+        if output != PROBE_EXPECTED {
+            return Err(format!("Probe produced {:?}, expected {:?}", output, PROBE_EXPECTED));
+        }
+        Ok(())
+    }
+}
+
+/// Message of a caught panic payload
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "(non-string panic payload)".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::onnx;
+
+    #[test]
+    fn test_bundled_probe_model_matches_builder() {
+        // Regenerate assets/nnapi_probe.onnx if the builder changes
+        assert_eq!(PROBE_MODEL, onnx::nnapi_probe().as_slice());
+    }
+
+    #[test]
+    fn test_nnapi_unavailable_off_device() {
+        // Host ONNX Runtime builds have no NNAPI, and without ORT_DYLIB_PATH loading ORT itself panics
+        let report = ProviderProbe::nnapi();
+        assert!(!report.available);
+        assert!(!report.error.as_deref().unwrap_or_default().is_empty());
+        assert!(report.to_json().contains("\"available\":false"));
+    }
+}
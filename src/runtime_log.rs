@@ -0,0 +1,144 @@
+/// Capture of ONNX Runtime log messages into an in-memory ring buffer
+use crate::constants::RUNTIME_LOG_CAPACITY;
+use crate::errors::{InferenceError, InferenceResult};
+use ort::logging::LogLevel;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Static storage for recent ONNX Runtime log entries (oldest first)
+static RUNTIME_LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Whether the ORT environment has been created through `RuntimeLog::init`
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Minimum severity captured into the ring buffer
+static LOG_LEVEL: Mutex<LogLevel> = Mutex::new(LogLevel::Warning);
+
+/// ONNX Runtime log capture
+pub struct RuntimeLog;
+
+impl RuntimeLog {
+    /// Create the ORT environment with a logger that feeds the ring buffer.
+    /// Called before every session is built; only the first call creates the environment.
+    pub fn init() -> InferenceResult<bool> {
+        if INITIALIZED.load(Ordering::SeqCst) {
+            return Ok(false);
+        }
+
+        let created = ort::init()
+            .with_name("onnx_inference")
+            .with_logger(Box::new(|level, category, _id, code_location, message| {
+                Self::push(level, &format!("[{}] {} ({})", category, message, code_location));
+            }))
+            .commit()
+            .map_err(|e| InferenceError::session_failed(format!("Failed to initialize ONNX Runtime environment: {:?}", e)))?;
+
+        INITIALIZED.store(true, Ordering::SeqCst);
+        Self::apply_level_to_environment();
+        Ok(created)
+    }
+
+    /// Map a JNI level code (0 = verbose .. 4 = fatal) to an ORT log level
+    pub fn level_from_code(code: i32) -> Option<LogLevel> {
+        match code {
+            0 => Some(LogLevel::Verbose),
+            1 => Some(LogLevel::Info),
+            2 => Some(LogLevel::Warning),
+            3 => Some(LogLevel::Error),
+            4 => Some(LogLevel::Fatal),
+            _ => None,
+        }
+    }
+
+    /// Set the minimum captured severity, also updating ORT's environment if it exists
+    pub fn set_level(level: LogLevel) {
+        if let Ok(mut current) = LOG_LEVEL.lock() {
+            *current = level;
+        }
+        Self::apply_level_to_environment();
+    }
+
+    /// Get the minimum captured severity
+    pub fn level() -> LogLevel {
+        LOG_LEVEL.lock().map(|level| *level).unwrap_or(LogLevel::Warning)
+    }
+
+    /// Append a message if it meets the configured severity, evicting the oldest when full
+    pub fn push(level: LogLevel, message: &str) {
+        if level < Self::level() {
+            return;
+        }
+
+        if let Ok(mut log) = RUNTIME_LOG.lock() {
+            if log.len() >= RUNTIME_LOG_CAPACITY {
+                log.pop_front();
+            }
+            log.push_back(format!("{:?}: {}", level, message));
+        }
+    }
+
+    /// Get the captured entries, oldest first
+    pub fn entries() -> Vec<String> {
+        match RUNTIME_LOG.lock() {
+            Ok(log) => log.iter().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Drop all captured entries (mainly for testing)
+    #[cfg(test)]
+    pub fn clear() {
+        if let Ok(mut log) = RUNTIME_LOG.lock() {
+            log.clear();
+        }
+    }
+
+    fn apply_level_to_environment() {
+        // Only touch ORT once it has been loaded; probing would create a default environment
+        if INITIALIZED.load(Ordering::SeqCst)
+            && let Ok(environment) = ort::environment::get_environment()
+        {
+            environment.set_log_level(Self::level());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inference::InferenceEngine;
+    use crate::test_utils::{lock_global_state, onnx, require_ort, write_temp_file};
+
+    #[test]
+    fn test_ring_buffer_evicts_oldest_and_filters_level() {
+        let _guard = lock_global_state();
+        RuntimeLog::clear();
+        RuntimeLog::set_level(LogLevel::Warning);
+
+        RuntimeLog::push(LogLevel::Info, "ignored");
+        assert!(RuntimeLog::entries().is_empty());
+
+        for i in 0..RUNTIME_LOG_CAPACITY + 5 {
+            RuntimeLog::push(LogLevel::Error, &format!("message {}", i));
+        }
+        let entries = RuntimeLog::entries();
+        assert_eq!(entries.len(), RUNTIME_LOG_CAPACITY);
+        assert_eq!(entries[0], "Error: message 5");
+        RuntimeLog::clear();
+    }
+
+    #[test]
+    #[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+    fn test_log_captures_messages_after_load() {
+        require_ort!();
+        let _guard = lock_global_state();
+
+        RuntimeLog::set_level(LogLevel::Verbose);
+        let model_path = write_temp_file("runtime_log.onnx", &onnx::tiny_classifier(1000));
+        InferenceEngine::load_model(&model_path).unwrap();
+
+        assert!(!RuntimeLog::entries().is_empty());
+        RuntimeLog::set_level(LogLevel::Warning);
+    }
+}
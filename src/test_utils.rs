@@ -0,0 +1,282 @@
+//! Shared helpers for unit tests
+#![allow(dead_code)]
+
+use std::sync::{Mutex, MutexGuard};
+
+/// Serializes tests that touch the engine's global state (labels, cache, config)
+static GLOBAL_STATE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Acquire the global test lock, recovering if a previous test panicked while holding it
+pub fn lock_global_state() -> MutexGuard<'static, ()> {
+    GLOBAL_STATE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Encode a solid-color RGB image as PNG bytes
+pub fn encode_png(width: u32, height: u32, color: [u8; 3]) -> Vec<u8> {
+    let img = image::RgbImage::from_pixel(width, height, image::Rgb(color));
+    let mut bytes = Vec::new();
+    image::DynamicImage::ImageRgb8(img)
+        .write_to(&mut std::io::Cursor::new(&mut bytes), image::ImageOutputFormat::Png)
+        .expect("PNG encoding should succeed");
+    bytes
+}
+
+/// Whether an ONNX Runtime dylib is available (set ORT_DYLIB_PATH to run session-backed tests)
+pub fn ort_available() -> bool {
+    std::env::var("ORT_DYLIB_PATH")
+        .map(|path| std::path::Path::new(&path).exists())
+        .unwrap_or(false)
+}
+
+/// Fail the current test when ONNX Runtime can't be loaded on this host. Session-backed tests are
+/// `#[ignore]`d, so they only run when asked for:
+/// `ORT_DYLIB_PATH=/path/to/libonnxruntime.so cargo test -- --ignored`
+macro_rules! require_ort {
+    () => {
+        assert!(
+            crate::test_utils::ort_available(),
+            "ONNX Runtime not available: set ORT_DYLIB_PATH to libonnxruntime to run session-backed tests"
+        );
+    };
+}
+pub(crate) use require_ort;
+
+/// Write model bytes to a unique temp file and return its path
+pub fn write_temp_file(name: &str, bytes: &[u8]) -> String {
+    let path = std::env::temp_dir().join(format!("onnx_inference_{}_{}", std::process::id(), name));
+    std::fs::write(&path, bytes).expect("temp file write should succeed");
+    path.to_string_lossy().into_owned()
+}
+
+/// Minimal ONNX protobuf writer for building tiny test models in code
+pub mod onnx {
+    /// ONNX tensor element type for f32
+    pub const FLOAT: i64 = 1;
+
+    fn varint(buf: &mut Vec<u8>, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                buf.push(byte);
+                break;
+            }
+            buf.push(byte | 0x80);
+        }
+    }
+
+    fn key(buf: &mut Vec<u8>, field: u32, wire_type: u8) {
+        varint(buf, ((field as u64) << 3) | wire_type as u64);
+    }
+
+    fn int_field(buf: &mut Vec<u8>, field: u32, value: i64) {
+        key(buf, field, 0);
+        varint(buf, value as u64);
+    }
+
+    fn bytes_field(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+        key(buf, field, 2);
+        varint(buf, value.len() as u64);
+        buf.extend_from_slice(value);
+    }
+
+    fn str_field(buf: &mut Vec<u8>, field: u32, value: &str) {
+        bytes_field(buf, field, value.as_bytes());
+    }
+
+    /// A tensor dimension: fixed size or a named dynamic dimension
+    #[derive(Clone, Copy)]
+    pub enum Dim {
+        Fixed(i64),
+        Dynamic(&'static str),
+    }
+
+    /// Node attribute value
+    pub enum Attr {
+        Int(i64),
+        Ints(Vec<i64>),
+        Float(f32),
+    }
+
+    /// Builder for a single-graph ONNX model
+    #[derive(Default)]
+    pub struct ModelBuilder {
+        nodes: Vec<Vec<u8>>,
+        initializers: Vec<Vec<u8>>,
+        inputs: Vec<Vec<u8>>,
+        outputs: Vec<Vec<u8>>,
+    }
+
+    fn value_info(name: &str, elem_type: i64, dims: &[Dim]) -> Vec<u8> {
+        let mut shape = Vec::new();
+        for dim in dims {
+            let mut dim_buf = Vec::new();
+            match dim {
+                Dim::Fixed(value) => int_field(&mut dim_buf, 1, *value),
+                Dim::Dynamic(param) => str_field(&mut dim_buf, 2, param),
+            }
+            bytes_field(&mut shape, 1, &dim_buf);
+        }
+
+        let mut tensor_type = Vec::new();
+        int_field(&mut tensor_type, 1, elem_type);
+        bytes_field(&mut tensor_type, 2, &shape);
+
+        let mut type_proto = Vec::new();
+        bytes_field(&mut type_proto, 1, &tensor_type);
+
+        let mut info = Vec::new();
+        str_field(&mut info, 1, name);
+        bytes_field(&mut info, 2, &type_proto);
+        info
+    }
+
+    impl ModelBuilder {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Declare a float graph input
+        pub fn input(mut self, name: &str, dims: &[Dim]) -> Self {
+            self.inputs.push(value_info(name, FLOAT, dims));
+            self
+        }
+
+        /// Declare a float graph output
+        pub fn output(mut self, name: &str, dims: &[Dim]) -> Self {
+            self.outputs.push(value_info(name, FLOAT, dims));
+            self
+        }
+
+        /// Add a float initializer (constant weight)
+        pub fn initializer(mut self, name: &str, dims: &[i64], values: &[f32]) -> Self {
+            let mut tensor = Vec::new();
+            for &dim in dims {
+                int_field(&mut tensor, 1, dim);
+            }
+            int_field(&mut tensor, 2, FLOAT);
+            str_field(&mut tensor, 8, name);
+            let raw: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            bytes_field(&mut tensor, 9, &raw);
+            self.initializers.push(tensor);
+            self
+        }
+
+        /// Add an operator node
+        pub fn node(mut self, op_type: &str, inputs: &[&str], outputs: &[&str], attrs: &[(&str, Attr)]) -> Self {
+            let mut node = Vec::new();
+            for input in inputs {
+                str_field(&mut node, 1, input);
+            }
+            for output in outputs {
+                str_field(&mut node, 2, output);
+            }
+            str_field(&mut node, 4, op_type);
+            for (name, attr) in attrs {
+                let mut attr_buf = Vec::new();
+                str_field(&mut attr_buf, 1, name);
+                match attr {
+                    Attr::Float(value) => {
+                        key(&mut attr_buf, 2, 5);
+                        attr_buf.extend_from_slice(&value.to_le_bytes());
+                        int_field(&mut attr_buf, 20, 1);
+                    }
+                    Attr::Int(value) => {
+                        int_field(&mut attr_buf, 3, *value);
+                        int_field(&mut attr_buf, 20, 2);
+                    }
+                    Attr::Ints(values) => {
+                        for value in values {
+                            int_field(&mut attr_buf, 8, *value);
+                        }
+                        int_field(&mut attr_buf, 20, 7);
+                    }
+                }
+                bytes_field(&mut node, 5, &attr_buf);
+            }
+            self.nodes.push(node);
+            self
+        }
+
+        /// Serialize to ONNX model bytes (IR version 8, default opset 13)
+        pub fn build(self) -> Vec<u8> {
+            let mut graph = Vec::new();
+            for node in &self.nodes {
+                bytes_field(&mut graph, 1, node);
+            }
+            str_field(&mut graph, 2, "test_graph");
+            for initializer in &self.initializers {
+                bytes_field(&mut graph, 5, initializer);
+            }
+            for input in &self.inputs {
+                bytes_field(&mut graph, 11, input);
+            }
+            for output in &self.outputs {
+                bytes_field(&mut graph, 12, output);
+            }
+
+            let mut opset = Vec::new();
+            str_field(&mut opset, 1, "");
+            int_field(&mut opset, 2, 13);
+
+            let mut model = Vec::new();
+            int_field(&mut model, 1, 8);
+            str_field(&mut model, 2, "onnx_inference_tests");
+            bytes_field(&mut model, 7, &graph);
+            bytes_field(&mut model, 8, &opset);
+            model
+        }
+    }
+
+    /// Tiny classifier: global average pool over H/W, then a `[3, num_classes]` projection.
+    /// Input is `[1, 3, H, W]` with dynamic spatial dims, output is `[1, num_classes]` logits.
+    pub fn tiny_classifier(num_classes: usize) -> Vec<u8> {
+        let weights: Vec<f32> = (0..3 * num_classes)
+            .map(|i| ((i % 7) as f32 - 3.0) * 0.1 + (i / num_classes) as f32 * 0.05)
+            .collect();
+
+        ModelBuilder::new()
+            .input("input", &[Dim::Fixed(1), Dim::Fixed(3), Dim::Dynamic("height"), Dim::Dynamic("width")])
+            .output("logits", &[Dim::Fixed(1), Dim::Fixed(num_classes as i64)])
+            .initializer("weights", &[3, num_classes as i64], &weights)
+            .node("ReduceMean", &["input"], &["pooled"], &[("axes", Attr::Ints(vec![2, 3])), ("keepdims", Attr::Int(0))])
+            .node("MatMul", &["pooled", "weights"], &["logits"], &[])
+            .build()
+    }
+
+    /// `tiny_classifier` over a single-channel `[1, 1, H, W]` input
+    pub fn grayscale_classifier(num_classes: usize) -> Vec<u8> {
+        let weights: Vec<f32> = (0..num_classes).map(|i| ((i % 5) as f32 - 2.0) * 0.1).collect();
+
+        ModelBuilder::new()
+            .input("input", &[Dim::Fixed(1), Dim::Fixed(1), Dim::Dynamic("height"), Dim::Dynamic("width")])
+            .output("logits", &[Dim::Fixed(1), Dim::Fixed(num_classes as i64)])
+            .initializer("weights", &[1, num_classes as i64], &weights)
+            .node("ReduceMean", &["input"], &["pooled"], &[("axes", Attr::Ints(vec![2, 3])), ("keepdims", Attr::Int(0))])
+            .node("MatMul", &["pooled", "weights"], &["logits"], &[])
+            .build()
+    }
+
+    /// `tiny_classifier` that also exposes its pooled `[1, 3]` features as a second output, `pooled`
+    pub fn pooled_classifier(num_classes: usize) -> Vec<u8> {
+        let weights: Vec<f32> = (0..3 * num_classes).map(|i| i as f32 * 0.01).collect();
+
+        ModelBuilder::new()
+            .input("input", &[Dim::Fixed(1), Dim::Fixed(3), Dim::Dynamic("height"), Dim::Dynamic("width")])
+            .output("logits", &[Dim::Fixed(1), Dim::Fixed(num_classes as i64)])
+            .output("pooled", &[Dim::Fixed(1), Dim::Fixed(3)])
+            .initializer("weights", &[3, num_classes as i64], &weights)
+            .node("ReduceMean", &["input"], &["pooled"], &[("axes", Attr::Ints(vec![2, 3])), ("keepdims", Attr::Int(0))])
+            .node("MatMul", &["pooled", "weights"], &["logits"], &[])
+            .build()
+    }
+
+    /// Single `Relu` over a fixed `[1, 4]` float input; bundled as assets/nnapi_probe.onnx
+    pub fn nnapi_probe() -> Vec<u8> {
+        ModelBuilder::new()
+            .input("input", &[Dim::Fixed(1), Dim::Fixed(4)])
+            .output("output", &[Dim::Fixed(1), Dim::Fixed(4)])
+            .node("Relu", &["input"], &["output"], &[])
+            .build()
+    }
+}
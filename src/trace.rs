@@ -0,0 +1,97 @@
+/// CSV trace of per-stage timings, one row per completed inference
+use crate::errors::{InferenceError, InferenceResult};
+use crate::types::InferenceResult as InferenceOutput;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Header row written when a trace starts
+const TRACE_HEADER: &str = "timestamp,prep_ms,infer_ms,post_ms,total_ms";
+
+/// Static storage for the open trace file; rows are buffered and written in blocks
+static TRACE: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+
+/// Timing trace recorder
+pub struct TimingTrace;
+
+impl TimingTrace {
+    /// Start tracing into `path` (truncated), replacing and flushing any trace already running
+    pub fn start(path: &str) -> InferenceResult<()> {
+        let file = File::create(path)
+            .map_err(|e| InferenceError::output_processing_failed(format!("Failed to create trace '{}': {}", path, e)))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", TRACE_HEADER)
+            .map_err(|e| InferenceError::output_processing_failed(format!("Failed to write trace '{}': {}", path, e)))?;
+
+        let previous = TRACE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).replace(writer);
+        if let Some(mut previous) = previous {
+            let _ = previous.flush();
+        }
+        Ok(())
+    }
+
+    /// Flush and close the trace; returns false if none was running
+    pub fn stop() -> InferenceResult<bool> {
+        let trace = TRACE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+        match trace {
+            Some(mut writer) => writer
+                .flush()
+                .map(|_| true)
+                .map_err(|e| InferenceError::output_processing_failed(format!("Failed to flush trace: {}", e))),
+            None => Ok(false),
+        }
+    }
+
+    /// Append a row for a completed inference if a trace is running (a failed write ends the trace)
+    pub fn record(result: &InferenceOutput) {
+        let mut trace = TRACE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let Some(writer) = trace.as_mut() else {
+            return;
+        };
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or_default();
+        let row = writeln!(
+            writer,
+            "{},{},{},{},{}",
+            timestamp_ms,
+            result.preprocessing_time_ms,
+            result.inference_time_ms,
+            result.postprocessing_time_ms,
+            result.total_time_ms
+        );
+        if row.is_err() {
+            *trace = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{lock_global_state, write_temp_file};
+
+    #[test]
+    fn test_trace_appends_one_row_per_inference() {
+        let _guard = lock_global_state();
+        let path = write_temp_file("timings.csv", &[]);
+
+        let result = InferenceOutput::new_with_timing(vec![], vec![1, 3], false, vec![], 4.0, 1.5, 0.5);
+        TimingTrace::record(&result);
+        TimingTrace::start(&path).unwrap();
+        TimingTrace::record(&result);
+        TimingTrace::record(&result);
+        assert!(TimingTrace::stop().unwrap());
+        TimingTrace::record(&result);
+        assert!(!TimingTrace::stop().unwrap());
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], TRACE_HEADER);
+        assert!(rows[1].ends_with(",1.5,4,0.5,6"));
+    }
+}
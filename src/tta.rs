@@ -0,0 +1,114 @@
+/// Test-time augmentation: crop generation and averaging of per-crop outputs
+use crate::preprocess::Roi;
+
+/// Side of each crop relative to the image's shorter side (224 of 256, as in standard ten-crop)
+const CROP_FRACTION: f32 = 0.875;
+
+/// Which set of views is classified
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TtaMode {
+    /// Center crop plus the four corner crops
+    FiveCrop,
+    /// The five crops plus their horizontal mirrors
+    TenCrop,
+}
+
+impl TtaMode {
+    /// Map a JNI mode code to a TTA mode
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(TtaMode::FiveCrop),
+            1 => Some(TtaMode::TenCrop),
+            _ => None,
+        }
+    }
+}
+
+/// One augmented view: a square region of the source image, optionally mirrored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TtaView {
+    pub roi: Roi,
+    pub flip: bool,
+}
+
+/// Test-time augmentation helpers
+pub struct Tta;
+
+impl Tta {
+    /// Views for a `width` x `height` image, center crop first
+    pub fn views(width: u32, height: u32, mode: TtaMode) -> Vec<TtaView> {
+        let side = ((width.min(height) as f32 * CROP_FRACTION).round() as u32).max(1);
+        let (right, bottom) = (width - side, height - side);
+        let origins = [(right / 2, bottom / 2), (0, 0), (right, 0), (0, bottom), (right, bottom)];
+        let crops = origins.map(|(x, y)| Roi {
+            x,
+            y,
+            width: side,
+            height: side,
+        });
+
+        let mut views: Vec<TtaView> = crops.iter().map(|&roi| TtaView { roi, flip: false }).collect();
+        if mode == TtaMode::TenCrop {
+            views.extend(crops.iter().map(|&roi| TtaView { roi, flip: true }));
+        }
+        views
+    }
+
+    /// Element-wise mean of equally sized outputs (`None` if empty or the lengths differ)
+    pub fn average(outputs: &[Vec<f32>]) -> Option<Vec<f32>> {
+        Self::weighted_average(outputs, &vec![1.0; outputs.len()])
+    }
+
+    /// Element-wise weighted mean, one weight per output (`None` if empty, the lengths differ,
+    /// the weights don't match the outputs or they sum to zero)
+    pub fn weighted_average(outputs: &[Vec<f32>], weights: &[f32]) -> Option<Vec<f32>> {
+        let first = outputs.first()?;
+        if outputs.iter().any(|output| output.len() != first.len()) || weights.len() != outputs.len() {
+            return None;
+        }
+        let total_weight: f32 = weights.iter().sum();
+        if total_weight <= 0.0 {
+            return None;
+        }
+
+        let mut sum = vec![0.0f32; first.len()];
+        for (output, &weight) in outputs.iter().zip(weights) {
+            for (acc, &value) in sum.iter_mut().zip(output) {
+                *acc += value * weight;
+            }
+        }
+        Some(sum.into_iter().map(|total| total / total_weight).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_views_cover_center_and_corners() {
+        let views = Tta::views(320, 256, TtaMode::TenCrop);
+        assert_eq!(views.len(), 10);
+
+        let side = 224;
+        assert_eq!(views[0].roi, Roi { x: 48, y: 16, width: side, height: side });
+        assert_eq!(views[4].roi, Roi { x: 96, y: 32, width: side, height: side });
+        assert!(views[..5].iter().all(|view| !view.flip));
+        assert!(views[5..].iter().zip(&views[..5]).all(|(mirror, view)| mirror.flip && mirror.roi == view.roi));
+    }
+
+    #[test]
+    fn test_average() {
+        let averaged = Tta::average(&[vec![0.2, 0.8], vec![0.6, 0.4]]).unwrap();
+        assert!((averaged[0] - 0.4).abs() < 1e-6);
+        assert!((averaged[1] - 0.6).abs() < 1e-6);
+
+        assert!(Tta::average(&[]).is_none());
+        assert!(Tta::average(&[vec![1.0], vec![1.0, 2.0]]).is_none());
+
+        let weighted = Tta::weighted_average(&[vec![0.2, 0.8], vec![0.6, 0.4]], &[3.0, 1.0]).unwrap();
+        assert!((weighted[0] - 0.3).abs() < 1e-6);
+        assert!((weighted[1] - 0.7).abs() < 1e-6);
+        assert!(Tta::weighted_average(&[vec![1.0]], &[0.0]).is_none());
+    }
+}
@@ -1,4 +1,10 @@
 /// Data structures for ONNX inference results and classification
+use crate::base64;
+use crate::config::ConfidenceFormat;
+use crate::constants::RESULT_SCHEMA_VERSION;
+use crate::json;
+use crate::postprocess::Postprocessor;
+use crate::preprocess::LetterboxInfo;
 use std::fmt;
 
 /// Represents a single classification result with class information and confidence
@@ -6,7 +12,10 @@ use std::fmt;
 pub struct ClassificationResult {
     pub class_id: usize,
     pub class_name: String,
+    /// Probability in `[0, 1]`
     pub confidence: f32,
+    /// The class's raw logit, set only when softmax was skipped for a large output
+    pub raw_score: Option<f32>,
 }
 
 impl ClassificationResult {
@@ -16,8 +25,21 @@ impl ClassificationResult {
             class_id,
             class_name,
             confidence,
+            raw_score: None,
         }
     }
+
+    /// Serialize as a JSON object with the confidence in `format`, plus `raw_score` when set
+    pub fn to_json(&self, format: ConfidenceFormat) -> String {
+        let raw_score = self.raw_score.map(|score| format!(",\"raw_score\":{}", score)).unwrap_or_default();
+        format!(
+            "{{\"class_id\":{},\"class_name\":\"{}\",\"confidence\":{}{}}}",
+            self.class_id,
+            json::escape(&self.class_name),
+            format.apply(self.confidence),
+            raw_score
+        )
+    }
 }
 
 impl fmt::Display for ClassificationResult {
@@ -32,6 +54,87 @@ impl fmt::Display for ClassificationResult {
     }
 }
 
+/// Summed confidence of one class group
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupPrediction {
+    pub group_id: usize,
+    pub group_name: String,
+    pub confidence: f32,
+}
+
+impl GroupPrediction {
+    /// Serialize as a JSON object with the confidence in `format`
+    pub fn to_json(&self, format: ConfidenceFormat) -> String {
+        format!(
+            "{{\"group_id\":{},\"group_name\":\"{}\",\"confidence\":{}}}",
+            self.group_id,
+            json::escape(&self.group_name),
+            format.apply(self.confidence)
+        )
+    }
+}
+
+/// Top predictions of one head of a multi-head classifier
+#[derive(Debug, Clone)]
+pub struct HeadPredictions {
+    pub head: String,
+    /// Class ids are relative to the head's slice of the output
+    pub predictions: Vec<ClassificationResult>,
+}
+
+impl HeadPredictions {
+    /// Serialize as `{"head":...,"predictions":[...]}` with confidences in `format`
+    pub fn to_json(&self, format: ConfidenceFormat) -> String {
+        let predictions: Vec<String> = self.predictions.iter().map(|p| p.to_json(format)).collect();
+        format!(
+            "{{\"head\":\"{}\",\"predictions\":[{}]}}",
+            json::escape(&self.head),
+            predictions.join(",")
+        )
+    }
+}
+
+/// A detected object box in pixel coordinates with its score and class
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub x1: f32,
+    pub y1: f32,
+    pub x2: f32,
+    pub y2: f32,
+    pub score: f32,
+    pub class_id: usize,
+}
+
+/// An additional model output extracted alongside the primary one
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputTensor {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>,
+}
+
+/// Postprocessing branch that produced a result's top predictions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostprocessMode {
+    /// Softmax over the classes, then top-k
+    TopK,
+    /// Softmax skipped (output over `max_softmax_classes`): the raw argmax only
+    Argmax,
+    /// Not a classification output; `data` is returned as is
+    Raw,
+}
+
+impl PostprocessMode {
+    /// Name reported over JNI
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PostprocessMode::TopK => "topk",
+            PostprocessMode::Argmax => "argmax",
+            PostprocessMode::Raw => "raw",
+        }
+    }
+}
+
 /// Complete inference result containing raw output data and predictions
 #[derive(Debug, Clone)]
 pub struct InferenceResult {
@@ -39,14 +142,51 @@ pub struct InferenceResult {
     pub shape: Vec<usize>,
     pub is_classification: bool,
     pub top_predictions: Vec<ClassificationResult>,
+    /// Branch that ranked `top_predictions`, for comparing latency across postprocessing paths
+    pub postprocess_mode: PostprocessMode,
     pub inference_time_ms: f32,
     pub preprocessing_time_ms: f32,
     pub postprocessing_time_ms: f32,
     pub total_time_ms: f32,
+    /// JNI marshalling time in `runInferenceNative` (input conversion + output array); not part of `total_time_ms`
+    pub jni_time_ms: f32,
+    /// Softmax of `data` for classification outputs; `None` otherwise so raw outputs aren't duplicated
+    pub probabilities: Option<Vec<f32>>,
+    /// Entropy of `probabilities` in nats; higher means a less certain prediction
+    pub entropy: Option<f32>,
+    /// Top-1 minus top-2 probability; a small margin means two classes are nearly tied
+    pub top_margin: Option<f32>,
+    /// Top-1 class of each region of an `[N, C]` proposals output, in region order (only with
+    /// region predictions turned on)
+    pub region_predictions: Option<Vec<ClassificationResult>>,
+    /// Serialized format version (`RESULT_SCHEMA_VERSION`) so consumers can branch on it
+    pub schema_version: u32,
+    /// Letterbox transform used to preprocess this input, for mapping outputs back to the original image
+    pub letterbox: Option<LetterboxInfo>,
+    /// Size of the decoded input image before any crop or resize: the whole image for ROI and TTA
+    /// runs, 0 when a tensor was passed in directly
+    pub orig_width: u32,
+    pub orig_height: u32,
+    /// Caller-supplied id echoed back so results can be matched to the requests that produced them.
+    /// Serialized only when set, so untagged results keep their schema.
+    pub request_id: Option<i64>,
+    /// Served from the result cache (identical input bytes) without running the model
+    pub cached: bool,
+    /// Non-fatal problems with the input, e.g. an image below the minimum resolution. Serialized
+    /// only when there are any, which needs an opted-in check such as a minimum image dimension.
+    pub warnings: Vec<String>,
+    /// Name of the model output `data` was read from (empty if unknown)
+    pub output_name: String,
+    /// Further requested outputs, in request order (empty unless several outputs were requested)
+    pub extra_outputs: Vec<OutputTensor>,
+    /// Shape of every tensor output the run produced, in model order (only the requested ones when
+    /// outputs were requested)
+    pub output_shapes: Vec<(String, Vec<usize>)>,
 }
 
 impl InferenceResult {
     /// Create a new inference result
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         data: Vec<f32>,
         shape: Vec<usize>,
@@ -62,10 +202,27 @@ impl InferenceResult {
             shape,
             is_classification,
             top_predictions,
+            // Classification results are argmax-only until probabilities are attached
+            postprocess_mode: if is_classification { PostprocessMode::Argmax } else { PostprocessMode::Raw },
             inference_time_ms,
             preprocessing_time_ms,
             postprocessing_time_ms,
             total_time_ms,
+            jni_time_ms: 0.0,
+            probabilities: None,
+            entropy: None,
+            top_margin: None,
+            region_predictions: None,
+            schema_version: RESULT_SCHEMA_VERSION,
+            letterbox: None,
+            orig_width: 0,
+            orig_height: 0,
+            request_id: None,
+            cached: false,
+            warnings: Vec::new(),
+            output_name: String::new(),
+            extra_outputs: Vec::new(),
+            output_shapes: Vec::new(),
         }
     }
 
@@ -92,6 +249,21 @@ impl InferenceResult {
         )
     }
 
+    /// Attach softmax probabilities computed from `data`, with their entropy and top-1/top-2 margin
+    pub fn with_probabilities(mut self, probabilities: Vec<f32>) -> Self {
+        self.entropy = Some(Postprocessor::entropy(&probabilities));
+        self.top_margin = Some(Postprocessor::top_margin(&probabilities));
+        self.probabilities = Some(probabilities);
+        self.postprocess_mode = PostprocessMode::TopK;
+        self
+    }
+
+    /// Tag the result with the id of the request that produced it
+    pub fn with_request_id(mut self, request_id: i64) -> Self {
+        self.request_id = Some(request_id);
+        self
+    }
+
     /// Get the number of elements in the output
     pub fn len(&self) -> usize {
         self.data.len()
@@ -102,6 +274,132 @@ impl InferenceResult {
         self.data.is_empty()
     }
 
+    /// Copy with every timing rounded to `decimals` places (`None` leaves them at full precision),
+    /// so telemetry buckets don't split on sub-microsecond noise
+    pub fn with_timings_rounded(mut self, decimals: Option<u32>) -> Self {
+        let Some(decimals) = decimals else {
+            return self;
+        };
+        let factor = 10f64.powi(decimals as i32);
+        let round = |ms: f32| ((f64::from(ms) * factor).round() / factor) as f32;
+        self.inference_time_ms = round(self.inference_time_ms);
+        self.preprocessing_time_ms = round(self.preprocessing_time_ms);
+        self.postprocessing_time_ms = round(self.postprocessing_time_ms);
+        self.total_time_ms = round(self.total_time_ms);
+        self.jni_time_ms = round(self.jni_time_ms);
+        self
+    }
+
+    /// End-to-end latency including JNI marshalling
+    pub fn end_to_end_time_ms(&self) -> f32 {
+        self.total_time_ms + self.jni_time_ms
+    }
+
+    /// Each stage's time in ms and its percentage of `total_time_ms`, in pipeline order. A zero
+    /// total (e.g. a result built without timings) reports 0% for every stage.
+    pub fn stage_shares(&self) -> [(&'static str, f32, f32); 3] {
+        let share = |ms: f32| {
+            if self.total_time_ms > 0.0 {
+                ms / self.total_time_ms * 100.0
+            } else {
+                0.0
+            }
+        };
+        [
+            ("preprocessing", self.preprocessing_time_ms),
+            ("inference", self.inference_time_ms),
+            ("postprocessing", self.postprocessing_time_ms),
+        ]
+        .map(|(stage, ms)| (stage, ms, share(ms)))
+    }
+
+    /// `{"schema_version":N,"total_time_ms":..,"stages":{"preprocessing":{"ms":..,"percent":..},..}}`
+    pub fn timing_breakdown_json(&self) -> String {
+        let stages: Vec<String> = self
+            .stage_shares()
+            .iter()
+            .map(|(stage, ms, percent)| format!("\"{}\":{{\"ms\":{},\"percent\":{}}}", stage, ms, percent))
+            .collect();
+        format!(
+            "{{\"schema_version\":{},\"total_time_ms\":{},\"stages\":{{{}}}}}",
+            self.schema_version,
+            self.total_time_ms,
+            stages.join(",")
+        )
+    }
+
+    /// Top predictions as display strings, e.g. "Class 207 (golden retriever): 83.21%"
+    pub fn formatted_predictions(&self) -> Vec<String> {
+        self.top_predictions.iter().map(ToString::to_string).collect()
+    }
+
+    /// Serialize shape, timings and top predictions (not the raw output values) as a JSON object,
+    /// plus `"cached":true` for results served from the result cache, `warnings` when there are any
+    /// and `request_id` when the result was tagged with one
+    pub fn to_json(&self, confidence_format: ConfidenceFormat) -> String {
+        let predictions: Vec<String> = self
+            .top_predictions
+            .iter()
+            .map(|p| p.to_json(confidence_format))
+            .collect();
+        let shape: Vec<String> = self.shape.iter().map(|dim| dim.to_string()).collect();
+        let request_id = self
+            .request_id
+            .map(|id| format!(",\"request_id\":{}", id))
+            .unwrap_or_default();
+        let cached = if self.cached { ",\"cached\":true" } else { "" };
+        let warnings = if self.warnings.is_empty() {
+            String::new()
+        } else {
+            let items: Vec<String> = self
+                .warnings
+                .iter()
+                .map(|warning| format!("\"{}\"", json::escape(warning)))
+                .collect();
+            format!(",\"warnings\":[{}]", items.join(","))
+        };
+
+        format!(
+            "{{\"schema_version\":{},\"shape\":[{}],\"is_classification\":{},\"predictions\":[{}],\"inference_time_ms\":{},\"preprocessing_time_ms\":{},\"postprocessing_time_ms\":{},\"total_time_ms\":{}{}{}{}}}",
+            self.schema_version,
+            shape.join(","),
+            self.is_classification,
+            predictions.join(","),
+            self.inference_time_ms,
+            self.preprocessing_time_ms,
+            self.postprocessing_time_ms,
+            self.total_time_ms,
+            cached,
+            warnings,
+            request_id
+        )
+    }
+
+    /// `{"schema_version":N,"shapes":{"<output name>":[dims...],...}}`
+    pub fn output_shapes_json(&self) -> String {
+        let shapes: Vec<String> = self
+            .output_shapes
+            .iter()
+            .map(|(name, shape)| {
+                let dims: Vec<String> = shape.iter().map(|dim| dim.to_string()).collect();
+                format!("\"{}\":[{}]", json::escape(name), dims.join(","))
+            })
+            .collect();
+        format!("{{\"schema_version\":{},\"shapes\":{{{}}}}}", self.schema_version, shapes.join(","))
+    }
+
+    /// `{"schema_version":N,"dtype":"float32","byte_order":"little","shape":[..],"data":".."}`, with
+    /// `data` the Base64 of the output's little-endian bytes
+    pub fn raw_output_base64_json(&self) -> String {
+        let dims: Vec<String> = self.shape.iter().map(|dim| dim.to_string()).collect();
+        format!(
+            "{{\"schema_version\":{},\"dtype\":\"float32\",\"byte_order\":\"little\",\"shape\":[{}],\"data\":\"{}\"}}",
+            self.schema_version,
+            dims.join(","),
+            base64::encode(&base64::f32_le_bytes(&self.data))
+        )
+    }
+
     /// Get the top prediction if available
     pub fn top_prediction(&self) -> Option<&ClassificationResult> {
         self.top_predictions.first()
@@ -118,4 +416,390 @@ impl fmt::Display for InferenceResult {
                self.preprocessing_time_ms, self.inference_time_ms, self.postprocessing_time_ms)?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Outcome of running a tiny model on one execution provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProviderProbeReport {
+    pub provider: String,
+    /// The probe model built and produced the expected output on this provider
+    pub available: bool,
+    /// Why the probe failed (`None` when available)
+    pub error: Option<String>,
+    pub elapsed_ms: f32,
+}
+
+impl ProviderProbeReport {
+    /// `{"schema_version":N,"provider":...,"available":...,"error":...|null,"elapsed_ms":...}`
+    pub fn to_json(&self) -> String {
+        let error = self
+            .error
+            .as_deref()
+            .map(|error| format!("\"{}\"", json::escape(error)))
+            .unwrap_or_else(|| "null".to_string());
+        format!(
+            "{{\"schema_version\":{},\"provider\":\"{}\",\"available\":{},\"error\":{},\"elapsed_ms\":{}}}",
+            RESULT_SCHEMA_VERSION,
+            json::escape(&self.provider),
+            self.available,
+            error,
+            self.elapsed_ms
+        )
+    }
+}
+
+/// Accuracy of the active model over a labeled manifest
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EvaluationReport {
+    /// Images that were classified
+    pub evaluated: usize,
+    pub top1_correct: usize,
+    pub top5_correct: usize,
+    /// Manifest entries that could not be evaluated, with the reason
+    pub skipped: Vec<(String, String)>,
+}
+
+impl EvaluationReport {
+    /// Fraction of evaluated images whose top prediction is the true class (0 when none were evaluated)
+    pub fn top1_accuracy(&self) -> f32 {
+        Self::fraction(self.top1_correct, self.evaluated)
+    }
+
+    /// Fraction of evaluated images with the true class among the top five predictions
+    pub fn top5_accuracy(&self) -> f32 {
+        Self::fraction(self.top5_correct, self.evaluated)
+    }
+
+    fn fraction(count: usize, total: usize) -> f32 {
+        if total == 0 { 0.0 } else { count as f32 / total as f32 }
+    }
+
+    /// Serialize counts, accuracies and skipped entries as a JSON object
+    pub fn to_json(&self) -> String {
+        let skipped: Vec<String> = self
+            .skipped
+            .iter()
+            .map(|(entry, reason)| format!("{{\"entry\":\"{}\",\"reason\":\"{}\"}}", json::escape(entry), json::escape(reason)))
+            .collect();
+
+        format!(
+            "{{\"schema_version\":{},\"evaluated\":{},\"top1_accuracy\":{},\"top5_accuracy\":{},\"skipped\":[{}]}}",
+            RESULT_SCHEMA_VERSION,
+            self.evaluated,
+            self.top1_accuracy(),
+            self.top5_accuracy(),
+            skipped.join(",")
+        )
+    }
+}
+
+/// Outcome of preloading a model manifest
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PreloadReport {
+    /// Loaded model paths with the execution provider each session runs on
+    pub loaded: Vec<(String, String)>,
+    /// Manifest entries that failed to load, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+impl PreloadReport {
+    /// `{"schema_version":N,"loaded":[{"path":..,"provider":..}],"failed":[{"entry":..,"error":..}]}`
+    pub fn to_json(&self) -> String {
+        let loaded: Vec<String> = self
+            .loaded
+            .iter()
+            .map(|(path, provider)| format!("{{\"path\":\"{}\",\"provider\":\"{}\"}}", json::escape(path), json::escape(provider)))
+            .collect();
+        let failed: Vec<String> = self
+            .failed
+            .iter()
+            .map(|(entry, error)| format!("{{\"entry\":\"{}\",\"error\":\"{}\"}}", json::escape(entry), json::escape(error)))
+            .collect();
+        format!(
+            "{{\"schema_version\":{},\"loaded\":[{}],\"failed\":[{}]}}",
+            RESULT_SCHEMA_VERSION,
+            loaded.join(","),
+            failed.join(",")
+        )
+    }
+}
+
+/// A cached model as reported by `InferenceEngine::loaded_model_info`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedModelInfo {
+    pub path: String,
+    /// Serialized model size; a rough lower bound on the session's memory use
+    pub size_bytes: usize,
+    /// Whether this is the model `run_inference` currently uses
+    pub active: bool,
+}
+
+/// A model input or output as declared in the graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct TensorInfo {
+    pub name: String,
+    /// ORT's description of the value, e.g. `Tensor<f32>(1, 3, dyn, dyn)`
+    pub value_type: String,
+    /// Declared dims for tensors (-1 = dynamic); `None` for maps/sequences
+    pub shape: Option<Vec<i64>>,
+}
+
+/// Snapshot of a loaded model, captured when its session is created
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInfo {
+    pub path: String,
+    pub inputs: Vec<TensorInfo>,
+    pub outputs: Vec<TensorInfo>,
+    /// Default-domain opset imported by the model (`None` if it couldn't be read)
+    pub opset: Option<i64>,
+    /// Producer name from the model metadata (empty if unset)
+    pub producer: String,
+    /// Execution provider the session registered
+    pub provider: String,
+}
+
+impl ModelInfo {
+    /// Serialize as a JSON object
+    pub fn to_json(&self) -> String {
+        let tensors = |infos: &[TensorInfo]| {
+            infos
+                .iter()
+                .map(|info| {
+                    let shape = match &info.shape {
+                        Some(dims) => format!("[{}]", dims.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(",")),
+                        None => "null".to_string(),
+                    };
+                    format!(
+                        "{{\"name\":\"{}\",\"type\":\"{}\",\"shape\":{}}}",
+                        json::escape(&info.name),
+                        json::escape(&info.value_type),
+                        shape
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let opset = self.opset.map_or_else(|| "null".to_string(), |opset| opset.to_string());
+
+        format!(
+            "{{\"schema_version\":{},\"path\":\"{}\",\"inputs\":[{}],\"outputs\":[{}],\"opset\":{},\"producer\":\"{}\",\"provider\":\"{}\"}}",
+            RESULT_SCHEMA_VERSION,
+            json::escape(&self.path),
+            tensors(&self.inputs),
+            tensors(&self.outputs),
+            opset,
+            json::escape(&self.producer),
+            json::escape(&self.provider)
+        )
+    }
+}
+
+/// Inference latency statistics from `InferenceEngine::benchmark`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkStats {
+    pub iterations: usize,
+    pub min_ms: f32,
+    pub mean_ms: f32,
+    pub p50_ms: f32,
+    pub p95_ms: f32,
+    pub max_ms: f32,
+}
+
+impl BenchmarkStats {
+    /// Summarize per-run times in milliseconds (percentiles use the nearest-rank method)
+    pub fn from_samples(mut samples: Vec<f32>) -> Self {
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let count = samples.len();
+        let percentile = |p: f32| {
+            let rank = ((p / 100.0) * count as f32).ceil() as usize;
+            samples.get(rank.clamp(1, count.max(1)) - 1).copied().unwrap_or(0.0)
+        };
+
+        Self {
+            iterations: count,
+            min_ms: samples.first().copied().unwrap_or(0.0),
+            mean_ms: if count > 0 { samples.iter().sum::<f32>() / count as f32 } else { 0.0 },
+            p50_ms: percentile(50.0),
+            p95_ms: percentile(95.0),
+            max_ms: samples.last().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+/// Summary of a preprocessed input tensor, for checking normalization at a glance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TensorStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    /// Population standard deviation
+    pub std: f32,
+}
+
+impl TensorStats {
+    /// Compute all four statistics in a single pass (`None` for an empty tensor).
+    /// Sums are accumulated in f64 so the variance doesn't cancel out on large inputs.
+    pub fn from_values(values: &[f32]) -> Option<Self> {
+        let first = *values.first()?;
+        let (mut min, mut max, mut sum, mut sum_sq) = (first, first, 0.0f64, 0.0f64);
+        for &value in values {
+            min = min.min(value);
+            max = max.max(value);
+            sum += f64::from(value);
+            sum_sq += f64::from(value) * f64::from(value);
+        }
+
+        let count = values.len() as f64;
+        let mean = sum / count;
+        Some(Self {
+            min,
+            max,
+            mean: mean as f32,
+            std: (sum_sq / count - mean * mean).max(0.0).sqrt() as f32,
+        })
+    }
+
+    /// `{"schema_version":N,"min":...,"max":...,"mean":...,"std":...}`
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"schema_version\":{},\"min\":{},\"max\":{},\"mean\":{},\"std\":{}}}",
+            RESULT_SCHEMA_VERSION, self.min, self.max, self.mean, self.std
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_confidence_json() {
+        let prediction = ClassificationResult::new(207, "golden retriever".to_string(), 0.75);
+        assert_eq!(
+            prediction.to_json(ConfidenceFormat::Fraction),
+            "{\"class_id\":207,\"class_name\":\"golden retriever\",\"confidence\":0.75}"
+        );
+        assert_eq!(
+            prediction.to_json(ConfidenceFormat::Percent),
+            "{\"class_id\":207,\"class_name\":\"golden retriever\",\"confidence\":75}"
+        );
+    }
+
+    #[test]
+    fn test_formatted_predictions() {
+        let result = InferenceResult::new(
+            vec![],
+            vec![1, 1000],
+            true,
+            vec![
+                ClassificationResult::new(207, "golden retriever".to_string(), 0.83214),
+                ClassificationResult::new(3, "tiger shark".to_string(), 0.05),
+            ],
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+        );
+        assert_eq!(
+            result.formatted_predictions(),
+            vec!["Class 207 (golden retriever): 83.21%", "Class 3 (tiger shark): 5.00%"]
+        );
+    }
+
+    #[test]
+    fn test_timings_round_to_configured_places() {
+        let result = InferenceResult::new(vec![], vec![1, 3], false, vec![], 12.34567, 0.004, 1.0, 13.34967);
+
+        let rounded = result.clone().with_timings_rounded(Some(2));
+        assert_eq!(rounded.inference_time_ms, 12.35);
+        assert_eq!(rounded.preprocessing_time_ms, 0.0);
+        assert_eq!(rounded.total_time_ms, 13.35);
+        assert!(rounded.to_json(ConfidenceFormat::Fraction).contains("\"inference_time_ms\":12.35,"));
+
+        assert_eq!(result.clone().with_timings_rounded(Some(0)).inference_time_ms, 12.0);
+        assert_eq!(result.clone().with_timings_rounded(None).inference_time_ms, result.inference_time_ms);
+    }
+
+    #[test]
+    fn test_stage_shares_sum_to_100() {
+        let result = InferenceResult::new_with_timing(vec![], vec![1, 3], false, vec![], 7.5, 1.75, 0.75);
+        let shares = result.stage_shares();
+        let total: f32 = shares.iter().map(|&(_, _, percent)| percent).sum();
+        assert!((total - 100.0).abs() < 1e-3);
+        assert_eq!(shares[1], ("inference", 7.5, 75.0));
+
+        let untimed = InferenceResult::new(vec![], vec![1, 3], false, vec![], 0.0, 0.0, 0.0, 0.0);
+        assert!(untimed.stage_shares().iter().all(|&(_, _, percent)| percent == 0.0));
+        assert!(untimed.timing_breakdown_json().contains("\"inference\":{\"ms\":0,\"percent\":0}"));
+    }
+
+    #[test]
+    fn test_request_id_in_json() {
+        let result = InferenceResult::new(vec![], vec![1, 3], false, vec![], 0.0, 0.0, 0.0, 0.0);
+        assert!(!result.to_json(ConfidenceFormat::Fraction).contains("request_id"));
+
+        let tagged = result.with_request_id(-9_000_000_001);
+        assert!(tagged.to_json(ConfidenceFormat::Fraction).ends_with(",\"request_id\":-9000000001}"));
+    }
+
+    #[test]
+    fn test_warnings_in_json_only_when_present() {
+        let mut result = InferenceResult::new(vec![], vec![1, 3], false, vec![], 0.0, 0.0, 0.0, 0.0);
+        assert!(!result.to_json(ConfidenceFormat::Fraction).contains("warnings"));
+
+        result.warnings.push("Low-resolution input: \"16x16\"".to_string());
+        assert!(result.to_json(ConfidenceFormat::Fraction).ends_with(",\"warnings\":[\"Low-resolution input: \\\"16x16\\\"\"]}"));
+    }
+
+    #[test]
+    fn test_provider_probe_json_uses_result_schema_version() {
+        let report = ProviderProbeReport {
+            provider: "nnapi".to_string(),
+            available: false,
+            error: Some("not supported\n".to_string()),
+            elapsed_ms: 1.5,
+        };
+        assert_eq!(
+            report.to_json(),
+            format!(
+                "{{\"schema_version\":{},\"provider\":\"nnapi\",\"available\":false,\"error\":\"not supported\\n\",\"elapsed_ms\":1.5}}",
+                RESULT_SCHEMA_VERSION
+            )
+        );
+    }
+
+    #[test]
+    fn test_output_shapes_json() {
+        let mut result = InferenceResult::new(vec![], vec![1, 10], true, vec![], 0.0, 0.0, 0.0, 0.0);
+        assert_eq!(result.output_shapes_json(), format!("{{\"schema_version\":{},\"shapes\":{{}}}}", RESULT_SCHEMA_VERSION));
+
+        result.output_shapes = vec![("logits".to_string(), vec![1, 10]), ("pooled".to_string(), vec![1, 3])];
+        assert_eq!(
+            result.output_shapes_json(),
+            format!("{{\"schema_version\":{},\"shapes\":{{\"logits\":[1,10],\"pooled\":[1,3]}}}}", RESULT_SCHEMA_VERSION)
+        );
+    }
+
+    #[test]
+    fn test_tensor_stats_of_tiny_input() {
+        let stats = TensorStats::from_values(&[-1.0, 0.5, 2.0, 2.5]).unwrap();
+        assert_eq!((stats.min, stats.max, stats.mean), (-1.0, 2.5, 1.0));
+        // Squared deviations 4, 0.25, 1, 2.25 average to 1.875
+        assert!((stats.std - 1.875f32.sqrt()).abs() < 1e-6);
+        assert_eq!(stats.to_json(), format!("{{\"schema_version\":{},\"min\":-1,\"max\":2.5,\"mean\":1,\"std\":{}}}", RESULT_SCHEMA_VERSION, stats.std));
+
+        assert!(TensorStats::from_values(&[]).is_none());
+    }
+
+    #[test]
+    fn test_benchmark_stats_are_ordered() {
+        let samples: Vec<f32> = (1..=20).rev().map(|ms| ms as f32).collect();
+        let stats = BenchmarkStats::from_samples(samples);
+
+        assert_eq!(stats.iterations, 20);
+        assert_eq!((stats.min_ms, stats.max_ms), (1.0, 20.0));
+        assert_eq!(stats.mean_ms, 10.5);
+        assert_eq!((stats.p50_ms, stats.p95_ms), (10.0, 19.0));
+        assert!(stats.min_ms <= stats.p50_ms && stats.p50_ms <= stats.p95_ms && stats.p95_ms <= stats.max_ms);
+    }
+}
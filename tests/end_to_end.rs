@@ -0,0 +1,49 @@
+//! End-to-end test of the Rust API (no JNI): load a bundled tiny classifier and run it on the host.
+//!
+//! `tests/fixtures/tiny_classifier.onnx` is a 1000-class model (global average pool + MatMul)
+//! produced by `test_utils::onnx::tiny_classifier(1000)`. ORT is loaded dynamically, so these
+//! tests are ignored by default and fail without an ONNX Runtime shared library; run them with
+//! `ORT_DYLIB_PATH=/path/to/libonnxruntime.so cargo test --test end_to_end -- --ignored`.
+
+use image::{ImageBuffer, ImageFormat, Rgb};
+use onnx_inference::InferenceEngine;
+use std::io::Cursor;
+
+const MODEL_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/tiny_classifier.onnx");
+
+fn ort_available() -> bool {
+    std::env::var("ORT_DYLIB_PATH")
+        .map(|path| std::path::Path::new(&path).exists())
+        .unwrap_or(false)
+}
+
+fn solid_color_png(width: u32, height: u32, rgb: [u8; 3]) -> Vec<u8> {
+    let image = ImageBuffer::from_pixel(width, height, Rgb(rgb));
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .expect("PNG encoding should succeed");
+    bytes
+}
+
+#[test]
+#[ignore = "needs ONNX Runtime: set ORT_DYLIB_PATH and run with --ignored"]
+fn test_load_and_classify_solid_color_image() {
+    assert!(ort_available(), "ONNX Runtime not available: set ORT_DYLIB_PATH to libonnxruntime");
+
+    InferenceEngine::load_model(MODEL_PATH).expect("fixture model should load");
+    assert!(InferenceEngine::is_model_loaded());
+    assert_eq!(InferenceEngine::get_loaded_model_path().as_deref(), Some(MODEL_PATH));
+
+    let result = InferenceEngine::run_inference(&solid_color_png(320, 240, [200, 80, 40]))
+        .expect("inference should succeed");
+
+    assert_eq!(result.shape, vec![1, 1000]);
+    assert_eq!(result.len(), 1000);
+    assert!(result.is_classification);
+    assert!(!result.top_predictions.is_empty());
+
+    let probabilities = result.probabilities.expect("classifier output should carry probabilities");
+    let sum: f32 = probabilities.iter().sum();
+    assert!((sum - 1.0).abs() < 1e-4, "probabilities sum to {}", sum);
+}